@@ -0,0 +1,9 @@
+use rucc::lsp::Server;
+
+fn main() {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    let exit_code = Server::new(stdin.lock(), stdout.lock()).run();
+    std::process::exit(exit_code);
+}
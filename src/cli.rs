@@ -0,0 +1,128 @@
+use crate::codegen::printer::Syntax;
+use crate::common::error::*;
+use crate::ir::opt::OptLevel;
+use crate::typechecker::Std;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Emit {
+    Asm,
+    Ir,
+    CfgDot,
+    Object,
+}
+
+// format of the --dump-* options
+#[derive(PartialEq, Clone, Copy)]
+pub enum DumpFormat {
+    Text,
+    Json,
+}
+
+pub struct Cli {
+    pub file: String,
+    pub emit: Emit,
+    pub opt_level: OptLevel,
+    pub syntax: Syntax,
+    pub std: Std,
+    pub debug: bool,
+    pub asm_comments: bool,
+    pub dump_ast: Option<DumpFormat>,
+    pub dump_tokens: Option<DumpFormat>,
+    pub print_passes: bool,
+    pub time_passes: bool,
+}
+impl Cli {
+    pub fn parse(args: &[String]) -> Self {
+        let mut file = None;
+        let mut emit = Emit::Asm;
+        let mut opt_level = OptLevel::O0;
+        let mut syntax = Syntax::Att;
+        let mut std = Std::C99;
+        let mut debug = false;
+        let mut asm_comments = false;
+        let mut dump_ast = None;
+        let mut dump_tokens = None;
+        let mut print_passes = false;
+        let mut time_passes = false;
+
+        for arg in args {
+            match arg.as_str() {
+                "--emit=asm" => emit = Emit::Asm,
+                "--emit=ir" => emit = Emit::Ir,
+                "--emit=cfg-dot" => emit = Emit::CfgDot,
+                "--emit=obj" | "-c" => emit = Emit::Object,
+                "-O0" => opt_level = OptLevel::O0,
+                "-O" | "-O1" => opt_level = OptLevel::O1,
+                "-O2" => opt_level = OptLevel::O2,
+                "-masm=att" => syntax = Syntax::Att,
+                "-masm=intel" => syntax = Syntax::Intel,
+                "-std=c89" => std = Std::C89,
+                "-std=c99" => std = Std::C99,
+                "-g" => debug = true,
+                "--asm-comments" => asm_comments = true,
+                "--dump-ast" => dump_ast = Some(DumpFormat::Text),
+                "--dump-ast=json" => dump_ast = Some(DumpFormat::Json),
+                "--dump-tokens" => dump_tokens = Some(DumpFormat::Text),
+                "--dump-tokens=json" => dump_tokens = Some(DumpFormat::Json),
+                "--print-passes" => print_passes = true,
+                "--time-passes" => time_passes = true,
+                _ if arg.starts_with('-') => {
+                    Error::sys_exit(&format!("unknown option: '{}'\n{}", arg, USAGE), 22)
+                }
+                _ if file.is_none() => file = Some(arg.clone()),
+                _ => Error::sys_exit(USAGE, 22),
+            }
+        }
+        // the built-in assembler can't generate the line-table from .loc directives
+        if debug && emit == Emit::Object {
+            Error::sys_exit("debug-info is only supported when emitting assembly", 22)
+        }
+        Cli {
+            file: file.unwrap_or_else(|| Error::sys_exit(USAGE, 22)),
+            emit,
+            opt_level,
+            syntax,
+            std,
+            debug,
+            asm_comments,
+            dump_ast,
+            dump_tokens,
+            print_passes,
+            time_passes,
+        }
+    }
+}
+
+// arguments of the 'rucc fmt' subcommand
+pub struct FmtCli {
+    pub file: String,
+    // only report if the file isn't formatted instead of rewriting it
+    pub check: bool,
+}
+impl FmtCli {
+    pub fn parse(args: &[String]) -> Self {
+        let mut file = None;
+        let mut check = false;
+
+        for arg in args {
+            match arg.as_str() {
+                "--check" => check = true,
+                _ if arg.starts_with('-') => {
+                    Error::sys_exit(&format!("unknown option: '{}'\n{}", arg, FMT_USAGE), 22)
+                }
+                _ if file.is_none() => file = Some(arg.clone()),
+                _ => Error::sys_exit(FMT_USAGE, 22),
+            }
+        }
+        FmtCli {
+            file: file.unwrap_or_else(|| Error::sys_exit(FMT_USAGE, 22)),
+            check,
+        }
+    }
+}
+
+static FMT_USAGE: &str = "usage: rucc fmt [--check] <file>";
+static USAGE: &str = "usage: rucc [--emit=asm|ir|cfg-dot|obj] [-c] [-O0|-O1|-O2] \
+                     [-masm=att|intel] [-std=c89|c99] [-g] [--asm-comments] [--dump-tokens[=json]] \
+                     [--dump-ast[=json]] [--print-passes] [--time-passes] <file>\n       \
+                     rucc fmt [--check] <file>";
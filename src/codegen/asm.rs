@@ -0,0 +1,282 @@
+use crate::common::types::*;
+
+// size of an operand, named after the instruction-suffix it uses
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Size {
+    Byte,
+    Long,
+    Quad,
+}
+impl Size {
+    pub fn of(type_decl: &NEWTypes) -> Size {
+        match type_decl.complete_suffix() {
+            "byte" => Size::Byte,
+            "long" => Size::Long,
+            _ => Size::Quad,
+        }
+    }
+    // spelled out the same way as the type's complete_suffix
+    pub fn name(self) -> &'static str {
+        match self {
+            Size::Byte => "byte",
+            Size::Long => "long",
+            Size::Quad => "quad",
+        }
+    }
+    pub fn suffix(self) -> char {
+        match self {
+            Size::Byte => 'b',
+            Size::Long => 'l',
+            Size::Quad => 'q',
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reg {
+    Rax,
+    Rcx,
+    Rdx,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    R11,
+    Rbp,
+    Rsp,
+}
+impl Reg {
+    pub fn name(self, size: Size) -> &'static str {
+        // names for byte, long and quad sized access
+        let names = match self {
+            Reg::Rax => ["al", "eax", "rax"],
+            Reg::Rcx => ["cl", "ecx", "rcx"],
+            Reg::Rdx => ["dl", "edx", "rdx"],
+            Reg::Rsi => ["sil", "esi", "rsi"],
+            Reg::Rdi => ["dil", "edi", "rdi"],
+            Reg::R8 => ["r8b", "r8d", "r8"],
+            Reg::R9 => ["r9b", "r9d", "r9"],
+            Reg::R10 => ["r10b", "r10d", "r10"],
+            Reg::R11 => ["r11b", "r11d", "r11"],
+            Reg::Rbp => ["bpl", "ebp", "rbp"],
+            Reg::Rsp => ["spl", "esp", "rsp"],
+        };
+        names[size as usize]
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Operand {
+    Reg(Reg, Size),
+    Imm(i64),
+    // memory at the address in base plus offset
+    Mem { base: Reg, offset: i64, size: Size },
+    // memory at a symbol, addressed relative to %rip
+    Label(String, Size),
+    // the symbol's entry in the global-offset-table which holds its address
+    Got(String),
+}
+impl Operand {
+    // immediates take on the size of the instruction they're used in
+    pub fn size(&self) -> Option<Size> {
+        match self {
+            Operand::Reg(_, size) | Operand::Mem { size, .. } | Operand::Label(_, size) => {
+                Some(*size)
+            }
+            Operand::Got(_) => Some(Size::Quad),
+            Operand::Imm(_) => None,
+        }
+    }
+    pub fn reg(&self) -> Option<Reg> {
+        match self {
+            Operand::Reg(reg, _) => Some(*reg),
+            _ => None,
+        }
+    }
+    pub fn is_mem(&self) -> bool {
+        matches!(
+            self,
+            Operand::Mem { .. } | Operand::Label(..) | Operand::Got(_)
+        )
+    }
+    // registers which are read to compute the operand's value or address
+    pub fn uses(&self, reg: Reg) -> bool {
+        match self {
+            Operand::Reg(r, _) | Operand::Mem { base: r, .. } => *r == reg,
+            Operand::Imm(_) | Operand::Label(..) | Operand::Got(_) => false,
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Imul,
+    And,
+    Or,
+    Xor,
+}
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShiftOp {
+    Sal,
+    Sar,
+}
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cond {
+    E,
+    Ne,
+    L,
+    Le,
+    G,
+    Ge,
+}
+impl Cond {
+    pub fn name(self) -> &'static str {
+        match self {
+            Cond::E => "e",
+            Cond::Ne => "ne",
+            Cond::L => "l",
+            Cond::Le => "le",
+            Cond::G => "g",
+            Cond::Ge => "ge",
+        }
+    }
+    pub fn inverse(self) -> Cond {
+        match self {
+            Cond::E => Cond::Ne,
+            Cond::Ne => Cond::E,
+            Cond::L => Cond::Ge,
+            Cond::Le => Cond::G,
+            Cond::G => Cond::Le,
+            Cond::Ge => Cond::L,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolType {
+    Function,
+    Object,
+}
+
+// assembler directives which lay out the data and sections around the code
+#[derive(Clone, PartialEq, Debug)]
+pub enum Directive {
+    Text,
+    Data,
+    // zero-initialized data which only takes up space once loaded
+    Bss,
+    // data which is never written to
+    Rodata,
+    Globl(String),
+    // null-terminated string, already escaped
+    String(String),
+    // integer constant of the given size
+    Value(Size, i64),
+    // address of a label
+    Address(String),
+    Zero(usize),
+    // pads the section until its offset is a multiple of the alignment
+    Align(usize),
+    // what the symbol refers to
+    Type(String, SymbolType),
+    // the symbol spans from its label to this directive
+    Size(String),
+    // named section, only used for debug-info
+    Section(String),
+    // source file and line of the following instructions
+    File(String),
+    Loc(usize),
+    // offset of a label into its section
+    Offset(String),
+    // distance between two labels, end minus start
+    Difference(Size, String, String),
+}
+
+// the subset of x86-64 instructions the codegen emits, operands are in AT&T order
+#[derive(Clone, PartialEq, Debug)]
+pub enum Instr {
+    Mov(Operand, Operand),
+    // sign extends the source into the larger destination
+    Movs(Operand, Operand),
+    // zero extends the source into the larger destination
+    Movz(Operand, Operand),
+    Lea(Operand, Operand),
+    Alu(AluOp, Operand, Operand),
+    Shift(ShiftOp, Operand, Operand),
+    Neg(Operand),
+    Not(Operand),
+    Cmp(Operand, Operand),
+    Test(Operand, Operand),
+    Set(Cond, Operand),
+    // sign extends %eax/%rax into %edx/%rdx
+    Cdq,
+    Cqo,
+    Idiv(Operand),
+    Push(Reg),
+    Pop(Reg),
+    Jmp(String),
+    Jcc(Cond, String),
+    // bool is to indicate if the callee is variadic and reads the amount of
+    // vector-registers used from %al
+    Call(String, bool),
+    // calls the function whose address is held by the register
+    CallIndirect(Reg, bool),
+    Ret,
+    Label(String),
+    Directive(Directive),
+    Comment(String),
+}
+impl Instr {
+    // returns true if the instruction reads the register
+    pub fn reads(&self, reg: Reg) -> bool {
+        match self {
+            Instr::Mov(src, dest)
+            | Instr::Movs(src, dest)
+            | Instr::Movz(src, dest)
+            | Instr::Lea(src, dest) => src.uses(reg) || (dest.is_mem() && dest.uses(reg)),
+            Instr::Alu(_, src, dest)
+            | Instr::Shift(_, src, dest)
+            | Instr::Cmp(src, dest)
+            | Instr::Test(src, dest) => src.uses(reg) || dest.uses(reg),
+            Instr::Neg(op) | Instr::Not(op) => op.uses(reg),
+            Instr::Set(_, op) => op.is_mem() && op.uses(reg),
+            Instr::Cdq | Instr::Cqo => reg == Reg::Rax,
+            Instr::Idiv(op) => op.uses(reg) || reg == Reg::Rax || reg == Reg::Rdx,
+            Instr::Push(r) => *r == reg,
+            Instr::Pop(_)
+            | Instr::Jmp(_)
+            | Instr::Jcc(..)
+            | Instr::Label(_)
+            | Instr::Directive(_)
+            | Instr::Comment(_) => false,
+            // arguments are passed in registers
+            Instr::Call(_, variadic) | Instr::CallIndirect(_, variadic) => {
+                matches!(self, Instr::CallIndirect(target, _) if *target == reg)
+                    || (*variadic && reg == Reg::Rax)
+                    || matches!(
+                        reg,
+                        Reg::Rdi | Reg::Rsi | Reg::Rdx | Reg::Rcx | Reg::R8 | Reg::R9
+                    )
+            }
+            Instr::Ret => reg == Reg::Rax,
+        }
+    }
+    // returns true if the instruction overwrites the whole register without reading it
+    pub fn kills(&self, reg: Reg) -> bool {
+        let full_write =
+            |op: &Operand| matches!(op, Operand::Reg(r, Size::Long | Size::Quad) if *r == reg);
+        match self {
+            Instr::Mov(_, dest)
+            | Instr::Movs(_, dest)
+            | Instr::Movz(_, dest)
+            | Instr::Lea(_, dest) => !self.reads(reg) && full_write(dest),
+            Instr::Cdq | Instr::Cqo => reg == Reg::Rdx,
+            Instr::Pop(r) => *r == reg,
+            // scratch-registers are caller-saved so they don't survive a call
+            Instr::Call(..) | Instr::CallIndirect(..) => !self.reads(reg),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use crate::codegen::{asm::*, encoder::*};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SectionKind {
+    Text,
+    Data,
+    Bss,
+    Rodata,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RelocTarget {
+    Symbol(String),
+    // labels which aren't visible outside of the object are relative to their section
+    Section(SectionKind),
+}
+#[derive(Clone, PartialEq, Debug)]
+pub struct Reloc {
+    pub offset: usize,
+    pub target: RelocTarget,
+    pub kind: RelocKind,
+    pub addend: i64,
+}
+
+pub struct Section {
+    pub kind: SectionKind,
+    // largest alignment of any object in the section
+    pub align: usize,
+    // .bss only has a size so its bytes are all zero
+    pub bytes: Vec<u8>,
+    pub relocs: Vec<Reloc>,
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub section: SectionKind,
+    pub offset: usize,
+    pub global: bool,
+    pub kind: Option<SymbolType>,
+    pub size: usize,
+}
+
+pub struct Object {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+}
+impl Object {
+    pub fn section(&self, kind: SectionKind) -> &Section {
+        self.sections
+            .iter()
+            .find(|s| s.kind == kind)
+            .expect("every section exists")
+    }
+}
+
+// code and data which ends up in one of the sections, laid out in order
+enum Item<'a> {
+    Label(&'a str),
+    Jump(Option<Cond>, &'a str),
+    Encoded(Encoding),
+    Bytes(Vec<u8>),
+    Address(&'a str),
+    Align(usize),
+    // end of the symbol's data
+    End(&'a str),
+}
+
+// the section a label belongs to depends on the data following it
+fn label_section(following: &[Instr], current: SectionKind) -> SectionKind {
+    let content: Vec<&Instr> = following
+        .iter()
+        .take_while(|instr| {
+            !matches!(
+                instr,
+                Instr::Label(_)
+                    | Instr::Directive(
+                        Directive::Text | Directive::Data | Directive::Bss | Directive::Rodata
+                    )
+            )
+        })
+        .collect();
+
+    match content.first() {
+        Some(Instr::Directive(Directive::String(_))) => SectionKind::Rodata,
+        Some(_)
+            if current == SectionKind::Data
+                && content.iter().all(|instr| {
+                    matches!(
+                        instr,
+                        Instr::Directive(Directive::Zero(_) | Directive::Size(_))
+                    )
+                }) =>
+        {
+            SectionKind::Bss
+        }
+        _ => current,
+    }
+}
+
+fn item_size(item: &Item, short: bool, offset: usize) -> usize {
+    match item {
+        Item::Align(align) => offset.next_multiple_of(*align) - offset,
+        Item::Label(_) | Item::End(_) => 0,
+        Item::Jump(cond, _) => jump_size(*cond, short),
+        Item::Encoded(encoding) => encoding.bytes.len(),
+        Item::Bytes(bytes) => bytes.len(),
+        Item::Address(_) => 8,
+    }
+}
+
+// computes the offset of every item, jumps are short unless marked as long
+fn layout(items: &[Item], long_jumps: &HashSet<usize>) -> (Vec<usize>, HashMap<String, usize>) {
+    let mut offsets = Vec::with_capacity(items.len());
+    let mut labels = HashMap::new();
+    let mut offset = 0;
+
+    for (i, item) in items.iter().enumerate() {
+        offsets.push(offset);
+        if let Item::Label(name) = item {
+            labels.insert(name.to_string(), offset);
+        }
+        offset += item_size(item, !long_jumps.contains(&i), offset);
+    }
+    (offsets, labels)
+}
+
+// encodes the instructions into sections, resolving jumps and recording relocations
+// for everything else which refers to a label
+pub fn assemble(instrs: &[Instr]) -> Object {
+    let kinds = [
+        SectionKind::Text,
+        SectionKind::Data,
+        SectionKind::Bss,
+        SectionKind::Rodata,
+    ];
+    let mut items: HashMap<SectionKind, Vec<Item>> =
+        kinds.iter().map(|kind| (*kind, Vec::new())).collect();
+    let mut globals = HashSet::new();
+    let mut alignment = None;
+    let mut types = HashMap::new();
+
+    // assembly starts out in the text-section
+    let mut current = SectionKind::Text;
+    let mut section = current;
+    for (i, instr) in instrs.iter().enumerate() {
+        let item = match instr {
+            Instr::Directive(Directive::Text) => {
+                (current, section) = (SectionKind::Text, SectionKind::Text);
+                continue;
+            }
+            Instr::Directive(Directive::Data) => {
+                (current, section) = (SectionKind::Data, SectionKind::Data);
+                continue;
+            }
+            Instr::Directive(Directive::Bss) => {
+                (current, section) = (SectionKind::Bss, SectionKind::Bss);
+                continue;
+            }
+            Instr::Directive(Directive::Rodata) => {
+                (current, section) = (SectionKind::Rodata, SectionKind::Rodata);
+                continue;
+            }
+            Instr::Directive(Directive::Globl(name)) => {
+                globals.insert(name.as_str());
+                continue;
+            }
+            Instr::Directive(Directive::Align(align)) => {
+                // the padding belongs to the section of the object following it
+                alignment = Some(*align);
+                continue;
+            }
+            Instr::Directive(Directive::Type(name, kind)) => {
+                types.insert(name.as_str(), *kind);
+                continue;
+            }
+            Instr::Directive(Directive::Size(name)) => Item::End(name),
+            Instr::Comment(_) => continue,
+            Instr::Label(name) => {
+                section = label_section(&instrs[i + 1..], current);
+                if let Some(align) = alignment.take() {
+                    items.get_mut(&section).unwrap().push(Item::Align(align));
+                }
+                Item::Label(name)
+            }
+            Instr::Directive(Directive::String(data)) => {
+                // string-data is already decoded, every char is a single byte
+                let mut bytes: Vec<u8> = data.chars().map(|c| c as u8).collect();
+                bytes.push(0);
+                Item::Bytes(bytes)
+            }
+            Instr::Directive(Directive::Value(size, n)) => Item::Bytes(
+                n.to_le_bytes()[..match size {
+                    Size::Byte => 1,
+                    Size::Long => 4,
+                    Size::Quad => 8,
+                }]
+                    .to_vec(),
+            ),
+            Instr::Directive(Directive::Address(label)) => Item::Address(label),
+            Instr::Directive(Directive::Zero(n)) => Item::Bytes(vec![0; *n]),
+            Instr::Directive(
+                Directive::Section(_)
+                | Directive::File(_)
+                | Directive::Loc(_)
+                | Directive::Offset(_)
+                | Directive::Difference(..),
+            ) => unreachable!("debug-info is only emitted as assembly"),
+            Instr::Jmp(label) => Item::Jump(None, label),
+            Instr::Jcc(cond, label) => Item::Jump(Some(*cond), label),
+            _ => Item::Encoded(encode(instr)),
+        };
+        items.get_mut(&section).unwrap().push(item);
+    }
+
+    // jumps start out short and only get longer until every displacement fits
+    let text = &items[&SectionKind::Text];
+    let mut long_jumps = HashSet::new();
+    loop {
+        let (offsets, labels) = layout(text, &long_jumps);
+        let mut changed = false;
+
+        for (i, item) in text.iter().enumerate() {
+            if let Item::Jump(cond, target) = item {
+                let end = offsets[i] + jump_size(*cond, true);
+                let disp = labels[*target] as i64 - end as i64;
+                if !long_jumps.contains(&i) && i8::try_from(disp).is_err() {
+                    long_jumps.insert(i);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // every label and where it ended up
+    let mut locations: HashMap<&str, (SectionKind, usize)> = HashMap::new();
+    let mut sizes = HashMap::new();
+    let mut layouts = HashMap::new();
+    for kind in kinds {
+        // only the text-section contains jumps
+        let (offsets, _) = layout(&items[&kind], &long_jumps);
+        for (item, offset) in items[&kind].iter().zip(offsets.iter()) {
+            match item {
+                Item::Label(name) => {
+                    locations.insert(name, (kind, *offset));
+                }
+                Item::End(name) => {
+                    sizes.insert(*name, *offset - locations[name].1);
+                }
+                _ => (),
+            }
+        }
+        layouts.insert(kind, offsets);
+    }
+    let reloc =
+        |offset: usize, label: &str, kind: RelocKind, addend: i64| match locations.get(label) {
+            Some((section, label_offset)) if !globals.contains(label) => Reloc {
+                offset,
+                target: RelocTarget::Section(*section),
+                kind,
+                addend: addend + *label_offset as i64,
+            },
+            _ => Reloc {
+                offset,
+                target: RelocTarget::Symbol(label.to_string()),
+                kind,
+                addend,
+            },
+        };
+
+    let mut sections = Vec::new();
+    for kind in kinds {
+        let offsets = &layouts[&kind];
+        let mut bytes = Vec::new();
+        let mut relocs = Vec::new();
+
+        for (i, item) in items[&kind].iter().enumerate() {
+            match item {
+                Item::Label(_) | Item::End(_) => (),
+                Item::Jump(cond, target) => {
+                    let short = !long_jumps.contains(&i);
+                    let end = offsets[i] + jump_size(*cond, short);
+                    let disp = locations[target].1 as i64 - end as i64;
+                    bytes.extend(jump(*cond, short, disp));
+                }
+                Item::Encoded(encoding) => {
+                    if let Some(fixup) = &encoding.fixup {
+                        relocs.push(reloc(
+                            offsets[i] + fixup.offset,
+                            &fixup.label,
+                            fixup.kind,
+                            fixup.addend,
+                        ));
+                    }
+                    bytes.extend(&encoding.bytes);
+                }
+                Item::Bytes(data) => bytes.extend(data),
+                Item::Align(align) => {
+                    bytes.resize(offsets[i].next_multiple_of(*align), 0);
+                }
+                Item::Address(label) => {
+                    relocs.push(reloc(offsets[i], label, RelocKind::Abs64, 0));
+                    bytes.extend([0; 8]);
+                }
+            }
+        }
+        let align = items[&kind]
+            .iter()
+            .filter_map(|item| match item {
+                Item::Align(align) => Some(*align),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(1);
+        sections.push(Section {
+            kind,
+            align,
+            bytes,
+            relocs,
+        });
+    }
+
+    // temporary labels starting with '.L' don't end up in the symbol-table
+    let mut symbols: Vec<Symbol> = locations
+        .iter()
+        .filter(|(name, _)| !name.starts_with(".L"))
+        .map(|(name, (section, offset))| Symbol {
+            name: name.to_string(),
+            section: *section,
+            offset: *offset,
+            global: globals.contains(name),
+            kind: types.get(name).copied(),
+            size: sizes.get(name).copied().unwrap_or(0),
+        })
+        .collect();
+    symbols.sort_by_key(|s| (s.section as usize, s.offset, s.name.clone()));
+
+    Object { sections, symbols }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_data_into_sections() {
+        let object = assemble(&[
+            Instr::Label(".LS0".to_string()),
+            Instr::Directive(Directive::String("hi\n".to_string())),
+            Instr::Directive(Directive::Data),
+            Instr::Label("x".to_string()),
+            Instr::Directive(Directive::Zero(4)),
+            Instr::Directive(Directive::Data),
+            Instr::Label("s".to_string()),
+            Instr::Directive(Directive::Address(".LS0".to_string())),
+            Instr::Directive(Directive::Bss),
+            Instr::Label("arr".to_string()),
+            Instr::Directive(Directive::Zero(8)),
+        ]);
+
+        assert_eq!(object.section(SectionKind::Rodata).bytes, b"hi\n\0");
+        assert_eq!(object.section(SectionKind::Bss).bytes, [0; 12]);
+        assert_eq!(
+            object.section(SectionKind::Data).relocs,
+            vec![Reloc {
+                offset: 0,
+                target: RelocTarget::Section(SectionKind::Rodata),
+                kind: RelocKind::Abs64,
+                addend: 0,
+            }]
+        );
+    }
+    #[test]
+    fn aligns_objects() {
+        let object = assemble(&[
+            Instr::Directive(Directive::Rodata),
+            Instr::Label(".LS0".to_string()),
+            Instr::Directive(Directive::String("hi".to_string())),
+            Instr::Directive(Directive::Rodata),
+            Instr::Directive(Directive::Align(4)),
+            Instr::Label("c".to_string()),
+            Instr::Directive(Directive::Value(Size::Long, 7)),
+        ]);
+
+        let rodata = object.section(SectionKind::Rodata);
+        assert_eq!(rodata.bytes, b"hi\0\0\x07\0\0\0");
+        assert_eq!(rodata.align, 4);
+        assert_eq!(object.symbols[0].offset, 4);
+    }
+    #[test]
+    fn records_symbol_types_and_sizes() {
+        let object = assemble(&[
+            Instr::Directive(Directive::Type("f".to_string(), SymbolType::Function)),
+            Instr::Label("f".to_string()),
+            Instr::Push(Reg::Rbp),
+            Instr::Ret,
+            Instr::Directive(Directive::Size("f".to_string())),
+            Instr::Directive(Directive::Data),
+            Instr::Directive(Directive::Type("x".to_string(), SymbolType::Object)),
+            Instr::Label("x".to_string()),
+            Instr::Directive(Directive::Zero(8)),
+            Instr::Directive(Directive::Size("x".to_string())),
+        ]);
+
+        let symbols: Vec<(&str, SectionKind, Option<SymbolType>, usize)> = object
+            .symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.section, s.kind, s.size))
+            .collect();
+        assert_eq!(
+            symbols,
+            vec![
+                ("f", SectionKind::Text, Some(SymbolType::Function), 2),
+                ("x", SectionKind::Bss, Some(SymbolType::Object), 8)
+            ]
+        );
+    }
+    #[test]
+    fn relaxes_jumps() {
+        let far = Instr::Alu(
+            AluOp::Add,
+            Operand::Imm(1000),
+            Operand::Reg(Reg::Rcx, Size::Long),
+        );
+        let mut instrs = vec![
+            Instr::Label(".L0".to_string()),
+            Instr::Jcc(Cond::E, ".L1".to_string()),
+        ];
+        instrs.extend(vec![far; 30]);
+        instrs.push(Instr::Label(".L1".to_string()));
+        instrs.push(Instr::Jmp(".L0".to_string()));
+
+        let text = &assemble(&instrs).sections[0].bytes;
+
+        // 30 adds with 6 bytes each don't fit into a short jump
+        assert_eq!(text[..6], [0x0f, 0x84, 180, 0, 0, 0]);
+        assert_eq!(text[186..], [0xe9, 0x41, 0xff, 0xff, 0xff]);
+    }
+}
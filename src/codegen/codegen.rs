@@ -1,1178 +1,896 @@
-use crate::codegen::register::*;
-use crate::common::{environment::*, expr::*, stmt::*, token::*, types::*};
+use crate::codegen::asm::{self, AluOp, Cond, Directive, Operand, Reg, ShiftOp, Size, SymbolType};
+use crate::codegen::{debug::DebugInfo, peephole::*, regalloc::*, register::*};
+use crate::common::types::*;
+use crate::ir::{instr::*, opt::OptLevel};
 use crate::typechecker::{align_by, create_label};
-use std::collections::HashMap;
-use std::fmt::Write;
-use std::fs::File;
-use std::io::Write as _;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
-// converts a register into a scratch-register if it matches the pattern
-macro_rules! convert_reg {
-    ($self:ident,$reg:ident,$($pattern:pat_param)|+) => {
-        match $reg {
-            $($pattern)|+ => $self.scratch_temp($reg)?,
-            _ => $reg
-        }
-    };
-}
-
-pub struct Compiler<'a> {
+pub struct Compiler {
     scratch: ScratchRegisters,
-    output: String,
-    env: Environment<Register>,
+    output: Vec<asm::Instr>,
+    // instructions of the current function, only added to the output once it's complete
+    instrs: Vec<asm::Instr>,
+    run_peephole: bool,
+    // only present when compiling with debug-info
+    debug: Option<DebugInfo>,
+    // annotates stack-slots and spills with comments
+    comments: bool,
     function_name: Option<String>,
     label_index: usize,
-    func_stack_size: &'a HashMap<String, usize>, // typechecker passes info about how many stack allocation there are in a function
-    const_labels: &'a HashMap<String, usize>,
-    saved_args: Vec<Register>,
+    // location of every virtual register in the current function
+    vregs: HashMap<usize, Register>,
+    // stack-slots of the current function, indexed by Value::Local
+    slots: Vec<StackRegister>,
+    // assembly label of every basic block in the current function
+    block_labels: HashMap<BlockId, String>,
+    // offset of the area where variadic functions store their unnamed register-arguments
+    va_save_area: Option<usize>,
+    // symbols defined in this module, all others might live in a shared library
+    defined: HashSet<String>,
     pub current_bp_offset: usize, // offset from base-pointer where variable stays
 }
-impl<'a> Compiler<'a> {
-    pub fn new(
-        func_stack_size: &'a HashMap<String, usize>,
-        const_labels: &'a HashMap<String, usize>,
-    ) -> Self {
+impl Compiler {
+    pub fn new(opt_level: OptLevel, debug: Option<DebugInfo>, comments: bool) -> Self {
         Compiler {
-            output: String::new(),
+            output: Vec::new(),
+            instrs: Vec::new(),
+            run_peephole: opt_level > OptLevel::O0,
+            debug,
+            comments,
             scratch: ScratchRegisters::new(),
             current_bp_offset: 0,
             label_index: 0,
-            env: Environment::new(None),
             function_name: None,
-            saved_args: Vec::new(),
-            const_labels,
-            func_stack_size,
+            vregs: HashMap::new(),
+            slots: Vec::new(),
+            block_labels: HashMap::new(),
+            va_save_area: None,
+            defined: HashSet::new(),
         }
     }
 
-    pub fn compile(&mut self, statements: &Vec<Stmt>) {
-        if let Err(e) = self.cg_const_labels() {
-            eprintln!("{:?}", e);
-            return;
-        }
-        if let Err(e) = self.cg_stmts(statements) {
-            eprintln!("{:?}", e);
-            return;
+    // generates the instructions for the whole module which can then either be printed or assembled
+    pub fn compile(mut self, module: &Module) -> Vec<asm::Instr> {
+        self.cg_module(module);
+        if let Some(debug) = &self.debug {
+            self.output.extend(debug.sections());
         }
-        let mut output = File::create("/Users/philipprados/documents/coding/Rust/rucc/generated.s")
-            .expect("create failed");
-
-        output
-            .write_all(self.output.as_bytes())
-            .expect("write failed");
+        self.output
     }
-    fn cg_const_labels(&mut self) -> Result<(), std::fmt::Error> {
-        for (data, label_index) in self.const_labels {
-            writeln!(self.output, "LS{}:\n\t.string \"{}\"", label_index, data)?;
+    fn cg_module(&mut self, module: &Module) {
+        if let Some(debug) = &self.debug {
+            self.output.push(debug.file_directive());
         }
-        Ok(())
-    }
-    fn cg_stmts(&mut self, statements: &Vec<Stmt>) -> Result<(), std::fmt::Error> {
-        for s in statements {
-            if let Err(e) = self.visit(s) {
-                return Err(e);
+        self.defined = module
+            .globals
+            .iter()
+            .map(|global| global.name.clone())
+            .chain(
+                module
+                    .functions
+                    .iter()
+                    .map(|function| function.name.clone()),
+            )
+            .collect();
+        self.cg_const_labels(&module.strings);
+        for global in module.globals.iter() {
+            self.declare_var(global);
+        }
+        for function in module.functions.iter() {
+            self.function_definition(function);
+        }
+    }
+    fn cg_const_labels(&mut self, strings: &[(String, usize)]) {
+        if !strings.is_empty() {
+            self.output.push(asm::Instr::Directive(Directive::Rodata));
+        }
+        for (data, label_index) in strings {
+            self.output
+                .push(asm::Instr::Label(format!(".LS{}", label_index)));
+            self.output
+                .push(asm::Instr::Directive(Directive::String(data.clone())));
+        }
+    }
+    fn declare_var(&mut self, global: &Global) {
+        if let Some(debug) = &mut self.debug {
+            debug.add_global(global);
+        }
+        self.output.push(asm::Instr::Directive(match &global.init {
+            // addresses are only known after relocation so they can't be read-only
+            Some(values)
+                if global.type_decl.qualifiers().is_const
+                    && values.iter().all(|v| matches!(v, Value::Const(..))) =>
+            {
+                Directive::Rodata
+            }
+            Some(_) => Directive::Data,
+            None => Directive::Bss,
+        }));
+        if !global.is_static {
+            self.output
+                .push(asm::Instr::Directive(Directive::Globl(global.name.clone())));
+        }
+        self.output.push(asm::Instr::Directive(Directive::Type(
+            global.name.clone(),
+            SymbolType::Object,
+        )));
+        self.output.push(asm::Instr::Directive(Directive::Align(
+            global.type_decl.alignment(),
+        )));
+        self.output.push(asm::Instr::Label(global.name.clone()));
+
+        match &global.init {
+            Some(values) => {
+                for value in values {
+                    self.output.push(asm::Instr::Directive(match value {
+                        Value::Const(n, type_decl) => Directive::Value(Size::of(type_decl), *n),
+                        Value::String(index) => Directive::Address(format!(".LS{}", index)),
+                        Value::Global(name) => Directive::Address(name.clone()),
+                        _ => unreachable!("global initializers are constant"),
+                    }));
+                }
             }
+            None => self.output.push(asm::Instr::Directive(Directive::Zero(
+                global.type_decl.size(),
+            ))),
         }
-        Ok(())
+        self.output
+            .push(asm::Instr::Directive(Directive::Size(global.name.clone())));
     }
-    fn visit(&mut self, statement: &Stmt) -> Result<(), std::fmt::Error> {
-        match statement {
-            Stmt::Expr(expr) => {
-                self.execute_expr(expr)?.free(); // result isn't used
-                Ok(())
-            }
-            Stmt::DeclareVar(type_decl, name, is_global) => {
-                self.declare_var(type_decl, name.unwrap_string(), *is_global)
-            }
-            Stmt::InitVar(type_decl, name, expr, is_global) => {
-                let value_reg = self.execute_expr(expr)?;
-                self.init_var(type_decl, name, value_reg, *is_global)
-            }
-            Stmt::InitList(type_decl, name, exprs, is_global) => {
-                self.init_list(type_decl, name.unwrap_string(), exprs, *is_global)
-            }
-            Stmt::Block(statements) => self.block(
-                statements,
-                Environment::new(Some(Box::new(self.env.clone()))),
-            ),
-            Stmt::FunctionDeclaration(_, _, _) => Ok(()),
-            Stmt::Function(_, name, params, body) => {
-                self.function_definition(name.unwrap_string(), params, body)
-            }
-            Stmt::Return(_, expr) => self.return_statement(expr), // jump to function postamble
-            Stmt::If(_, cond, then_branch, else_branch) => {
-                self.if_statement(cond, then_branch, else_branch)
-            }
-            Stmt::While(_, cond, body) => self.while_statement(cond, body),
+    fn emit(&mut self, instr: asm::Instr) {
+        self.instrs.push(instr);
+    }
+    fn comment(&mut self, text: String) {
+        if self.comments {
+            self.emit(asm::Instr::Comment(text));
         }
     }
 
-    fn init_list(
-        &mut self,
-        type_decl: &NEWTypes,
-        name: String,
-        exprs: &Vec<Expr>,
-        is_global: bool,
-    ) -> Result<(), std::fmt::Error> {
-        match is_global {
-            true => {
-                writeln!(self.output, "\n\t.data\n_{}:", name)?;
+    fn function_definition(&mut self, function: &Function) {
+        self.function_name = Some(function.name.clone()); // save function name for return label jump
 
-                self.env.declare_var(
-                    name.clone(),
-                    Register::Label(LabelRegister::Var(name, type_decl.clone())),
-                );
-            }
-            false => {
-                self.declare_var(type_decl, name, is_global)?;
-            }
+        let allocation = allocate(function, &self.scratch);
+        self.assign_locations(function, &allocation);
+
+        for block in function.blocks.iter() {
+            let label = format!(".L{}", create_label(&mut self.label_index));
+            self.block_labels.insert(block.id, label);
         }
-        for e in exprs.iter() {
-            match (is_global, &e.kind) {
-                // init-list is assignment syntax sugar
-                (true, ExprKind::Assign { r_expr, .. }) => {
-                    let r_value = self.execute_expr(r_expr)?;
-                    writeln!(
-                        self.output,
-                        "\t.{} {}",
-                        r_value.get_type().complete_suffix(),
-                        r_value.base_name()
-                    )?;
-                    r_value.free()
+
+        // generate function code
+        self.cg_func_preamble(function);
+        for (block_index, block) in function.blocks.iter().enumerate() {
+            if block_index != 0 {
+                self.emit(asm::Instr::Label(self.block_labels[&block.id].clone()));
+            }
+            for (instr_index, instr) in block.instrs.iter().enumerate() {
+                match instr {
+                    Instr::Call {
+                        dest,
+                        callee,
+                        args,
+                        variadic,
+                    } => self.cg_call(
+                        dest,
+                        callee,
+                        args,
+                        *variadic,
+                        &allocation.live_across_calls[&(block_index, instr_index)],
+                    ),
+                    Instr::VaStart { list } => self.cg_va_start(list, function.params.len()),
+                    _ => self.cg_instr(instr),
                 }
-                (false, _) => self.execute_expr(e)?.free(),
-                _ => unreachable!(),
-            };
+            }
+            let next_block = function.blocks.get(block_index + 1).map(|b| b.id);
+            self.cg_terminator(&block.terminator, next_block, &function.return_type);
+        }
+        self.cg_func_postamble(&function.name);
+
+        if self.run_peephole {
+            // functions reading volatile objects keep every memory-access
+            let keep_memory = function.blocks.iter().any(|block| {
+                block
+                    .instrs
+                    .iter()
+                    .any(|instr| matches!(instr, Instr::Load { volatile: true, .. }))
+            });
+            peephole(&mut self.instrs, keep_memory);
+        }
+        self.output.push(asm::Instr::Directive(Directive::Text));
+        if !function.is_static {
+            self.output.push(asm::Instr::Directive(Directive::Globl(
+                function.name.clone(),
+            )));
+        }
+        self.output.push(asm::Instr::Directive(Directive::Type(
+            function.name.clone(),
+            SymbolType::Function,
+        )));
+        self.output.append(&mut self.instrs);
+        self.output.push(asm::Instr::Directive(Directive::Size(
+            function.name.clone(),
+        )));
+
+        if let Some(debug) = &mut self.debug {
+            let bp_offsets: Vec<usize> = self.slots.iter().map(|slot| slot.bp_offset()).collect();
+            debug.add_function(function, &bp_offsets);
         }
-        Ok(())
-    }
-    fn while_statement(&mut self, cond: &Expr, body: &Stmt) -> Result<(), std::fmt::Error> {
-        let start_label = create_label(&mut self.label_index);
-        let end_label = create_label(&mut self.label_index);
-
-        writeln!(self.output, "\tjmp    L{}\nL{}:", end_label, start_label)?;
-        self.visit(body)?;
-
-        writeln!(self.output, "L{}:", end_label)?;
-        let cond_reg = self.execute_expr(cond)?;
-        writeln!(
-            self.output,
-            "\tcmpl    $0, {}\n\tjne      L{}",
-            cond_reg.name(),
-            start_label
-        )?;
-        cond_reg.free();
-
-        Ok(())
+        self.current_bp_offset = 0;
+        self.function_name = None;
+        self.vregs.clear();
+        self.slots.clear();
+        self.block_labels.clear();
+        self.va_save_area = None;
     }
+    // lays out the stack-frame for all stack-slots and spilled virtual registers
+    fn assign_locations(&mut self, function: &Function, allocation: &Allocation) {
+        for type_decl in function.slots.iter() {
+            self.current_bp_offset += type_decl.size();
+            self.current_bp_offset = align(self.current_bp_offset, type_decl);
 
-    fn if_statement(
-        &mut self,
-        cond: &Expr,
-        then_branch: &Stmt,
-        else_branch: &Option<Stmt>,
-    ) -> Result<(), std::fmt::Error> {
-        let mut cond_reg = self.execute_expr(cond)?;
-        cond_reg = convert_reg!(self, cond_reg, Register::Literal(..));
-
-        let done_label = create_label(&mut self.label_index);
-        let mut else_label = done_label;
-
-        writeln!(self.output, "\tcmpl    $0, {}", cond_reg.name())?;
-        cond_reg.free();
+            self.slots.push(StackRegister::new(
+                self.current_bp_offset,
+                type_decl.clone(),
+            ));
+        }
 
-        if !else_branch.is_none() {
-            else_label = create_label(&mut self.label_index);
+        let mut vregs: Vec<&VReg> = function
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter().filter_map(|instr| instr.dest()))
+            .collect();
+        vregs.sort_by_key(|reg| reg.id);
+        vregs.dedup_by_key(|reg| reg.id);
+
+        for reg in vregs {
+            let location = match &allocation.locations[&reg.id] {
+                Location::Scratch(scratch) => {
+                    Register::Scratch(Rc::clone(scratch), reg.type_decl.clone())
+                }
+                Location::Spill => {
+                    self.current_bp_offset += reg.type_decl.size();
+                    self.current_bp_offset = align(self.current_bp_offset, &reg.type_decl);
+
+                    Register::Stack(StackRegister::new(
+                        self.current_bp_offset,
+                        reg.type_decl.clone(),
+                    ))
+                }
+            };
+            self.vregs.insert(reg.id, location);
         }
-        writeln!(self.output, "\tje    L{}", else_label)?;
-        self.visit(then_branch)?;
 
-        if let Some(else_branch) = else_branch {
-            writeln!(self.output, "\tjmp    L{}", done_label)?;
-            writeln!(self.output, "L{}:", else_label)?;
-            self.visit(else_branch)?;
+        // all six argument-registers, the floating-point ones are never saved
+        if function.variadic {
+            self.current_bp_offset = align_by(self.current_bp_offset, 8) + 48;
+            self.va_save_area = Some(self.current_bp_offset);
         }
-        writeln!(self.output, "L{}:", done_label)?;
-        Ok(())
     }
-    fn return_statement(&mut self, value: &Option<Expr>) -> Result<(), std::fmt::Error> {
-        let function_epilogue = format!(
-            "{}_epilogue",
-            self.function_name
-                .as_ref()
-                .expect("typechecker catches nested function-declarations")
-                .clone()
-        );
-        match value {
-            Some(expr) => {
-                let return_value = self.execute_expr(expr)?;
-                writeln!(
-                    self.output,
-                    "\tmov{}    {}, {}\n\tjmp    {}",
-                    return_value.get_type().suffix(),
-                    return_value.name(),
-                    return_value.get_type().return_reg(),
-                    function_epilogue
-                )?;
-                return_value.free();
-                Ok(())
+    // lists which variable or spilled virtual register lives at which stack-offset
+    fn cg_slot_comments(&mut self, function: &Function) {
+        let mut slots: Vec<(usize, String)> = self
+            .slots
+            .iter()
+            .zip(function.slot_names.iter())
+            .map(|(slot, name)| (slot.bp_offset(), name.clone()))
+            .collect();
+        for (id, location) in self.vregs.iter() {
+            if let Register::Stack(slot) = location {
+                slots.push((slot.bp_offset(), format!("%{} (spilled)", id)));
             }
-            None => writeln!(self.output, "\tjmp    {}", function_epilogue),
         }
-    }
-    fn declare_var(
-        &mut self,
-        type_decl: &NEWTypes,
-        name: String,
-        is_global: bool,
-    ) -> Result<(), std::fmt::Error> {
-        let reg = match is_global {
-            true => {
-                writeln!(
-                    self.output,
-                    "\n\t.data\n_{name}:\n\t.zero {}",
-                    type_decl.size()
-                )?;
-                Register::Label(LabelRegister::Var(name.clone(), type_decl.clone()))
-            }
-            false => {
-                self.current_bp_offset += type_decl.size();
-                self.current_bp_offset = align(self.current_bp_offset, type_decl);
-
-                Register::Stack(StackRegister::new(
-                    self.current_bp_offset,
-                    type_decl.clone(),
-                ))
-            }
-        };
-        self.env.declare_var(name, reg);
-        Ok(())
-    }
-    fn init_var(
-        &mut self,
-        type_decl: &NEWTypes,
-        var_name: &Token,
-        mut value_reg: Register,
-        is_global: bool,
-    ) -> Result<(), std::fmt::Error> {
-        let name = var_name.unwrap_string();
-        match is_global {
-            true => {
-                writeln!(
-                    self.output,
-                    "\n\t.data\n_{name}:\n\t.{} {}",
-                    type_decl.complete_suffix(),
-                    value_reg.base_name()
-                )?;
-
-                self.env.declare_var(
-                    name.clone(),
-                    Register::Label(LabelRegister::Var(name, type_decl.clone())),
-                );
-            }
-            false => {
-                self.declare_var(type_decl, name, is_global)?;
-
-                value_reg = convert_reg!(self, value_reg, Register::Stack(..));
-                value_reg = self.convert_to_rval(value_reg)?;
+        slots.sort();
 
-                writeln!(
-                    self.output,
-                    "\tmov{}    {}, {}",
-                    type_decl.suffix(),
-                    value_reg.name(),
-                    self.env.get_var(var_name).unwrap().name() // since var-declaration set everything up we just need declared register
-                )?;
-                value_reg.free();
-            }
+        for (offset, name) in slots {
+            self.comment(format!("rbp-{}: {}", offset, name));
         }
-
-        Ok(())
     }
-    fn function_definition(
-        &mut self,
-        name: String,
-        params: &[(NEWTypes, Token)],
-        body: &Vec<Stmt>,
-    ) -> Result<(), std::fmt::Error> {
-        self.function_name = Some(name.clone()); // save function name for return label jump
-
-        // generate function code
-        self.cg_func_preamble(&name, params)?;
-        self.cg_stmts(body)?;
-        self.cg_func_postamble(&name)?;
-
-        self.current_bp_offset = 0;
-        self.function_name = None;
-
-        Ok(())
+    fn stack_size(&self) -> usize {
+        align_by(self.current_bp_offset, 16)
     }
-    fn allocate_stack(&mut self, name: &str) -> Result<(), std::fmt::Error> {
-        if self.func_stack_size[name] > 0 {
-            writeln!(
-                self.output,
-                "\tsubq    ${},%rsp",
-                self.func_stack_size[name]
-            )?;
+    fn cg_func_preamble(&mut self, function: &Function) {
+        self.emit(asm::Instr::Label(function.name.clone())); // generate function label
+        if self.debug.is_some() {
+            self.emit(asm::Instr::Directive(Directive::Loc(function.line)));
         }
-        Ok(())
-    }
-    fn dealloc_stack(&mut self, name: &str) -> Result<(), std::fmt::Error> {
-        if self.func_stack_size[name] > 0 {
-            writeln!(
-                self.output,
-                "\taddq    ${},%rsp",
-                self.func_stack_size[name]
-            )?;
+        if self.comments {
+            self.cg_slot_comments(function);
         }
-        Ok(())
-    }
-    fn cg_func_preamble(
-        &mut self,
-        name: &str,
-        params: &[(NEWTypes, Token)],
-    ) -> Result<(), std::fmt::Error> {
-        writeln!(self.output, "\n\t.text\n\t.globl _{}", name)?;
-        writeln!(self.output, "_{}:", name)?; // generate function label
-        writeln!(self.output, "\tpushq   %rbp\n\tmovq    %rsp, %rbp")?; // setup base pointer and stackpointer
+
+        // setup base pointer and stackpointer
+        self.emit(asm::Instr::Push(Reg::Rbp));
+        self.emit(asm::Instr::Mov(
+            Operand::Reg(Reg::Rsp, Size::Quad),
+            Operand::Reg(Reg::Rbp, Size::Quad),
+        ));
 
         // allocate stack-space for local vars
-        self.allocate_stack(name)?;
+        if self.stack_size() > 0 {
+            self.emit(asm::Instr::Alu(
+                AluOp::Sub,
+                Operand::Imm(self.stack_size() as i64),
+                Operand::Reg(Reg::Rsp, Size::Quad),
+            ));
+        }
 
         // initialize parameters
-        for (i, (type_decl, param_name)) in params.iter().enumerate() {
-            self.init_var(
-                type_decl,
-                param_name,
-                Register::Arg(i, type_decl.clone()),
-                false,
-            )?;
+        for (i, (type_decl, slot)) in function.params.iter().enumerate() {
+            let src = match i.checked_sub(ARG_REGISTERS.len()) {
+                None => Register::Arg(i, type_decl.clone()),
+                // the caller pushed the remaining arguments in order right above the return-address
+                Some(n) => {
+                    let incoming = Operand::Mem {
+                        base: Reg::Rbp,
+                        offset: 16 + 8 * n as i64,
+                        size: Size::of(type_decl),
+                    };
+                    self.emit(asm::Instr::Mov(
+                        incoming,
+                        Operand::Reg(Reg::Rax, Size::of(type_decl)),
+                    ));
+                    Register::Temp(TempRegister::Rax, type_decl.clone())
+                }
+            };
+            self.cg_mov(&src, &Register::Stack(self.slots[*slot].clone()));
+        }
+        if let Some(save_area) = self.va_save_area {
+            for i in function.params.len()..6 {
+                self.cg_mov(
+                    &Register::Arg(i, NEWTypes::Primitive(Types::Long)),
+                    &Register::Stack(StackRegister::new(
+                        save_area - 8 * i,
+                        NEWTypes::Primitive(Types::Long),
+                    )),
+                );
+            }
         }
-        Ok(())
     }
-    fn cg_func_postamble(&mut self, name: &str) -> Result<(), std::fmt::Error> {
-        writeln!(self.output, "{}_epilogue:", name)?;
-        self.dealloc_stack(name)?;
+    fn cg_func_postamble(&mut self, name: &str) {
+        self.emit(asm::Instr::Label(format!(".L{}_epilogue", name)));
+        if self.stack_size() > 0 {
+            self.emit(asm::Instr::Alu(
+                AluOp::Add,
+                Operand::Imm(self.stack_size() as i64),
+                Operand::Reg(Reg::Rsp, Size::Quad),
+            ));
+        }
 
-        writeln!(self.output, "\tpopq    %rbp\n\tret")?;
-        Ok(())
+        self.emit(asm::Instr::Pop(Reg::Rbp));
+        self.emit(asm::Instr::Ret);
+        if self.debug.is_some() {
+            self.emit(asm::Instr::Label(DebugInfo::end_label(name)));
+        }
     }
 
-    pub fn block(
+    fn cg_terminator(
         &mut self,
-        statements: &Vec<Stmt>,
-        env: Environment<Register>,
-    ) -> Result<(), std::fmt::Error> {
-        self.env = env;
-        let result = self.cg_stmts(statements);
-
-        // this means assignment to vars inside block which were declared outside
-        // of the block are still apparent after block
-        self.env = *self.env.enclosing.as_ref().unwrap().clone();
-        result
-    }
-
-    fn cg_literal(&mut self, num: usize, t: Types) -> Result<Register, std::fmt::Error> {
-        Ok(Register::Literal(num as usize, NEWTypes::Primitive(t)))
-    }
-    pub fn execute_expr(&mut self, ast: &Expr) -> Result<Register, std::fmt::Error> {
-        match &ast.kind {
-            ExprKind::Binary { left, token, right } => {
-                let left_reg = self.execute_expr(left)?;
-                let right_reg = self.execute_expr(right)?;
-
-                self.cg_binary(left_reg, &token.token, right_reg)
-            }
-            ExprKind::Number(v) => self.cg_literal(*v as usize, Types::Int),
-            ExprKind::CharLit(c) => self.cg_literal(*c as usize, Types::Char),
-            ExprKind::Grouping { expr } => self.execute_expr(expr),
-            ExprKind::Unary { token, right } => self.cg_unary(token, right),
-            ExprKind::Logical { left, token, right } => self.cg_logical(left, token, right),
-            ExprKind::Assign { l_expr, r_expr, .. } => {
-                let left_reg = self.execute_expr(l_expr)?;
-                let right_reg = self.execute_expr(r_expr)?;
-
-                self.cg_assign(left_reg, right_reg)
+        terminator: &Terminator,
+        next_block: Option<BlockId>,
+        return_type: &NEWTypes,
+    ) {
+        match terminator {
+            // falls through if target is the next block anyway
+            Terminator::Jump(target) if Some(*target) == next_block => (),
+            Terminator::Jump(target) => {
+                self.emit(asm::Instr::Jmp(self.block_labels[target].clone()))
             }
-            ExprKind::CompoundAssign {
-                l_expr,
-                r_expr,
-                token,
-            } => self.cg_comp_assign(l_expr, token, r_expr),
-            ExprKind::Ident(name) => Ok(self.env.get_var(name).unwrap()),
-            ExprKind::Call { callee, args, .. } => {
-                self.cg_call(callee, args, ast.type_decl.clone().unwrap())
+            Terminator::Branch {
+                cond,
+                then_block,
+                else_block,
+            } => {
+                let mut cond_reg = self.cg_operand(cond, TempRegister::Rax);
+                if let Register::Literal(..) = cond_reg {
+                    cond_reg = self.cg_to_temp(&cond_reg, TempRegister::Rax);
+                }
+                self.emit(asm::Instr::Cmp(Operand::Imm(0), cond_reg.operand()));
+
+                let then_label = self.block_labels[then_block].clone();
+                let else_label = self.block_labels[else_block].clone();
+                if Some(*else_block) == next_block {
+                    self.emit(asm::Instr::Jcc(Cond::Ne, then_label))
+                } else if Some(*then_block) == next_block {
+                    self.emit(asm::Instr::Jcc(Cond::E, else_label))
+                } else {
+                    self.emit(asm::Instr::Jcc(Cond::Ne, then_label));
+                    self.emit(asm::Instr::Jmp(else_label))
+                }
             }
-            ExprKind::CastUp { expr } => self.cg_cast_up(expr, ast.type_decl.clone().unwrap()),
-            ExprKind::CastDown { expr } => self.cg_cast_down(expr, ast.type_decl.clone().unwrap()),
-            ExprKind::ScaleUp { expr, by } => self.cg_scale_up(expr, by),
-            ExprKind::ScaleDown { expr, shift_amount } => self.cg_scale_down(expr, shift_amount),
-            ExprKind::String(token) => self.cg_string(token.unwrap_string()),
-            ExprKind::PostUnary {
-                token,
-                left,
-                by_amount,
-            } => self.cg_postunary(token, left, by_amount),
+            Terminator::Return(value) => self.return_statement(value, return_type),
         }
     }
-    fn cg_comp_assign(
-        &mut self,
-        l_expr: &Expr,
-        token: &Token,
-        r_expr: &Expr,
-    ) -> Result<Register, std::fmt::Error> {
-        let l_reg = self.execute_expr(l_expr)?;
-        let r_reg = self.execute_expr(r_expr)?;
-
-        let mut temp_scratch = Register::Scratch(
-            self.scratch.scratch_alloc(),
-            l_reg.get_type(),
-            ValueKind::Rvalue,
+    fn return_statement(&mut self, value: &Option<Value>, return_type: &NEWTypes) {
+        let function_epilogue = format!(
+            ".L{}_epilogue",
+            self.function_name
+                .as_ref()
+                .expect("typechecker catches nested function-declarations")
+                .clone()
         );
-        // have to do integer-promotion in codegen
-        if temp_scratch.get_type().size() < Types::Int.size()
-            && (matches!(l_reg, Register::Scratch(..)) || matches!(l_reg, Register::Stack(..)))
-        {
-            temp_scratch.set_type(NEWTypes::Primitive(Types::Int));
-            writeln!(
-                self.output,
-                "movs{}{}   {}, {}",
-                l_reg.get_type().suffix(),
-                temp_scratch.get_type().suffix(),
-                l_reg.name(),
-                temp_scratch.name()
-            )?;
-        } else {
-            writeln!(
-                self.output,
-                "\tmov{}    {}, {}",
-                temp_scratch.get_type().suffix(),
-                l_reg.name(),
-                temp_scratch.name(),
-            )?;
+        if let Some(value) = value {
+            let return_value = self
+                .cg_operand(value, TempRegister::Rax)
+                .with_type(return_type.clone());
+            self.cg_mov(
+                &return_value,
+                &Register::Temp(TempRegister::Rax, return_type.clone()),
+            );
         }
-        let mut bin_reg = self.cg_binary(temp_scratch, &token.comp_to_binary(), r_reg)?;
-
-        // we can do this because typechecker would catch any type-errors
-        bin_reg.set_type(l_reg.get_type());
-        let result = self.cg_assign(l_reg, bin_reg)?;
-
-        Ok(result)
+        self.emit(asm::Instr::Jmp(function_epilogue))
     }
-    fn cg_postunary(
-        &mut self,
-        token: &Token,
-        expr: &Expr,
-        by_amount: &usize,
-    ) -> Result<Register, std::fmt::Error> {
-        let reg = self.execute_expr(expr)?;
-        let mut return_reg = Register::Scratch(
-            self.scratch.scratch_alloc(),
-            reg.get_type(),
-            ValueKind::Rvalue,
-        );
 
-        // assign value to return-register before binary operation
-        // have to do integer-promotion in codegen
-        if return_reg.get_type().size() < Types::Int.size() {
-            return_reg.set_type(NEWTypes::Primitive(Types::Int));
-            writeln!(
-                self.output,
-                "movs{}{}   {}, {}",
-                reg.get_type().suffix(),
-                return_reg.get_type().suffix(),
-                reg.name(),
-                return_reg.name()
-            )?;
-        } else {
-            writeln!(
-                self.output,
-                "\tmov{}    {}, {}",
-                return_reg.get_type().suffix(),
-                reg.name(),
-                return_reg.name(),
-            )?;
+    // returns the register holding a value, addresses get materialized into the temp-register
+    fn cg_operand(&mut self, value: &Value, temp: TempRegister) -> Register {
+        match value {
+            Value::Reg(reg) => self.vregs[&reg.id].with_type(reg.type_decl.clone()),
+            // only mov can take a 64bit immediate
+            Value::Const(n, type_decl) if i32::try_from(*n).is_err() => {
+                self.cg_to_temp(&Register::Literal(*n, type_decl.clone()), temp)
+            }
+            Value::Const(n, type_decl) => Register::Literal(*n, type_decl.clone()),
+            // the address of a symbol from a shared library isn't known when linking
+            // position-independent executables so it's read from the global-offset-table
+            Value::Global(name) if !self.defined.contains(name) => {
+                let dest = Register::Temp(temp, char_ptr());
+                self.emit(asm::Instr::Mov(Operand::Got(name.clone()), dest.operand()));
+                dest
+            }
+            Value::Local(_) | Value::Global(_) | Value::String(_) => {
+                let dest = Register::Temp(temp, char_ptr());
+                let mem = self.cg_memory(value, &char_ptr(), temp);
+                self.cg_lea(&mem, &dest);
+                dest
+            }
         }
-
-        match token.token {
-            TokenType::PlusPlus => writeln!(
-                self.output,
-                "\tadd{}  ${},{}",
-                reg.get_type().suffix(),
-                by_amount,
-                reg.name()
-            )?,
-            TokenType::MinusMinus => writeln!(
-                self.output,
-                "\tsub{}  ${},{}",
-                reg.get_type().suffix(),
-                by_amount,
-                reg.name()
-            )?,
-            _ => unreachable!(),
-        };
-        reg.free();
-
-        Ok(return_reg)
     }
-    fn cg_string(&mut self, name: String) -> Result<Register, std::fmt::Error> {
-        Ok(Register::Label(LabelRegister::String(
-            self.const_labels[&name],
-        )))
-    }
-    fn cg_scale_down(
-        &mut self,
-        expr: &Expr,
-        by_amount: &usize,
-    ) -> Result<Register, std::fmt::Error> {
-        let mut value_reg = self.execute_expr(expr)?;
-        value_reg = convert_reg!(self, value_reg, Register::Literal(..));
-
-        writeln!(
-            self.output,
-            "sar{}   ${}, {}", // right shift number, equivalent to division (works bc type-size is 2^n)
-            value_reg.get_type().suffix(),
-            by_amount,
-            value_reg.name()
-        )?;
-
-        Ok(value_reg)
-    }
-    fn cg_scale_up(&mut self, expr: &Expr, by_amount: &usize) -> Result<Register, std::fmt::Error> {
-        let mut value_reg = self.execute_expr(expr)?;
-        value_reg = convert_reg!(self, value_reg, Register::Literal(..) | Register::Stack(..));
-
-        writeln!(
-            self.output,
-            "imul{}   ${}, {}",
-            value_reg.get_type().suffix(),
-            by_amount,
-            value_reg.name()
-        )?;
-
-        Ok(value_reg)
-    }
-    fn cg_cast_down(
-        &mut self,
-        expr: &Expr,
-        new_type: NEWTypes,
-    ) -> Result<Register, std::fmt::Error> {
-        let mut value_reg = self.execute_expr(expr)?;
-        value_reg.set_type(new_type);
-
-        Ok(value_reg)
-    }
-    fn cg_cast_up(&mut self, expr: &Expr, new_type: NEWTypes) -> Result<Register, std::fmt::Error> {
-        let mut value_reg = self.execute_expr(expr)?;
-
-        if matches!(value_reg, Register::Scratch(..)) || matches!(value_reg, Register::Stack(..)) {
-            let dest_reg = Register::Scratch(
-                self.scratch.scratch_alloc(),
-                new_type.clone(),
-                ValueKind::Rvalue,
-            );
-
-            writeln!(
-                self.output,
-                "movs{}{}   {}, {}", //sign extend smaller type
-                expr.type_decl.clone().unwrap().suffix(),
-                new_type.suffix(),
-                value_reg.name(),
-                dest_reg.name()
-            )?;
-            value_reg.free();
-            Ok(dest_reg)
-        } else {
-            value_reg.set_type(new_type);
-            Ok(value_reg)
+    // returns the memory an address points to
+    fn cg_memory(&mut self, addr: &Value, type_decl: &NEWTypes, temp: TempRegister) -> Register {
+        match addr {
+            Value::Local(slot) => {
+                Register::Stack(self.slots[*slot].clone()).with_type(type_decl.clone())
+            }
+            Value::Global(name) => Register::Label(LabelRegister::new(
+                LabelKind::Var(name.clone()),
+                type_decl.clone(),
+            )),
+            Value::String(index) => Register::Label(LabelRegister::new(
+                LabelKind::String(*index),
+                type_decl.clone(),
+            )),
+            Value::Reg(_) => {
+                let mut ptr = self.cg_operand(addr, temp);
+                if ptr.is_mem() {
+                    ptr = self.cg_to_temp(&ptr, temp);
+                }
+                Register::Deref(Box::new(ptr), type_decl.clone())
+            }
+            Value::Const(..) => unreachable!("typechecker doesn't allow dereferencing constants"),
         }
     }
-    fn cg_assign(
-        &mut self,
-        l_value: Register,
-        mut r_value: Register,
-    ) -> Result<Register, std::fmt::Error> {
-        // can't move from mem to mem so make temp scratch-register
-        r_value = convert_reg!(self, r_value, Register::Stack(..) | Register::Label(..));
-        r_value = self.convert_to_rval(r_value)?;
-
-        writeln!(
-            self.output,
-            "\tmov{}    {}, {}",
-            r_value.get_type().suffix(),
-            r_value.name(),
-            l_value.name(),
-        )?;
-        r_value.free();
-        Ok(l_value)
+    fn cg_to_temp(&mut self, reg: &Register, temp: TempRegister) -> Register {
+        let dest = Register::Temp(temp, reg.get_type());
+        self.cg_mov(reg, &dest);
+        dest
     }
-    fn cg_call(
-        &mut self,
-        callee: &Expr,
-        args: &Vec<Expr>,
-        return_type: NEWTypes,
-    ) -> Result<Register, std::fmt::Error> {
-        let func_name = match &callee.kind {
-            ExprKind::Ident(func_name) => func_name.unwrap_string(),
-            _ => unreachable!("typechecker"),
-        };
-        // TODO: implement args by pushing on stack
-        assert!(args.len() <= 6, "function cant have more than 6 args");
-
-        let callee_saved_regs = self.registers_in_use();
-        self.spill_regs(&callee_saved_regs)?;
-
-        // moving the arguments into their designated registers
-        for (i, expr) in args.iter().enumerate().rev() {
-            let reg = self.execute_expr(expr)?;
-
-            let arg = Register::Arg(i, expr.type_decl.clone().unwrap());
-            writeln!(
-                self.output,
-                "\tmov{}    {}, {}",
-                expr.type_decl.clone().unwrap().suffix(),
-                reg.name(),
-                arg.name(),
-            )?;
-            reg.free();
-
-            self.saved_args.push(arg);
-        }
-
-        writeln!(self.output, "\tcall    _{}", func_name)?;
-
-        self.unspill_regs(&callee_saved_regs, args.len())?;
-
-        if !return_type.is_void() {
-            let reg_index = self.scratch.scratch_alloc();
-            let return_reg = Register::Scratch(reg_index, return_type.clone(), ValueKind::Rvalue);
-            writeln!(
-                self.output,
-                "\tmov{}    {}, {}",
-                return_type.suffix(),
-                return_type.return_reg(),
-                return_reg.name()
-            )?;
-            Ok(return_reg)
-        } else {
-            Ok(Register::Void)
+    // register the result of an instruction is computed in before moving it into dest
+    fn accumulator(&self, dest: &Register) -> Register {
+        match dest {
+            Register::Scratch(..) => dest.clone(),
+            _ => Register::Temp(TempRegister::Rax, dest.get_type()),
         }
     }
-    fn registers_in_use(&self) -> Vec<Register> {
-        let mut regs = Vec::new();
-
-        unique(&self.saved_args).into_iter().for_each(|r| {
-            regs.push(r);
-        });
-
-        self.scratch
-            .registers
-            .iter()
-            .filter(|r| r.borrow().in_use)
-            .for_each(|r| {
-                regs.push(Register::Scratch(
-                    Rc::clone(r),
-                    NEWTypes::Pointer(Box::new(NEWTypes::Primitive(Types::Char))),
-                    ValueKind::Rvalue,
-                ));
-            });
-        regs
+    fn dest(&self, reg: &VReg) -> Register {
+        self.vregs[&reg.id].with_type(reg.type_decl.clone())
     }
-    fn spill_regs(&mut self, callee_saved_regs: &Vec<Register>) -> Result<(), std::fmt::Error> {
-        // push registers that are in use currently onto stack so they won't be overwritten during function
-        for reg in callee_saved_regs.iter().by_ref() {
-            writeln!(self.output, "\tpushq   {}", reg.base_name())?;
+    fn cg_mov(&mut self, src: &Register, dest: &Register) {
+        if src.same_reg(dest) {
+            return;
         }
-
-        // have to 16byte align stack depending on amount of pushs before
-        if !callee_saved_regs.is_empty() && callee_saved_regs.len() % 2 != 0 {
-            writeln!(self.output, "\tsubq    $8,%rsp")?;
+        // can't move from memory to memory directly, 64bit immediates can only be moved into registers
+        if dest.is_mem() && (src.is_mem() || src.is_wide_literal()) {
+            let temp = self.cg_to_temp(src, TempRegister::Rax);
+            return self.cg_mov(&temp, dest);
+        }
+        self.emit(asm::Instr::Mov(
+            src.with_type(dest.get_type()).operand(),
+            dest.operand(),
+        ))
+    }
+    fn cg_lea(&mut self, mem: &Register, dest: &Register) {
+        if dest.is_mem() {
+            let temp = Register::Temp(TempRegister::Rax, dest.get_type());
+            self.cg_lea(mem, &temp);
+            return self.cg_mov(&temp, dest);
+        }
+        self.emit(asm::Instr::Lea(
+            mem.operand(),
+            dest.with_type(char_ptr()).operand(),
+        ))
+    }
+
+    fn cg_instr(&mut self, instr: &Instr) {
+        match instr {
+            Instr::Copy { dest, src } => {
+                let src = self.cg_operand(src, TempRegister::Rax);
+                self.cg_mov(&src, &self.dest(dest))
+            }
+            Instr::Load { dest, addr, .. } => {
+                let dest = self.dest(dest);
+                let mem = self.cg_memory(addr, &dest.get_type(), TempRegister::Rdx);
+                self.cg_mov(&mem, &dest)
+            }
+            Instr::Store { addr, value } => {
+                let mut value = self.cg_operand(value, TempRegister::Rax);
+                if value.is_mem() {
+                    value = self.cg_to_temp(&value, TempRegister::Rax);
+                }
+                let mem = self.cg_memory(addr, &value.get_type(), TempRegister::Rdx);
+                self.cg_mov(&value, &mem)
+            }
+            Instr::Binary {
+                dest,
+                op,
+                left,
+                right,
+            } => self.cg_binary(self.dest(dest), *op, left, right),
+            Instr::Unary { dest, op, operand } => self.cg_unary(self.dest(dest), *op, operand),
+            Instr::Cast { dest, src } => self.cg_cast(self.dest(dest), src),
+            Instr::Call { .. } => unreachable!("calls are generated by cg_call"),
+            Instr::VaStart { .. } => unreachable!("va_start is generated by cg_va_start"),
+            Instr::Line(line) => self.emit(asm::Instr::Directive(Directive::Loc(*line))),
+            Instr::Comment(text) => self.emit(asm::Instr::Comment(text.clone())),
+        }
+    }
+    // fills in gp_offset, fp_offset, overflow_arg_area and reg_save_area of the va_list
+    fn cg_va_start(&mut self, list: &Value, named_params: usize) {
+        let mut list = self.cg_operand(list, TempRegister::Rdx);
+        if list.is_mem() {
+            list = self.cg_to_temp(&list, TempRegister::Rdx);
+        }
+        let base = list
+            .operand()
+            .reg()
+            .expect("address has to be in a register");
+        let field = |offset, size| Operand::Mem { base, offset, size };
+
+        let register_params = named_params.min(ARG_REGISTERS.len());
+        let stack_params = named_params - register_params;
+        self.emit(asm::Instr::Mov(
+            Operand::Imm(8 * register_params as i64),
+            field(0, Size::Long),
+        ));
+        // no floating-point registers were saved so they're all marked as used
+        self.emit(asm::Instr::Mov(Operand::Imm(176), field(4, Size::Long)));
+
+        // arguments passed on the stack start above the return-address and saved base-pointer
+        let save_area = self
+            .va_save_area
+            .expect("va_start only in variadic functions");
+        for (offset, bp_offset) in [(8, 16 + 8 * stack_params as i64), (16, -(save_area as i64))] {
+            self.emit(asm::Instr::Lea(
+                Operand::Mem {
+                    base: Reg::Rbp,
+                    offset: bp_offset,
+                    size: Size::Quad,
+                },
+                Operand::Reg(Reg::Rax, Size::Quad),
+            ));
+            self.emit(asm::Instr::Mov(
+                Operand::Reg(Reg::Rax, Size::Quad),
+                field(offset, Size::Quad),
+            ));
+        }
+    }
+    fn cg_cast(&mut self, dest: Register, src: &Value) {
+        let src = self.cg_operand(src, TempRegister::Rax);
+        let new_type = dest.get_type();
+
+        match src {
+            Register::Literal(n, _) => {
+                self.cg_mov(&Register::Literal(wrap(n, &new_type), new_type), &dest)
+            }
+            _ if new_type.size() > src.get_type().size() => {
+                //sign extend smaller type
+                let acc = self.accumulator(&dest);
+                self.emit(asm::Instr::Movs(src.operand(), acc.operand()));
+                self.cg_mov(&acc, &dest)
+            }
+            // truncating just uses the lower bytes of the value
+            _ => self.cg_mov(&src.with_type(new_type), &dest),
+        }
+    }
+    fn cg_unary(&mut self, dest: Register, op: UnaryOp, operand: &Value) {
+        let operand = self.cg_operand(operand, TempRegister::Rax);
+        match op {
+            UnaryOp::Neg | UnaryOp::BitNot => {
+                let acc = self.accumulator(&dest);
+                self.cg_mov(&operand, &acc);
+                self.emit(if op == UnaryOp::Neg {
+                    asm::Instr::Neg(acc.operand())
+                } else {
+                    asm::Instr::Not(acc.operand())
+                });
+                self.cg_mov(&acc, &dest)
+            }
+            UnaryOp::Not => {
+                let operand = self.cg_to_temp(&operand, TempRegister::Rax);
+                self.emit(asm::Instr::Cmp(Operand::Imm(0), operand.operand()));
+                self.emit(asm::Instr::Set(Cond::E, Operand::Reg(Reg::Rax, Size::Byte)));
+                self.cg_set_result(dest)
+            }
         }
-        Ok(())
     }
-    fn unspill_regs(
+    // zero extends the flag-result in %al into dest
+    fn cg_set_result(&mut self, dest: Register) {
+        if dest.get_type().size() == 1 {
+            return self.cg_mov(&Register::Temp(TempRegister::Rax, dest.get_type()), &dest);
+        }
+        let acc = self.accumulator(&dest);
+        self.emit(asm::Instr::Movz(
+            Operand::Reg(Reg::Rax, Size::Byte),
+            acc.operand(),
+        ));
+        self.cg_mov(&acc, &dest)
+    }
+
+    fn cg_binary(&mut self, dest: Register, op: BinOp, left: &Value, right: &Value) {
+        let left = self.cg_operand(left, TempRegister::Rax);
+        let right = self.cg_operand(right, TempRegister::Rcx);
+
+        match op {
+            BinOp::Add => self.cg_arithmetic(AluOp::Add, dest, left, right),
+            BinOp::Sub => self.cg_arithmetic(AluOp::Sub, dest, left, right),
+            BinOp::Mul => self.cg_arithmetic(AluOp::Imul, dest, left, right),
+            BinOp::And => self.cg_arithmetic(AluOp::And, dest, left, right),
+            BinOp::Or => self.cg_arithmetic(AluOp::Or, dest, left, right),
+            BinOp::Xor => self.cg_arithmetic(AluOp::Xor, dest, left, right),
+            BinOp::Div => self.cg_div(dest, left, right, TempRegister::Rax),
+            // remainder is stored in %rdx
+            BinOp::Mod => self.cg_div(dest, left, right, TempRegister::Rdx),
+            BinOp::Shl => self.cg_shift(ShiftOp::Sal, dest, left, right),
+            BinOp::Sar => self.cg_shift(ShiftOp::Sar, dest, left, right),
+            BinOp::Eq => self.cg_comparison(Cond::E, dest, left, right),
+            BinOp::Ne => self.cg_comparison(Cond::Ne, dest, left, right),
+            BinOp::Gt => self.cg_comparison(Cond::G, dest, left, right),
+            BinOp::Ge => self.cg_comparison(Cond::Ge, dest, left, right),
+            BinOp::Lt => self.cg_comparison(Cond::L, dest, left, right),
+            BinOp::Le => self.cg_comparison(Cond::Le, dest, left, right),
+        }
+    }
+    fn cg_arithmetic(&mut self, op: AluOp, dest: Register, left: Register, right: Register) {
+        let mut acc = self.accumulator(&dest);
+        // right operand can't be overwritten before it's used
+        if right.same_reg(&acc) {
+            acc = Register::Temp(TempRegister::Rax, dest.get_type());
+        }
+        self.cg_mov(&left, &acc);
+        self.emit(asm::Instr::Alu(
+            op,
+            right.with_type(acc.get_type()).operand(),
+            acc.operand(),
+        ));
+        self.cg_mov(&acc, &dest)
+    }
+    fn cg_div(
         &mut self,
-        callee_saved_regs: &Vec<Register>,
-        args_len: usize,
-    ) -> Result<(), std::fmt::Error> {
-        // undo the stack alignment from before call
-        if !callee_saved_regs.is_empty() && callee_saved_regs.len() % 2 != 0 {
-            writeln!(self.output, "\taddq    $8,%rsp")?;
-        }
-
-        // pop registers from before function call back to scratch registers
-        for reg in callee_saved_regs.iter().rev().by_ref() {
-            writeln!(self.output, "\tpopq   {}", reg.base_name())?;
-        }
-        // pop all argument registers from current function-call of stack
-        for _ in 0..args_len {
-            self.saved_args.pop();
-        }
-
-        Ok(())
+        dest: Register,
+        left: Register,
+        mut right: Register,
+        result: TempRegister,
+    ) {
+        // idiv doesn't take an immediate operand
+        if let Register::Literal(..) = right {
+            right = self.cg_to_temp(&right, TempRegister::Rcx);
+        }
+        self.cg_mov(&left, &Register::Temp(TempRegister::Rax, left.get_type()));
+
+        // sign extend %rax into %rdx before dividing
+        self.emit(match left.get_type().size() {
+            8 => asm::Instr::Cqo,
+            _ => asm::Instr::Cdq,
+        });
+        self.emit(asm::Instr::Idiv(right.operand()));
+        self.cg_mov(&Register::Temp(result, dest.get_type()), &dest)
+    }
+    fn cg_shift(&mut self, op: ShiftOp, dest: Register, left: Register, right: Register) {
+        // shift amount has to be in %cl
+        self.cg_mov(&right, &Register::Temp(TempRegister::Rcx, right.get_type()));
+
+        let acc = self.accumulator(&dest);
+        self.cg_mov(&left, &acc);
+        self.emit(asm::Instr::Shift(
+            op,
+            Operand::Reg(Reg::Rcx, Size::Byte),
+            acc.operand(),
+        ));
+        self.cg_mov(&acc, &dest)
+    }
+    fn cg_comparison(&mut self, cond: Cond, dest: Register, left: Register, right: Register) {
+        let left = self.cg_to_temp(&left, TempRegister::Rax);
+        self.emit(asm::Instr::Cmp(
+            right.with_type(left.get_type()).operand(),
+            left.operand(),
+        ));
+        self.emit(asm::Instr::Set(cond, Operand::Reg(Reg::Rax, Size::Byte)));
+        self.cg_set_result(dest)
     }
 
-    fn cg_logical(
+    fn cg_call(
         &mut self,
-        left: &Expr,
-        token: &Token,
-        right: &Expr,
-    ) -> Result<Register, std::fmt::Error> {
-        match token.token {
-            TokenType::AmpAmp => self.cg_and(left, right),
-            TokenType::PipePipe => self.cg_or(left, right),
-            _ => unreachable!(),
+        dest: &Option<VReg>,
+        callee: &Value,
+        args: &[Value],
+        variadic: bool,
+        callee_saved_regs: &[Rc<RefCell<ScratchRegister>>],
+    ) {
+        self.spill_regs(callee_saved_regs);
+        let stack_size = self.cg_stack_args(args);
+        let args = &args[..args.len().min(ARG_REGISTERS.len())];
+        match callee {
+            Value::Global(name) => {
+                self.cg_args(args, None);
+                self.cg_vector_count(variadic);
+                self.emit(asm::Instr::Call(name.clone(), variadic));
+            }
+            // function-pointer is moved into a register which isn't overwritten by the arguments
+            _ => {
+                self.cg_args(args, Some(callee));
+                self.cg_vector_count(variadic);
+                self.emit(asm::Instr::CallIndirect(TempRegister::R11.reg(), variadic));
+            }
         }
-    }
-    fn cg_or(&mut self, left: &Expr, right: &Expr) -> Result<Register, std::fmt::Error> {
-        let mut left = self.execute_expr(left)?;
-        left = convert_reg!(self, left, Register::Literal(..));
-
-        let true_label = create_label(&mut self.label_index);
-
-        // jump to true label left is true => short circuit
-        writeln!(
-            self.output,
-            "\tcmp{}    $0, {}\n\tjne    L{}",
-            left.get_type().suffix(),
-            left.name(),
-            true_label
-        )?;
-        left.free();
-
-        let mut right = self.execute_expr(right)?;
-        right = convert_reg!(self, right, Register::Literal(..));
-
-        let false_label = create_label(&mut self.label_index);
-
-        // if right is false we know expression is false
-        writeln!(
-            self.output,
-            "\tcmp{}    $0, {}\n\tje    L{}",
-            right.get_type().suffix(),
-            right.name(),
-            false_label
-        )?;
-        right.free();
-
-        let done_label = create_label(&mut self.label_index);
-        let result = Register::Scratch(
-            self.scratch.scratch_alloc(),
-            NEWTypes::Primitive(Types::Int),
-            ValueKind::Rvalue,
-        );
-        // if expression true write 1 in result and skip false label
-        writeln!(
-            self.output,
-            "L{}:\n\tmovl    $1, {}",
-            true_label,
-            result.name()
-        )?;
-        writeln!(self.output, "\tjmp     L{}", done_label)?;
-
-        writeln!(
-            self.output,
-            "L{}:\n\tmovl    $0, {}",
-            false_label,
-            result.name()
-        )?;
-        writeln!(self.output, "L{}:", done_label)?;
-
-        Ok(result)
-    }
-    fn cg_and(&mut self, left: &Expr, right: &Expr) -> Result<Register, std::fmt::Error> {
-        let mut left = self.execute_expr(left)?;
-        left = convert_reg!(self, left, Register::Literal(..));
-
-        let false_label = create_label(&mut self.label_index);
-
-        // if left is false expression is false, we jump to false label
-        writeln!(
-            self.output,
-            "\tcmp{}    $0, {}\n\tje    L{}",
-            left.get_type().suffix(),
-            left.name(),
-            false_label
-        )?;
-        left.free();
-
-        // left is true if right false jump to false label
-        let mut right = self.execute_expr(right)?;
-        right = convert_reg!(self, right, Register::Literal(..));
-        writeln!(
-            self.output,
-            "\tcmp{}    $0, {}\n\tje    L{}",
-            right.get_type().suffix(),
-            right.name(),
-            false_label
-        )?;
-        right.free();
-
-        // if no prior jump was taken expression is true
-        let true_label = create_label(&mut self.label_index);
-        let result = Register::Scratch(
-            self.scratch.scratch_alloc(),
-            NEWTypes::Primitive(Types::Int),
-            ValueKind::Rvalue,
-        );
-        writeln!(
-            self.output,
-            "\tmovl    $1, {}\n\tjmp    L{}",
-            result.name(),
-            true_label
-        )?;
-
-        writeln!(self.output, "L{}:", false_label)?;
-        writeln!(self.output, "\tmovl    $0, {}", result.name())?;
+        if stack_size > 0 {
+            self.emit(asm::Instr::Alu(
+                AluOp::Add,
+                Operand::Imm(stack_size as i64),
+                Operand::Reg(Reg::Rsp, Size::Quad),
+            ));
+        }
+        self.unspill_regs(callee_saved_regs);
+
+        if let Some(dest) = dest {
+            let dest = self.dest(dest);
+            self.cg_mov(&Register::Temp(TempRegister::Rax, dest.get_type()), &dest);
+        }
+    }
+    // moves the arguments into their designated registers and the target of an indirect call
+    // into %r11
+    // variadic callees expect the amount of vector-registers used for arguments in %al,
+    // floating-point arguments don't exist so it's always zero
+    fn cg_vector_count(&mut self, variadic: bool) {
+        if variadic {
+            self.emit(asm::Instr::Mov(
+                Operand::Imm(0),
+                Operand::Reg(Reg::Rax, Size::Long),
+            ));
+        }
+    }
+    // arguments after the ones passed in registers are pushed from right to left, returns the
+    // amount of bytes to pop after the call
+    fn cg_stack_args(&mut self, args: &[Value]) -> usize {
+        let stack_args = args.get(ARG_REGISTERS.len()..).unwrap_or_default();
+
+        // stack has to stay 16byte aligned at the call
+        if stack_args.len() % 2 == 1 {
+            self.emit(asm::Instr::Alu(
+                AluOp::Sub,
+                Operand::Imm(8),
+                Operand::Reg(Reg::Rsp, Size::Quad),
+            ));
+        }
+        for arg in stack_args.iter().rev() {
+            let src = self.cg_operand(arg, TempRegister::Rax);
+            self.cg_mov(&src, &Register::Temp(TempRegister::Rax, src.get_type()));
+            self.emit(asm::Instr::Push(Reg::Rax));
+        }
+        8 * (stack_args.len() + stack_args.len() % 2)
+    }
+    fn cg_args(&mut self, args: &[Value], callee: Option<&Value>) {
+        // arg-registers overlap with scratch-registers so register-to-register moves
+        // have to be ordered in a way that no source is overwritten before it's read
+        let mut pending: Vec<(Register, Register)> = Vec::new();
+        let mut others = Vec::new();
+
+        // the type of the destination is set by the move
+        let callee = callee.map(|callee| (callee, Register::Temp(TempRegister::R11, char_ptr())));
+        let moves = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| (arg, Register::Arg(i, char_ptr())))
+            .chain(callee);
+
+        for (arg, dest) in moves {
+            match arg {
+                Value::Reg(reg) if !self.vregs[&reg.id].is_mem() => {
+                    pending.push((self.dest(reg), dest))
+                }
+                _ => others.push((arg, dest)),
+            }
+        }
+        while !pending.is_empty() {
+            let ready = pending
+                .iter()
+                .position(|(_, dest)| !pending.iter().any(|(src, _)| src.same_reg(dest)));
 
-        writeln!(self.output, "L{}:", true_label)?;
-        Ok(result)
-    }
-    fn cg_unary(&mut self, token: &Token, right: &Expr) -> Result<Register, std::fmt::Error> {
-        let mut reg = self.execute_expr(right)?;
-        if matches!(reg, Register::Literal(..)) {
-            reg = self.scratch_temp(reg)?;
+            match ready {
+                Some(index) => {
+                    let (src, dest) = pending.remove(index);
+                    self.cg_mov(&src.with_type(char_ptr()), &dest.with_type(char_ptr()));
+                }
+                // break the cycle by moving one source out of the way
+                None => {
+                    let (src, dest) = pending.remove(0);
+                    let temp = self.cg_to_temp(&src.with_type(char_ptr()), TempRegister::Rax);
+                    pending.push((temp, dest));
+                }
+            }
         }
-        match token.token {
-            TokenType::Bang => self.cg_bang(reg),
-            TokenType::Minus => self.cg_negate(reg),
-            TokenType::Amp => self.cg_address_at(reg),
-            TokenType::Star => self.cg_deref(reg),
-            TokenType::Tilde => self.cg_bit_not(reg),
-            _ => unreachable!(),
+        // remaining arguments don't read any arg-registers
+        for (arg, dest) in others {
+            let src = self.cg_operand(arg, TempRegister::Rax);
+            self.cg_mov(&src, &dest.with_type(src.get_type()));
         }
     }
-    fn cg_bit_not(&mut self, reg: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(self.output, "\tnotl    {}", reg.name())?; // typechecker guarantees integer-type
-
-        Ok(reg)
-    }
-    fn cg_bang(&mut self, reg: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tcmp{} $0, {}\n\tsete %al",
-            reg.get_type().suffix(),
-            reg.name()
-        )?; // compares reg-value with 0
-
-        let result = Register::Scratch(
-            self.scratch.scratch_alloc(),
-            reg.get_type(),
-            ValueKind::Rvalue,
-        );
-        // sets %al to 1 if comparison true and to 0 when false and then copies %al to current reg
-        if reg.get_type() == NEWTypes::Primitive(Types::Char) {
-            writeln!(self.output, "\tmovb %al, {}", result.name())?;
-        } else {
-            writeln!(
-                self.output,
-                "\tmovzb{} %al, {}",
-                result.get_type().suffix(),
-                result.name()
-            )?;
+    fn spill_regs(&mut self, callee_saved_regs: &[Rc<RefCell<ScratchRegister>>]) {
+        if !callee_saved_regs.is_empty() {
+            self.comment(format!("spill {}", reg_names(callee_saved_regs.iter())));
         }
-        reg.free();
-
-        Ok(result)
-    }
-    fn cg_negate(&mut self, reg: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tneg{} {}",
-            reg.get_type().suffix(),
-            reg.name()
-        )?;
-        Ok(reg)
-    }
-    fn cg_address_at(&mut self, reg: Register) -> Result<Register, std::fmt::Error> {
-        let dest = Register::Scratch(
-            self.scratch.scratch_alloc(),
-            NEWTypes::Pointer(Box::new(reg.get_type())),
-            ValueKind::Rvalue,
-        );
-        writeln!(self.output, "\tleaq    {}, {}", reg.name(), dest.name())?;
-
-        reg.free();
-        Ok(dest)
-    }
-    fn cg_deref(&mut self, mut reg: Register) -> Result<Register, std::fmt::Error> {
-        reg = convert_reg!(self, reg, Register::Label(..) | Register::Stack(..));
-        reg = self.convert_to_rval(reg)?;
-
-        reg.set_type(reg.get_type().deref_at().unwrap());
-        reg.set_value_kind(ValueKind::Lvalue);
-        Ok(reg)
-    }
-
-    fn cg_add(&mut self, left: Register, right: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tadd{} {}, {}\n",
-            right.get_type().suffix(),
-            left.name(),
-            right.name()
-        )?;
-
-        left.free();
-        Ok(right)
-    }
-    fn cg_sub(&mut self, left: Register, right: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tsub{} {}, {}\n",
-            right.get_type().suffix(),
-            right.name(),
-            left.name()
-        )?;
-
-        right.free();
-        Ok(left)
-    }
-
-    fn cg_mult(&mut self, left: Register, right: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\timul{} {}, {}\n",
-            right.get_type().suffix(),
-            left.name(),
-            right.name()
-        )?;
-
-        left.free();
-        Ok(right)
-    }
-
-    fn cg_div(&mut self, left: Register, right: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tmov{} {}, {}",
-            left.get_type().suffix(),
-            left.name(),
-            left.get_type().return_reg(),
-        )?;
-        // rax / rcx => rax
-        writeln!(
-            self.output,
-            "\t{}\n\tidiv{} {}",
-            match right.get_type().size() {
-                0..=7 => "cdq",
-                _ => "cqo",
-            },
-            right.get_type().suffix(),
-            right.name()
-        )?;
-        // move rax(div result) into right reg
-        writeln!(
-            self.output,
-            "\tmov{} {}, {}",
-            right.get_type().suffix(),
-            right.get_type().return_reg(),
-            right.name()
-        )?;
-
-        left.free();
-        Ok(right)
-    }
-
-    fn cg_mod(&mut self, left: Register, right: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tmov{} {}, {}",
-            left.get_type().suffix(),
-            left.name(),
-            left.get_type().return_reg(),
-        )?;
-        // rax % rcx => rdx
-        writeln!(
-            self.output,
-            "\t{}\n\tidiv{} {}",
-            match right.get_type().size() {
-                0..=7 => "cdq",
-                _ => "cqo",
-            },
-            right.get_type().suffix(),
-            right.name()
-        )?;
-        writeln!(
-            self.output,
-            "\tmov{} {}, {}",
-            right.get_type().suffix(),
-            Register::Arg(2, right.get_type()).name(), // rdx register stores remainder
-            right.name()
-        )?;
-
-        left.free();
-        Ok(right)
-    }
-
-    fn cg_bit_xor(&mut self, left: Register, right: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\txor{} {}, {}\n",
-            right.get_type().suffix(),
-            left.name(),
-            right.name()
-        )?;
-
-        left.free();
-        Ok(right)
-    }
-    fn cg_bit_or(&mut self, left: Register, right: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tor{} {}, {}\n",
-            right.get_type().suffix(),
-            left.name(),
-            right.name()
-        )?;
-
-        left.free();
-        Ok(right)
-    }
-    fn cg_bit_and(&mut self, left: Register, right: Register) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tand{} {}, {}\n",
-            right.get_type().suffix(),
-            left.name(),
-            right.name()
-        )?;
-
-        left.free();
-        Ok(right)
-    }
-    fn cg_shift(
-        &mut self,
-        direction: &str,
-        left: Register,
-        right: Register,
-    ) -> Result<Register, std::fmt::Error> {
-        // expects shift amount to be in %cl (4th arg register)
-        writeln!(
-            self.output,
-            "\tmov{}    {},{}",
-            right.get_type().suffix(),
-            right.name(),
-            Register::Arg(3, right.get_type()).name()
-        )?;
-        right.free();
-
-        writeln!(
-            self.output,
-            "\tsa{}{} %cl, {}\n",
-            direction,
-            left.get_type().suffix(),
-            left.name()
-        )?;
-
-        Ok(left)
-    }
-    fn cg_comparison(
-        &mut self,
-        operator: &str,
-        left: Register,
-        right: Register,
-    ) -> Result<Register, std::fmt::Error> {
-        writeln!(
-            self.output,
-            "\tcmp{} {}, {}",
-            right.get_type().suffix(),
-            right.name(),
-            left.name()
-        )?;
-        // write ZF to %al based on operator and zero extend %right_register with value of %al
-        writeln!(self.output, "\t{operator} %al",)?;
-        if right.get_type() == NEWTypes::Primitive(Types::Char) {
-            writeln!(self.output, "\tmovb %al, {}", right.name())?;
-        } else {
-            writeln!(
-                self.output,
-                "\tmovzb{} %al, {}",
-                right.get_type().suffix(),
-                right.name()
-            )?;
+        // push registers that are live across the call onto stack so they won't be overwritten
+        for reg in callee_saved_regs.iter() {
+            self.emit(asm::Instr::Push(reg.borrow().reg));
         }
 
-        left.free();
-        Ok(right)
-    }
-
-    fn cg_binary(
-        &mut self,
-        mut left_reg: Register,
-        token: &TokenType,
-        mut right_reg: Register,
-    ) -> Result<Register, std::fmt::Error> {
-        left_reg = convert_reg!(
-            self,
-            left_reg,
-            Register::Literal(..) | Register::Label(..) | Register::Stack(..)
-        );
-        right_reg = convert_reg!(
-            self,
-            right_reg,
-            Register::Literal(..) | Register::Label(..) | Register::Stack(..)
-        );
-
-        left_reg = self.convert_to_rval(left_reg)?;
-        right_reg = self.convert_to_rval(right_reg)?;
-
-        match token {
-            TokenType::Plus => self.cg_add(left_reg, right_reg),
-            TokenType::Minus => self.cg_sub(left_reg, right_reg),
-            TokenType::Star => self.cg_mult(left_reg, right_reg),
-            TokenType::Slash => self.cg_div(left_reg, right_reg),
-            TokenType::Mod => self.cg_mod(left_reg, right_reg),
-            TokenType::Xor => self.cg_bit_xor(left_reg, right_reg),
-            TokenType::Pipe => self.cg_bit_or(left_reg, right_reg),
-            TokenType::Amp => self.cg_bit_and(left_reg, right_reg),
-            TokenType::LessLess => self.cg_shift("l", left_reg, right_reg),
-            TokenType::GreaterGreater => self.cg_shift("r", left_reg, right_reg),
-            TokenType::EqualEqual => self.cg_comparison("sete", left_reg, right_reg),
-            TokenType::BangEqual => self.cg_comparison("setne", left_reg, right_reg),
-            TokenType::Greater => self.cg_comparison("setg", left_reg, right_reg),
-            TokenType::GreaterEqual => self.cg_comparison("setge", left_reg, right_reg),
-            TokenType::Less => self.cg_comparison("setl", left_reg, right_reg),
-            TokenType::LessEqual => self.cg_comparison("setle", left_reg, right_reg),
-            _ => unreachable!(),
+        // have to 16byte align stack depending on amount of pushs before
+        if !callee_saved_regs.len().is_multiple_of(2) {
+            self.emit(asm::Instr::Alu(
+                AluOp::Sub,
+                Operand::Imm(8),
+                Operand::Reg(Reg::Rsp, Size::Quad),
+            ));
         }
     }
-    fn convert_to_rval(&mut self, mut reg: Register) -> Result<Register, std::fmt::Error> {
-        if reg.is_lval() {
-            reg = self.scratch_temp(reg)?
+    fn unspill_regs(&mut self, callee_saved_regs: &[Rc<RefCell<ScratchRegister>>]) {
+        if !callee_saved_regs.is_empty() {
+            self.comment(format!(
+                "unspill {}",
+                reg_names(callee_saved_regs.iter().rev())
+            ));
+        }
+        // undo the stack alignment from before call
+        if !callee_saved_regs.len().is_multiple_of(2) {
+            self.emit(asm::Instr::Alu(
+                AluOp::Add,
+                Operand::Imm(8),
+                Operand::Reg(Reg::Rsp, Size::Quad),
+            ));
         }
-        Ok(reg)
-    }
-    fn scratch_temp(&mut self, reg: Register) -> Result<Register, std::fmt::Error> {
-        let temp_scratch = Register::Scratch(
-            self.scratch.scratch_alloc(),
-            reg.get_type(),
-            ValueKind::Rvalue,
-        );
 
-        writeln!(
-            self.output,
-            "\tmov{}    {}, {}",
-            temp_scratch.get_type().suffix(),
-            reg.name(),
-            temp_scratch.name(),
-        )?;
-        reg.free();
-        Ok(temp_scratch)
+        // pop registers from before function call back to scratch registers
+        for reg in callee_saved_regs.iter().rev() {
+            self.emit(asm::Instr::Pop(reg.borrow().reg));
+        }
     }
 }
 
-fn unique(vec: &[Register]) -> Vec<Register> {
-    let mut result = Vec::new();
+fn reg_names<'a>(regs: impl Iterator<Item = &'a Rc<RefCell<ScratchRegister>>>) -> String {
+    regs.map(|reg| reg.borrow().reg.name(Size::Quad).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-    vec.iter().for_each(|r| {
-        if !result.contains(r) {
-            result.push(r.clone());
-        }
-    });
+fn char_ptr() -> NEWTypes {
+    NEWTypes::Pointer(Box::new(NEWTypes::Primitive(Types::Char)))
+}
 
-    result
+// truncates a constant to the range of its type
+fn wrap(n: i64, type_decl: &NEWTypes) -> i64 {
+    match type_decl.size() {
+        1 => n as i8 as i64,
+        4 => n as i32 as i64,
+        _ => n,
+    }
 }
 
 pub fn align(offset: usize, type_decl: &NEWTypes) -> usize {
@@ -0,0 +1,489 @@
+use crate::codegen::asm::{Directive, Instr, Size};
+use crate::common::types::*;
+use crate::ir::instr::{Function, Global};
+
+const DW_TAG_ARRAY_TYPE: u8 = 0x01;
+const DW_TAG_FORMAL_PARAMETER: u8 = 0x05;
+const DW_TAG_POINTER_TYPE: u8 = 0x0f;
+const DW_TAG_COMPILE_UNIT: u8 = 0x11;
+const DW_TAG_SUBRANGE_TYPE: u8 = 0x21;
+const DW_TAG_BASE_TYPE: u8 = 0x24;
+const DW_TAG_CONST_TYPE: u8 = 0x26;
+const DW_TAG_SUBPROGRAM: u8 = 0x2e;
+const DW_TAG_VARIABLE: u8 = 0x34;
+const DW_TAG_VOLATILE_TYPE: u8 = 0x35;
+const DW_TAG_RESTRICT_TYPE: u8 = 0x37;
+
+const DW_AT_LOCATION: u8 = 0x02;
+const DW_AT_NAME: u8 = 0x03;
+const DW_AT_BYTE_SIZE: u8 = 0x0b;
+const DW_AT_STMT_LIST: u8 = 0x10;
+const DW_AT_LOW_PC: u8 = 0x11;
+const DW_AT_HIGH_PC: u8 = 0x12;
+const DW_AT_LANGUAGE: u8 = 0x13;
+const DW_AT_COMP_DIR: u8 = 0x1b;
+const DW_AT_PRODUCER: u8 = 0x25;
+const DW_AT_UPPER_BOUND: u8 = 0x2f;
+const DW_AT_DECL_FILE: u8 = 0x3a;
+const DW_AT_DECL_LINE: u8 = 0x3b;
+const DW_AT_ENCODING: u8 = 0x3e;
+const DW_AT_EXTERNAL: u8 = 0x3f;
+const DW_AT_FRAME_BASE: u8 = 0x40;
+const DW_AT_TYPE: u8 = 0x49;
+
+const DW_FORM_ADDR: u8 = 0x01;
+const DW_FORM_DATA4: u8 = 0x06;
+const DW_FORM_DATA8: u8 = 0x07;
+const DW_FORM_STRING: u8 = 0x08;
+const DW_FORM_DATA1: u8 = 0x0b;
+const DW_FORM_FLAG: u8 = 0x0c;
+const DW_FORM_REF4: u8 = 0x13;
+const DW_FORM_SEC_OFFSET: u8 = 0x17;
+const DW_FORM_EXPRLOC: u8 = 0x18;
+
+const DW_OP_ADDR: u8 = 0x03;
+const DW_OP_BREG6: u8 = 0x76;
+const DW_OP_FBREG: u8 = 0x91;
+
+const DW_ATE_SIGNED: u8 = 0x05;
+const DW_ATE_SIGNED_CHAR: u8 = 0x06;
+const DW_LANG_C89: u8 = 0x01;
+
+// debugging-information-entries which are emitted, their code is the position in ABBREVIATIONS
+#[derive(Clone, Copy)]
+enum Abbrev {
+    CompileUnit = 1,
+    Subprogram,
+    VoidSubprogram,
+    Parameter,
+    Variable,
+    GlobalVariable,
+    BaseType,
+    PointerType,
+    VoidPointerType,
+    ArrayType,
+    SubrangeType,
+    ConstType,
+    VolatileType,
+    RestrictType,
+}
+
+// tag, whether entry has children and its attributes with their forms
+type Abbreviation = (u8, bool, &'static [(u8, u8)]);
+static ABBREVIATIONS: [Abbreviation; 14] = [
+    (
+        DW_TAG_COMPILE_UNIT,
+        true,
+        &[
+            (DW_AT_PRODUCER, DW_FORM_STRING),
+            (DW_AT_LANGUAGE, DW_FORM_DATA1),
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_COMP_DIR, DW_FORM_STRING),
+            (DW_AT_LOW_PC, DW_FORM_ADDR),
+            (DW_AT_HIGH_PC, DW_FORM_DATA8),
+            (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+        ],
+    ),
+    (
+        DW_TAG_SUBPROGRAM,
+        true,
+        &[
+            (DW_AT_EXTERNAL, DW_FORM_FLAG),
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_DECL_FILE, DW_FORM_DATA1),
+            (DW_AT_DECL_LINE, DW_FORM_DATA4),
+            (DW_AT_TYPE, DW_FORM_REF4),
+            (DW_AT_LOW_PC, DW_FORM_ADDR),
+            (DW_AT_HIGH_PC, DW_FORM_DATA8),
+            (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+        ],
+    ),
+    (
+        DW_TAG_SUBPROGRAM,
+        true,
+        &[
+            (DW_AT_EXTERNAL, DW_FORM_FLAG),
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_DECL_FILE, DW_FORM_DATA1),
+            (DW_AT_DECL_LINE, DW_FORM_DATA4),
+            (DW_AT_LOW_PC, DW_FORM_ADDR),
+            (DW_AT_HIGH_PC, DW_FORM_DATA8),
+            (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+        ],
+    ),
+    (
+        DW_TAG_FORMAL_PARAMETER,
+        false,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_TYPE, DW_FORM_REF4),
+            (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+        ],
+    ),
+    (
+        DW_TAG_VARIABLE,
+        false,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_TYPE, DW_FORM_REF4),
+            (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+        ],
+    ),
+    (
+        DW_TAG_VARIABLE,
+        false,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_TYPE, DW_FORM_REF4),
+            (DW_AT_EXTERNAL, DW_FORM_FLAG),
+            (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+        ],
+    ),
+    (
+        DW_TAG_BASE_TYPE,
+        false,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_ENCODING, DW_FORM_DATA1),
+            (DW_AT_BYTE_SIZE, DW_FORM_DATA1),
+        ],
+    ),
+    (
+        DW_TAG_POINTER_TYPE,
+        false,
+        &[(DW_AT_BYTE_SIZE, DW_FORM_DATA1), (DW_AT_TYPE, DW_FORM_REF4)],
+    ),
+    (
+        DW_TAG_POINTER_TYPE,
+        false,
+        &[(DW_AT_BYTE_SIZE, DW_FORM_DATA1)],
+    ),
+    (DW_TAG_ARRAY_TYPE, true, &[(DW_AT_TYPE, DW_FORM_REF4)]),
+    (
+        DW_TAG_SUBRANGE_TYPE,
+        false,
+        &[(DW_AT_UPPER_BOUND, DW_FORM_DATA8)],
+    ),
+    (DW_TAG_CONST_TYPE, false, &[(DW_AT_TYPE, DW_FORM_REF4)]),
+    (DW_TAG_VOLATILE_TYPE, false, &[(DW_AT_TYPE, DW_FORM_REF4)]),
+    (DW_TAG_RESTRICT_TYPE, false, &[(DW_AT_TYPE, DW_FORM_REF4)]),
+];
+
+fn sleb128(mut n: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+// qualified types are described by a chain of entries with a single qualifier each,
+// returns the entry for the outermost one and the type it refers to
+fn peel_qualifier(qualifiers: Qualifiers, inner: &NEWTypes) -> (Abbrev, NEWTypes) {
+    let mut rest = qualifiers;
+    let abbrev = if rest.is_const {
+        rest.is_const = false;
+        Abbrev::ConstType
+    } else if rest.is_volatile {
+        rest.is_volatile = false;
+        Abbrev::VolatileType
+    } else {
+        rest.is_restrict = false;
+        Abbrev::RestrictType
+    };
+    (abbrev, inner.clone().qualify(rest))
+}
+
+// escapes a string so that it can be used inside of a directive
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+struct Variable {
+    name: String,
+    type_decl: NEWTypes,
+    bp_offset: usize,
+}
+struct FunctionInfo {
+    name: String,
+    is_static: bool,
+    line: usize,
+    return_type: NEWTypes,
+    params: Vec<Variable>,
+    locals: Vec<Variable>,
+}
+
+// collects the functions and variables of a module to describe them in DWARF
+pub struct DebugInfo {
+    file: String,
+    comp_dir: String,
+    functions: Vec<FunctionInfo>,
+    // symbol-name, type and whether the global has internal linkage
+    globals: Vec<(String, NEWTypes, bool)>,
+    // every type which is referenced, described in the order they were first used
+    types: Vec<NEWTypes>,
+}
+impl DebugInfo {
+    pub fn new(file: &str) -> Self {
+        let comp_dir = std::env::current_dir()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_else(|_| ".".to_string());
+        DebugInfo {
+            file: escape(file),
+            comp_dir: escape(&comp_dir),
+            functions: Vec::new(),
+            globals: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+    pub fn file_directive(&self) -> Instr {
+        Instr::Directive(Directive::File(self.file.clone()))
+    }
+    // label after the last instruction of a function
+    pub fn end_label(name: &str) -> String {
+        format!(".L{}_end", name)
+    }
+
+    fn add_type(&mut self, type_decl: &NEWTypes) {
+        match type_decl {
+            _ if type_decl.is_void() => return,
+            NEWTypes::Function { .. } => return,
+            NEWTypes::Pointer(to) | NEWTypes::Array { of: to, .. } => self.add_type(to),
+            NEWTypes::Qualified(qualifiers, inner) => {
+                self.add_type(&peel_qualifier(*qualifiers, inner).1)
+            }
+            NEWTypes::Primitive(_) => (),
+        }
+        if !self.types.contains(type_decl) {
+            self.types.push(type_decl.clone());
+        }
+    }
+    pub fn add_global(&mut self, global: &Global) {
+        self.add_type(&global.type_decl);
+        self.globals.push((
+            global.name.clone(),
+            global.type_decl.clone(),
+            global.is_static,
+        ));
+    }
+    // bp_offsets holds the stack-location of every slot in the function
+    pub fn add_function(&mut self, function: &Function, bp_offsets: &[usize]) {
+        self.add_type(&function.return_type);
+
+        let mut params = Vec::new();
+        let mut locals = Vec::new();
+        for (slot, type_decl) in function.slots.iter().enumerate() {
+            self.add_type(type_decl);
+            let var = Variable {
+                name: function.slot_names[slot].clone(),
+                type_decl: type_decl.clone(),
+                bp_offset: bp_offsets[slot],
+            };
+            if function.params.iter().any(|(_, param)| *param == slot) {
+                params.push(var);
+            } else {
+                locals.push(var);
+            }
+        }
+        self.functions.push(FunctionInfo {
+            name: function.name.clone(),
+            is_static: function.is_static,
+            line: function.line,
+            return_type: function.return_type.clone(),
+            params,
+            locals,
+        });
+    }
+
+    // generates the .debug_abbrev and .debug_info sections, the line-table is generated by the
+    // assembler from the .loc directives
+    pub fn sections(&self) -> Vec<Instr> {
+        let (first, last) = match (self.functions.first(), self.functions.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Vec::new(),
+        };
+        let mut out = DwarfWriter(Vec::new());
+
+        out.section(".debug_abbrev");
+        out.label(".Ldebug_abbrev0");
+        for (i, (tag, children, attrs)) in ABBREVIATIONS.iter().enumerate() {
+            out.bytes(&[i as u8 + 1, *tag, *children as u8]);
+            for (attr, form) in attrs.iter() {
+                out.bytes(&[*attr, *form]);
+            }
+            out.bytes(&[0, 0]);
+        }
+        out.bytes(&[0]);
+
+        out.section(".debug_info");
+        out.label(".Ldebug_info0");
+        out.directive(Directive::Difference(
+            Size::Long,
+            ".Ldebug_info_end0".to_string(),
+            ".Ldebug_info_start0".to_string(),
+        ));
+        out.label(".Ldebug_info_start0");
+        out.bytes(&[4, 0]); // version
+        out.directive(Directive::Offset(".Ldebug_abbrev0".to_string()));
+        out.bytes(&[8]); // address-size
+
+        out.bytes(&[Abbrev::CompileUnit as u8]);
+        out.string("rucc");
+        out.bytes(&[DW_LANG_C89]);
+        out.string(&self.file);
+        out.string(&self.comp_dir);
+        out.range(&first.name, &last.name);
+        out.directive(Directive::Offset(".Ldebug_line0".to_string()));
+
+        for (i, type_decl) in self.types.iter().enumerate() {
+            out.label(&format!(".Ldebug_type{}", i));
+            match type_decl {
+                NEWTypes::Primitive(t) => {
+                    out.bytes(&[Abbrev::BaseType as u8]);
+                    out.string(&type_decl.to_string());
+                    out.bytes(&[
+                        if *t == Types::Char {
+                            DW_ATE_SIGNED_CHAR
+                        } else {
+                            DW_ATE_SIGNED
+                        },
+                        t.size() as u8,
+                    ]);
+                }
+                // function-pointers are described as untyped pointers
+                NEWTypes::Pointer(to) if to.is_void() || to.is_func() => {
+                    out.bytes(&[Abbrev::VoidPointerType as u8, 8]);
+                }
+                NEWTypes::Pointer(to) => {
+                    out.bytes(&[Abbrev::PointerType as u8, 8]);
+                    out.type_ref(self.type_index(to));
+                }
+                NEWTypes::Array { amount, of } => {
+                    out.bytes(&[Abbrev::ArrayType as u8]);
+                    out.type_ref(self.type_index(of));
+                    out.bytes(&[Abbrev::SubrangeType as u8]);
+                    out.directive(Directive::Value(Size::Quad, *amount as i64 - 1));
+                    out.bytes(&[0]);
+                }
+                NEWTypes::Qualified(qualifiers, inner) => {
+                    let (abbrev, rest) = peel_qualifier(*qualifiers, inner);
+                    out.bytes(&[abbrev as u8]);
+                    out.type_ref(self.type_index(&rest));
+                }
+                NEWTypes::Function { .. } => unreachable!("functions are never added as types"),
+            }
+        }
+
+        for (name, type_decl, is_static) in self.globals.iter() {
+            out.bytes(&[Abbrev::GlobalVariable as u8]);
+            // static locals are described by their source-name instead of their unique symbol
+            out.string(name.split('.').next().unwrap());
+            out.type_ref(self.type_index(type_decl));
+            out.bytes(&[!is_static as u8]);
+            out.bytes(&[9, DW_OP_ADDR]);
+            out.directive(Directive::Address(name.clone()));
+        }
+
+        for function in self.functions.iter() {
+            out.bytes(&[if function.return_type.is_void() {
+                Abbrev::VoidSubprogram
+            } else {
+                Abbrev::Subprogram
+            } as u8]);
+            out.bytes(&[!function.is_static as u8]);
+            out.string(&function.name);
+            out.bytes(&[1]); // file
+            out.directive(Directive::Value(Size::Long, function.line as i64));
+            if !function.return_type.is_void() {
+                out.type_ref(self.type_index(&function.return_type));
+            }
+            out.range(&function.name, &function.name);
+            // locals are addressed relative to the base-pointer
+            out.bytes(&[2, DW_OP_BREG6, 0]);
+
+            for (abbrev, vars) in [
+                (Abbrev::Parameter, &function.params),
+                (Abbrev::Variable, &function.locals),
+            ] {
+                for var in vars {
+                    out.bytes(&[abbrev as u8]);
+                    out.string(&var.name);
+                    out.type_ref(self.type_index(&var.type_decl));
+                    let offset = sleb128(-(var.bp_offset as i64));
+                    out.bytes(&[offset.len() as u8 + 1, DW_OP_FBREG]);
+                    out.bytes(&offset);
+                }
+            }
+            out.bytes(&[0]);
+        }
+        out.bytes(&[0]);
+        out.label(".Ldebug_info_end0");
+
+        out.section(".debug_line");
+        out.label(".Ldebug_line0");
+
+        out.0
+    }
+    fn type_index(&self, type_decl: &NEWTypes) -> usize {
+        self.types
+            .iter()
+            .position(|t| t == type_decl)
+            .expect("all types are added before emitting")
+    }
+}
+
+struct DwarfWriter(Vec<Instr>);
+impl DwarfWriter {
+    fn directive(&mut self, directive: Directive) {
+        self.0.push(Instr::Directive(directive));
+    }
+    fn label(&mut self, name: &str) {
+        self.0.push(Instr::Label(name.to_string()));
+    }
+    fn section(&mut self, name: &str) {
+        self.directive(Directive::Section(format!("{},\"\",@progbits", name)));
+    }
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.directive(Directive::Value(Size::Byte, *byte as i64));
+        }
+    }
+    // string-directives hold one char per byte like decoded string-literals
+    fn string(&mut self, s: &str) {
+        self.directive(Directive::String(s.bytes().map(char::from).collect()));
+    }
+    fn type_ref(&mut self, index: usize) {
+        self.directive(Directive::Difference(
+            Size::Long,
+            format!(".Ldebug_type{}", index),
+            ".Ldebug_info0".to_string(),
+        ));
+    }
+    // low_pc and high_pc spanning from the start of the first to the end of the last function
+    fn range(&mut self, first: &str, last: &str) {
+        self.directive(Directive::Address(first.to_string()));
+        self.directive(Directive::Difference(
+            Size::Quad,
+            DebugInfo::end_label(last),
+            first.to_string(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_signed_leb128() {
+        assert_eq!(sleb128(-8), vec![0x78]);
+        assert_eq!(sleb128(-128), vec![0x80, 0x7f]);
+        assert_eq!(sleb128(63), vec![0x3f]);
+        assert_eq!(sleb128(64), vec![0xc0, 0x00]);
+    }
+}
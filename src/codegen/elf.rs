@@ -0,0 +1,319 @@
+use crate::codegen::asm::SymbolType;
+use crate::codegen::assembler::*;
+use crate::codegen::encoder::RelocKind;
+use std::collections::HashMap;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+
+const SYMBOL_SIZE: u64 = 24;
+const RELA_SIZE: u64 = 24;
+const SECTION_HEADER_SIZE: u16 = 64;
+
+struct StringTable {
+    bytes: Vec<u8>,
+}
+impl StringTable {
+    fn new() -> Self {
+        StringTable { bytes: vec![0] }
+    }
+    fn add(&mut self, s: &str) -> u32 {
+        let index = self.bytes.len() as u32;
+        self.bytes.extend(s.as_bytes());
+        self.bytes.push(0);
+        index
+    }
+}
+
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+impl SectionHeader {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.name.to_le_bytes());
+        out.extend(self.kind.to_le_bytes());
+        out.extend(self.flags.to_le_bytes());
+        out.extend(0u64.to_le_bytes()); // address
+        out.extend(self.offset.to_le_bytes());
+        out.extend(self.size.to_le_bytes());
+        out.extend(self.link.to_le_bytes());
+        out.extend(self.info.to_le_bytes());
+        out.extend(self.align.to_le_bytes());
+        out.extend(self.entsize.to_le_bytes());
+    }
+}
+
+fn write_symbol(out: &mut Vec<u8>, name: u32, info: u8, section: u16, value: u64, size: u64) {
+    out.extend(name.to_le_bytes());
+    out.push(info);
+    out.push(0); // visibility
+    out.extend(section.to_le_bytes());
+    out.extend(value.to_le_bytes());
+    out.extend(size.to_le_bytes());
+}
+
+fn align_to(out: &mut Vec<u8>, align: usize) {
+    while !out.len().is_multiple_of(align) {
+        out.push(0);
+    }
+}
+
+// serializes the object into a relocatable ELF64 file for x86-64
+pub fn write_elf(object: &Object) -> Vec<u8> {
+    // order of the sections in the file, .rela sections directly follow the section they patch.
+    // functions aren't aligned individually so the text-section is aligned like gas does
+    let layout = [
+        (SectionKind::Text, ".text", SHF_ALLOC | SHF_EXECINSTR, 16),
+        (SectionKind::Data, ".data", SHF_ALLOC | SHF_WRITE, 1),
+        (SectionKind::Bss, ".bss", SHF_ALLOC | SHF_WRITE, 1),
+        (SectionKind::Rodata, ".rodata", SHF_ALLOC, 1),
+    ];
+    let mut section_index: HashMap<SectionKind, u16> = HashMap::new();
+    let mut index = 1;
+    for (kind, ..) in layout.iter() {
+        section_index.insert(*kind, index);
+        index += if object.section(*kind).relocs.is_empty() {
+            1
+        } else {
+            2
+        };
+    }
+
+    // locals have to come before globals in the symbol-table
+    let mut strtab = StringTable::new();
+    let mut symtab = Vec::new();
+    write_symbol(&mut symtab, 0, 0, 0, 0, 0);
+    for (kind, ..) in layout.iter() {
+        write_symbol(
+            &mut symtab,
+            0,
+            STB_LOCAL << 4 | STT_SECTION,
+            section_index[kind],
+            0,
+            0,
+        );
+    }
+    let (locals, globals): (Vec<&Symbol>, Vec<&Symbol>) =
+        object.symbols.iter().partition(|s| !s.global);
+
+    let mut symbol_index: HashMap<&str, u32> = HashMap::new();
+    let mut count = 1 + layout.len() as u32;
+    for symbol in locals.iter().chain(globals.iter()) {
+        let kind = match symbol.kind {
+            Some(SymbolType::Function) => STT_FUNC,
+            Some(SymbolType::Object) => STT_OBJECT,
+            None => STT_NOTYPE,
+        };
+        let bind = if symbol.global { STB_GLOBAL } else { STB_LOCAL };
+        write_symbol(
+            &mut symtab,
+            strtab.add(&symbol.name),
+            bind << 4 | kind,
+            section_index[&symbol.section],
+            symbol.offset as u64,
+            symbol.size as u64,
+        );
+        symbol_index.insert(&symbol.name, count);
+        count += 1;
+    }
+    let first_global = 1 + layout.len() as u32 + locals.len() as u32;
+
+    // referenced symbols which aren't defined in this object are resolved by the linker
+    for section in object.sections.iter() {
+        for reloc in section.relocs.iter() {
+            if let RelocTarget::Symbol(name) = &reloc.target {
+                if !symbol_index.contains_key(name.as_str()) {
+                    write_symbol(
+                        &mut symtab,
+                        strtab.add(name),
+                        STB_GLOBAL << 4 | STT_NOTYPE,
+                        0,
+                        0,
+                        0,
+                    );
+                    symbol_index.insert(name, count);
+                    count += 1;
+                }
+            }
+        }
+    }
+
+    let mut shstrtab = StringTable::new();
+    let mut headers = vec![SectionHeader {
+        name: 0,
+        kind: 0,
+        flags: 0,
+        offset: 0,
+        size: 0,
+        link: 0,
+        info: 0,
+        align: 0,
+        entsize: 0,
+    }];
+    let mut out = vec![0; 64];
+    let symtab_index = index as u32 + 1; // after .note.GNU-stack
+
+    for (kind, name, flags, min_align) in layout.iter() {
+        let section = object.section(*kind);
+        let align = section.align.max(*min_align);
+        align_to(&mut out, align);
+
+        headers.push(SectionHeader {
+            name: shstrtab.add(name),
+            kind: if *kind == SectionKind::Bss {
+                SHT_NOBITS
+            } else {
+                SHT_PROGBITS
+            },
+            flags: *flags,
+            offset: out.len() as u64,
+            size: section.bytes.len() as u64,
+            link: 0,
+            info: 0,
+            align: align as u64,
+            entsize: 0,
+        });
+        if *kind != SectionKind::Bss {
+            out.extend(&section.bytes);
+        }
+
+        if section.relocs.is_empty() {
+            continue;
+        }
+        align_to(&mut out, 8);
+        let offset = out.len() as u64;
+        for reloc in section.relocs.iter() {
+            let symbol = match &reloc.target {
+                RelocTarget::Symbol(name) => symbol_index[name.as_str()],
+                RelocTarget::Section(kind) => {
+                    1 + layout.iter().position(|(k, ..)| k == kind).unwrap() as u32
+                }
+            };
+            let kind: u64 = match reloc.kind {
+                RelocKind::Abs64 => 1,
+                RelocKind::Pc32 => 2,
+                RelocKind::Plt32 => 4,
+                RelocKind::GotPcRel => 9,
+            };
+            out.extend((reloc.offset as u64).to_le_bytes());
+            out.extend(((symbol as u64) << 32 | kind).to_le_bytes());
+            out.extend(reloc.addend.to_le_bytes());
+        }
+        headers.push(SectionHeader {
+            name: shstrtab.add(&format!(".rela{}", name)),
+            kind: SHT_RELA,
+            flags: SHF_INFO_LINK,
+            offset,
+            size: section.relocs.len() as u64 * RELA_SIZE,
+            link: symtab_index,
+            info: section_index[kind] as u32,
+            align: 8,
+            entsize: RELA_SIZE,
+        });
+    }
+
+    // marks the stack as non-executable
+    headers.push(SectionHeader {
+        name: shstrtab.add(".note.GNU-stack"),
+        kind: SHT_PROGBITS,
+        flags: 0,
+        offset: out.len() as u64,
+        size: 0,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    });
+
+    align_to(&mut out, 8);
+    headers.push(SectionHeader {
+        name: shstrtab.add(".symtab"),
+        kind: SHT_SYMTAB,
+        flags: 0,
+        offset: out.len() as u64,
+        size: symtab.len() as u64,
+        link: symtab_index + 1,
+        info: first_global,
+        align: 8,
+        entsize: SYMBOL_SIZE,
+    });
+    out.extend(symtab);
+
+    headers.push(SectionHeader {
+        name: shstrtab.add(".strtab"),
+        kind: SHT_STRTAB,
+        flags: 0,
+        offset: out.len() as u64,
+        size: strtab.bytes.len() as u64,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    });
+    out.extend(&strtab.bytes);
+
+    let shstrtab_name = shstrtab.add(".shstrtab");
+    headers.push(SectionHeader {
+        name: shstrtab_name,
+        kind: SHT_STRTAB,
+        flags: 0,
+        offset: out.len() as u64,
+        size: shstrtab.bytes.len() as u64,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    });
+    out.extend(&shstrtab.bytes);
+
+    align_to(&mut out, 8);
+    let section_headers = out.len() as u64;
+    for header in headers.iter() {
+        header.write(&mut out);
+    }
+
+    // elf-header
+    let mut header = Vec::new();
+    header.extend(b"\x7fELF");
+    header.extend([2, 1, 1, 0]); // 64bit, little-endian, version 1, System V ABI
+    header.extend([0; 8]);
+    header.extend(1u16.to_le_bytes()); // relocatable
+    header.extend(62u16.to_le_bytes()); // x86-64
+    header.extend(1u32.to_le_bytes());
+    header.extend(0u64.to_le_bytes()); // entry
+    header.extend(0u64.to_le_bytes()); // program-headers
+    header.extend(section_headers.to_le_bytes());
+    header.extend(0u32.to_le_bytes()); // flags
+    header.extend(64u16.to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    header.extend(SECTION_HEADER_SIZE.to_le_bytes());
+    header.extend((headers.len() as u16).to_le_bytes());
+    header.extend((headers.len() as u16 - 1).to_le_bytes()); // .shstrtab is last
+    out[..64].copy_from_slice(&header);
+
+    out
+}
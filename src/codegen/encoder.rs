@@ -0,0 +1,567 @@
+use crate::codegen::asm::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RelocKind {
+    // 32bit offset relative to the end of the displacement
+    Pc32,
+    // same as Pc32 but allows the linker to go through the procedure linkage table
+    Plt32,
+    // absolute 64bit address
+    Abs64,
+    // 32bit offset to the symbol's entry in the global-offset-table
+    GotPcRel,
+}
+
+// reference to a label whose address is only known after layout or linking
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fixup {
+    pub offset: usize,
+    pub label: String,
+    pub kind: RelocKind,
+    pub addend: i64,
+}
+
+#[derive(Default, PartialEq, Debug)]
+pub struct Encoding {
+    pub bytes: Vec<u8>,
+    pub fixup: Option<Fixup>,
+}
+
+enum Imm {
+    None,
+    Byte(i64),
+    Long(i64),
+}
+impl Imm {
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Imm::None => vec![],
+            Imm::Byte(n) => vec![*n as i8 as u8],
+            Imm::Long(n) => (*n as i32).to_le_bytes().to_vec(),
+        }
+    }
+}
+
+// register number used in the ModRM byte and opcode
+fn number(reg: Reg) -> u8 {
+    match reg {
+        Reg::Rax => 0,
+        Reg::Rcx => 1,
+        Reg::Rdx => 2,
+        Reg::Rsp => 4,
+        Reg::Rbp => 5,
+        Reg::Rsi => 6,
+        Reg::Rdi => 7,
+        Reg::R8 => 8,
+        Reg::R9 => 9,
+        Reg::R10 => 10,
+        Reg::R11 => 11,
+    }
+}
+fn fits_i8(n: i64) -> bool {
+    i8::try_from(n).is_ok()
+}
+fn imm32(n: i64) -> i64 {
+    assert!(
+        i32::try_from(n).is_ok(),
+        "immediate {} doesn't fit into 32bits",
+        n
+    );
+    n
+}
+fn cond_code(cond: Cond) -> u8 {
+    match cond {
+        Cond::E => 0x4,
+        Cond::Ne => 0x5,
+        Cond::L => 0xc,
+        Cond::Ge => 0xd,
+        Cond::Le => 0xe,
+        Cond::G => 0xf,
+    }
+}
+// %spl, %bpl, %sil and %dil can only be accessed with a rex-prefix
+fn needs_rex(op: &Operand) -> bool {
+    matches!(op, Operand::Reg(reg, Size::Byte) if (4..8).contains(&number(*reg)))
+}
+
+// the reg-field of the ModRM byte either holds a register or an opcode extension
+enum Field<'a> {
+    Reg(&'a Operand),
+    Ext(u8),
+}
+
+// encodes an instruction taking a ModRM byte: [rex] opcode modrm [sib] [disp] [imm]
+fn modrm(opcode: &[u8], field: Field, rm: &Operand, size: Size, imm: Imm) -> Encoding {
+    let mut rex = if size == Size::Quad { 0x48 } else { 0 };
+    let reg = match field {
+        Field::Reg(op) => {
+            if needs_rex(op) {
+                rex |= 0x40;
+            }
+            number(op.reg().expect("reg-field has to be a register"))
+        }
+        Field::Ext(n) => n,
+    };
+    if reg >= 8 {
+        rex |= 0x44;
+    }
+
+    let mut tail = Vec::new();
+    let mut label = None;
+    match rm {
+        Operand::Reg(r, _) => {
+            if needs_rex(rm) {
+                rex |= 0x40;
+            }
+            if number(*r) >= 8 {
+                rex |= 0x41;
+            }
+            tail.push(0xc0 | (reg & 7) << 3 | number(*r) & 7);
+        }
+        Operand::Mem { base, offset, .. } => {
+            let base = number(*base);
+            if base >= 8 {
+                rex |= 0x41;
+            }
+            // %rbp and %r13 as base always need a displacement
+            let (mode, disp) = if *offset == 0 && base & 7 != 5 {
+                (0, vec![])
+            } else if fits_i8(*offset) {
+                (1, vec![*offset as i8 as u8])
+            } else {
+                (2, (imm32(*offset) as i32).to_le_bytes().to_vec())
+            };
+            tail.push(mode << 6 | (reg & 7) << 3 | base & 7);
+            // %rsp and %r12 as base need a sib-byte
+            if base & 7 == 4 {
+                tail.push(0x24);
+            }
+            tail.extend(disp);
+        }
+        Operand::Label(name, _) => {
+            tail.push(0x05 | (reg & 7) << 3);
+            tail.extend([0; 4]);
+            label = Some((name.clone(), RelocKind::Pc32));
+        }
+        Operand::Got(name) => {
+            tail.push(0x05 | (reg & 7) << 3);
+            tail.extend([0; 4]);
+            label = Some((name.clone(), RelocKind::GotPcRel));
+        }
+        Operand::Imm(_) => unreachable!("immediate can't be encoded in ModRM"),
+    }
+
+    let mut bytes = Vec::new();
+    if rex != 0 {
+        bytes.push(rex);
+    }
+    bytes.extend(opcode);
+    // the displacement is relative to the end of the instruction
+    let imm = imm.bytes();
+    let fixup = label.map(|(label, kind)| Fixup {
+        offset: bytes.len() + 1,
+        label,
+        kind,
+        addend: -4 - imm.len() as i64,
+    });
+    bytes.extend(tail);
+    bytes.extend(imm);
+
+    Encoding { bytes, fixup }
+}
+
+// instructions which encode a register in the lower bits of the opcode
+fn opcode_reg(opcode: u8, reg: &Operand, size: Size, imm: &[u8]) -> Encoding {
+    let n = number(reg.reg().expect("opcode can only encode registers"));
+    let mut rex = if size == Size::Quad { 0x48 } else { 0 };
+    if n >= 8 {
+        rex |= 0x41;
+    }
+    if needs_rex(reg) {
+        rex |= 0x40;
+    }
+    let mut bytes = Vec::new();
+    if rex != 0 {
+        bytes.push(rex);
+    }
+    bytes.push(opcode + (n & 7));
+    bytes.extend(imm);
+
+    Encoding { bytes, fixup: None }
+}
+
+fn size_of(op: &Operand) -> Size {
+    op.size().expect("operand has to have a size")
+}
+
+// add, or, and, sub, xor and cmp share the same encodings with a different opcode-extension
+fn arithmetic(ext: u8, src: &Operand, dest: &Operand) -> Encoding {
+    let size = size_of(dest);
+    let byte = size == Size::Byte;
+
+    match src {
+        Operand::Imm(n) if byte && dest.reg() == Some(Reg::Rax) => Encoding {
+            bytes: vec![ext << 3 | 4, *n as i8 as u8],
+            fixup: None,
+        },
+        Operand::Imm(n) if byte => modrm(&[0x80], Field::Ext(ext), dest, size, Imm::Byte(*n)),
+        Operand::Imm(n) if fits_i8(*n) => {
+            modrm(&[0x83], Field::Ext(ext), dest, size, Imm::Byte(*n))
+        }
+        // short form for the accumulator
+        Operand::Imm(n) if dest.reg() == Some(Reg::Rax) => {
+            let mut bytes = if size == Size::Quad {
+                vec![0x48]
+            } else {
+                vec![]
+            };
+            bytes.push(ext << 3 | 5);
+            bytes.extend((imm32(*n) as i32).to_le_bytes());
+            Encoding { bytes, fixup: None }
+        }
+        Operand::Imm(n) => modrm(&[0x81], Field::Ext(ext), dest, size, Imm::Long(imm32(*n))),
+        Operand::Reg(..) => modrm(
+            &[ext << 3 | if byte { 0 } else { 1 }],
+            Field::Reg(src),
+            dest,
+            size,
+            Imm::None,
+        ),
+        _ => modrm(
+            &[ext << 3 | if byte { 2 } else { 3 }],
+            Field::Reg(dest),
+            src,
+            size,
+            Imm::None,
+        ),
+    }
+}
+// neg, not, idiv and shifts only take a single operand and an opcode-extension
+fn unary(opcode: u8, ext: u8, op: &Operand, imm: Imm) -> Encoding {
+    let size = size_of(op);
+    let opcode = if size == Size::Byte {
+        opcode
+    } else {
+        opcode + 1
+    };
+    modrm(&[opcode], Field::Ext(ext), op, size, imm)
+}
+
+// encodes every instruction except for labels, directives and jumps whose size
+// depends on the layout
+pub fn encode(instr: &Instr) -> Encoding {
+    match instr {
+        Instr::Mov(Operand::Imm(n), dest @ Operand::Reg(..)) => match size_of(dest) {
+            Size::Byte => opcode_reg(0xb0, dest, Size::Byte, &[*n as i8 as u8]),
+            Size::Long => opcode_reg(0xb8, dest, Size::Long, &(*n as i32).to_le_bytes()),
+            Size::Quad if i32::try_from(*n).is_ok() => {
+                modrm(&[0xc7], Field::Ext(0), dest, Size::Quad, Imm::Long(*n))
+            }
+            Size::Quad => opcode_reg(0xb8, dest, Size::Quad, &n.to_le_bytes()),
+        },
+        Instr::Mov(Operand::Imm(n), dest) => match size_of(dest) {
+            Size::Byte => modrm(&[0xc6], Field::Ext(0), dest, Size::Byte, Imm::Byte(*n)),
+            size => modrm(&[0xc7], Field::Ext(0), dest, size, Imm::Long(imm32(*n))),
+        },
+        Instr::Mov(src @ Operand::Reg(..), dest) => {
+            let size = size_of(dest);
+            let opcode = if size == Size::Byte { 0x88 } else { 0x89 };
+            modrm(&[opcode], Field::Reg(src), dest, size, Imm::None)
+        }
+        Instr::Mov(src, dest) => {
+            let size = size_of(dest);
+            let opcode = if size == Size::Byte { 0x8a } else { 0x8b };
+            modrm(&[opcode], Field::Reg(dest), src, size, Imm::None)
+        }
+        Instr::Movs(src, dest) => match size_of(src) {
+            Size::Byte => modrm(
+                &[0x0f, 0xbe],
+                Field::Reg(dest),
+                src,
+                size_of(dest),
+                Imm::None,
+            ),
+            _ => modrm(&[0x63], Field::Reg(dest), src, Size::Quad, Imm::None),
+        },
+        Instr::Movz(src, dest) => modrm(
+            &[0x0f, 0xb6],
+            Field::Reg(dest),
+            src,
+            size_of(dest),
+            Imm::None,
+        ),
+        Instr::Lea(src, dest) => modrm(&[0x8d], Field::Reg(dest), src, Size::Quad, Imm::None),
+        Instr::Alu(AluOp::Imul, Operand::Imm(n), dest) => {
+            let (opcode, imm) = if fits_i8(*n) {
+                (0x6b, Imm::Byte(*n))
+            } else {
+                (0x69, Imm::Long(imm32(*n)))
+            };
+            modrm(&[opcode], Field::Reg(dest), dest, size_of(dest), imm)
+        }
+        Instr::Alu(AluOp::Imul, src, dest) => modrm(
+            &[0x0f, 0xaf],
+            Field::Reg(dest),
+            src,
+            size_of(dest),
+            Imm::None,
+        ),
+        Instr::Alu(op, src, dest) => arithmetic(
+            match op {
+                AluOp::Add => 0,
+                AluOp::Or => 1,
+                AluOp::And => 4,
+                AluOp::Sub => 5,
+                AluOp::Xor => 6,
+                AluOp::Imul => unreachable!(),
+            },
+            src,
+            dest,
+        ),
+        Instr::Cmp(left, right) => arithmetic(7, left, right),
+        Instr::Test(left, right) => {
+            let size = size_of(right);
+            let opcode = if size == Size::Byte { 0x84 } else { 0x85 };
+            modrm(&[opcode], Field::Reg(left), right, size, Imm::None)
+        }
+        Instr::Shift(op, count, dest) => {
+            let ext = match op {
+                ShiftOp::Sal => 4,
+                ShiftOp::Sar => 7,
+            };
+            match count {
+                Operand::Imm(1) => unary(0xd0, ext, dest, Imm::None),
+                Operand::Imm(n) => unary(0xc0, ext, dest, Imm::Byte(*n)),
+                // shift amount is in %cl
+                _ => unary(0xd2, ext, dest, Imm::None),
+            }
+        }
+        Instr::Neg(op) => unary(0xf6, 3, op, Imm::None),
+        Instr::Not(op) => unary(0xf6, 2, op, Imm::None),
+        Instr::Idiv(op) => unary(0xf6, 7, op, Imm::None),
+        Instr::Set(cond, op) => modrm(
+            &[0x0f, 0x90 | cond_code(*cond)],
+            Field::Ext(0),
+            op,
+            Size::Byte,
+            Imm::None,
+        ),
+        Instr::Cdq => Encoding {
+            bytes: vec![0x99],
+            fixup: None,
+        },
+        Instr::Cqo => Encoding {
+            bytes: vec![0x48, 0x99],
+            fixup: None,
+        },
+        Instr::Push(reg) => opcode_reg(0x50, &Operand::Reg(*reg, Size::Long), Size::Long, &[]),
+        Instr::Pop(reg) => opcode_reg(0x58, &Operand::Reg(*reg, Size::Long), Size::Long, &[]),
+        Instr::Call(name, _) => Encoding {
+            bytes: vec![0xe8, 0, 0, 0, 0],
+            fixup: Some(Fixup {
+                offset: 1,
+                label: name.clone(),
+                kind: RelocKind::Plt32,
+                addend: -4,
+            }),
+        },
+        // operand-size is always 64 bit so no REX.W is needed
+        Instr::CallIndirect(reg, _) => modrm(
+            &[0xff],
+            Field::Ext(2),
+            &Operand::Reg(*reg, Size::Long),
+            Size::Long,
+            Imm::None,
+        ),
+        Instr::Ret => Encoding {
+            bytes: vec![0xc3],
+            fixup: None,
+        },
+        Instr::Jmp(_)
+        | Instr::Jcc(..)
+        | Instr::Label(_)
+        | Instr::Directive(_)
+        | Instr::Comment(_) => {
+            unreachable!("encoded by the assembler")
+        }
+    }
+}
+
+// encodes a jump with a displacement relative to the end of the jump
+pub fn jump(cond: Option<Cond>, short: bool, disp: i64) -> Vec<u8> {
+    let mut bytes = match (cond, short) {
+        (None, true) => vec![0xeb],
+        (None, false) => vec![0xe9],
+        (Some(cond), true) => vec![0x70 | cond_code(cond)],
+        (Some(cond), false) => vec![0x0f, 0x80 | cond_code(cond)],
+    };
+    if short {
+        bytes.push(disp as i8 as u8);
+    } else {
+        bytes.extend((disp as i32).to_le_bytes());
+    }
+    bytes
+}
+pub fn jump_size(cond: Option<Cond>, short: bool) -> usize {
+    jump(cond, short, 0).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(instr: Instr) -> Vec<u8> {
+        encode(&instr).bytes
+    }
+    fn reg(reg: Reg, size: Size) -> Operand {
+        Operand::Reg(reg, size)
+    }
+    fn local(offset: i64, size: Size) -> Operand {
+        Operand::Mem {
+            base: Reg::Rbp,
+            offset,
+            size,
+        }
+    }
+
+    // expected bytes are the output of the GNU assembler
+    #[test]
+    fn encodes_moves() {
+        assert_eq!(
+            bytes(Instr::Mov(
+                reg(Reg::Rsp, Size::Quad),
+                reg(Reg::Rbp, Size::Quad)
+            )),
+            [0x48, 0x89, 0xe5]
+        );
+        assert_eq!(
+            bytes(Instr::Mov(
+                reg(Reg::Rsi, Size::Long),
+                local(-12, Size::Long)
+            )),
+            [0x89, 0x75, 0xf4]
+        );
+        assert_eq!(
+            bytes(Instr::Mov(local(-24, Size::Long), reg(Reg::R8, Size::Long))),
+            [0x44, 0x8b, 0x45, 0xe8]
+        );
+        assert_eq!(
+            bytes(Instr::Mov(Operand::Imm(5), reg(Reg::Rax, Size::Long))),
+            [0xb8, 5, 0, 0, 0]
+        );
+        assert_eq!(
+            bytes(Instr::Mov(Operand::Imm(-4), local(-300, Size::Long))),
+            [0xc7, 0x85, 0xd4, 0xfe, 0xff, 0xff, 0xfc, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            bytes(Instr::Mov(
+                reg(Reg::Rdi, Size::Byte),
+                Operand::Mem {
+                    base: Reg::R10,
+                    offset: 0,
+                    size: Size::Byte
+                }
+            )),
+            [0x41, 0x88, 0x3a]
+        );
+        assert_eq!(
+            bytes(Instr::Movs(
+                reg(Reg::R11, Size::Long),
+                reg(Reg::R10, Size::Quad)
+            )),
+            [0x4d, 0x63, 0xd3]
+        );
+        assert_eq!(
+            bytes(Instr::Movz(
+                reg(Reg::Rax, Size::Byte),
+                reg(Reg::R9, Size::Long)
+            )),
+            [0x44, 0x0f, 0xb6, 0xc8]
+        );
+    }
+    #[test]
+    fn encodes_arithmetic() {
+        assert_eq!(
+            bytes(Instr::Alu(
+                AluOp::Add,
+                reg(Reg::R9, Size::Long),
+                reg(Reg::R10, Size::Long)
+            )),
+            [0x45, 0x01, 0xca]
+        );
+        assert_eq!(
+            bytes(Instr::Alu(
+                AluOp::Sub,
+                Operand::Imm(48),
+                reg(Reg::Rsp, Size::Quad)
+            )),
+            [0x48, 0x83, 0xec, 0x30]
+        );
+        assert_eq!(
+            bytes(Instr::Alu(
+                AluOp::Add,
+                Operand::Imm(1000),
+                reg(Reg::Rax, Size::Long)
+            )),
+            [0x05, 0xe8, 0x03, 0, 0]
+        );
+        assert_eq!(
+            bytes(Instr::Alu(
+                AluOp::Imul,
+                Operand::Imm(4),
+                reg(Reg::R10, Size::Long)
+            )),
+            [0x45, 0x6b, 0xd2, 0x04]
+        );
+        assert_eq!(
+            bytes(Instr::Cmp(
+                local(-40, Size::Long),
+                reg(Reg::Rax, Size::Long)
+            )),
+            [0x3b, 0x45, 0xd8]
+        );
+        assert_eq!(
+            bytes(Instr::Shift(
+                ShiftOp::Sar,
+                reg(Reg::Rcx, Size::Byte),
+                reg(Reg::R8, Size::Long)
+            )),
+            [0x41, 0xd3, 0xf8]
+        );
+        assert_eq!(
+            bytes(Instr::Set(Cond::L, reg(Reg::Rax, Size::Byte))),
+            [0x0f, 0x9c, 0xc0]
+        );
+        assert_eq!(bytes(Instr::Push(Reg::R11)), [0x41, 0x53]);
+    }
+    #[test]
+    fn records_fixups_for_labels() {
+        let encoding = encode(&Instr::Mov(
+            Operand::Imm(3),
+            Operand::Label("x".to_string(), Size::Long),
+        ));
+
+        assert_eq!(encoding.bytes, [0xc7, 0x05, 0, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(
+            encoding.fixup,
+            Some(Fixup {
+                offset: 2,
+                label: "x".to_string(),
+                kind: RelocKind::Pc32,
+                addend: -8
+            })
+        );
+        let encoding = encode(&Instr::Mov(
+            Operand::Got("printf".to_string()),
+            reg(Reg::Rcx, Size::Quad),
+        ));
+        assert_eq!(encoding.bytes, [0x48, 0x8b, 0x0d, 0, 0, 0, 0]);
+        assert_eq!(
+            encoding.fixup.map(|fixup| fixup.kind),
+            Some(RelocKind::GotPcRel)
+        );
+
+        assert_eq!(jump(Some(Cond::Ge), true, -20), [0x7d, 0xec]);
+        assert_eq!(jump(None, false, 300), [0xe9, 0x2c, 0x01, 0, 0]);
+    }
+}
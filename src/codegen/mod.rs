@@ -1,2 +1,11 @@
+pub mod asm;
+pub mod assembler;
+#[allow(clippy::module_inception)]
 pub mod codegen;
+pub mod debug;
+pub mod elf;
+pub mod encoder;
+pub mod peephole;
+pub mod printer;
+pub mod regalloc;
 pub mod register;
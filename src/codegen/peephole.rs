@@ -0,0 +1,476 @@
+use crate::codegen::asm::*;
+use std::collections::HashMap;
+
+static REGISTERS: [Reg; 11] = [
+    Reg::Rax,
+    Reg::Rcx,
+    Reg::Rdx,
+    Reg::Rsi,
+    Reg::Rdi,
+    Reg::R8,
+    Reg::R9,
+    Reg::R10,
+    Reg::R11,
+    Reg::Rbp,
+    Reg::Rsp,
+];
+
+// set of hardware-registers with one bit per register
+type RegSet = u16;
+
+fn bit(reg: Reg) -> RegSet {
+    1 << reg as u16
+}
+
+// replaces `consumed` instructions starting at `start`
+struct Edit {
+    start: usize,
+    consumed: usize,
+    replacement: Vec<Instr>,
+}
+
+// rewrites the instructions of a single function until no pattern matches anymore,
+// keep_memory disables the patterns which remove reads or writes of memory
+pub fn peephole(instrs: &mut Vec<Instr>, keep_memory: bool) {
+    // comments don't take part in any pattern, so that annotated code is optimized the same way
+    let mut comments = Vec::new();
+    let mut code = Vec::with_capacity(instrs.len());
+    for instr in instrs.drain(..) {
+        match instr {
+            Instr::Comment(_) => comments.push((code.len(), instr)),
+            _ => code.push(instr),
+        }
+    }
+
+    loop {
+        let mut edits: Vec<Edit> = remove_unneeded_spills(&code).into_iter().collect();
+        if edits.is_empty() {
+            let live = live_out(&code);
+            let mut i = 0;
+            while i < code.len() {
+                match rewrite(&code[i..], &live[i..], keep_memory) {
+                    Some((replacement, consumed)) => {
+                        edits.push(Edit {
+                            start: i,
+                            consumed,
+                            replacement,
+                        });
+                        i += consumed;
+                    }
+                    None => i += 1,
+                }
+            }
+        }
+
+        if edits.is_empty() {
+            break;
+        }
+        apply(&mut code, edits, &mut comments);
+    }
+
+    let mut comments = comments.into_iter().peekable();
+    for (i, instr) in code.into_iter().enumerate() {
+        while let Some((_, comment)) = comments.next_if(|(anchor, _)| *anchor == i) {
+            instrs.push(comment);
+        }
+        instrs.push(instr);
+    }
+    instrs.extend(comments.map(|(_, comment)| comment));
+}
+
+// applies non-overlapping edits in order, comments in front of a replaced instruction
+// move in front of its replacement
+fn apply(instrs: &mut Vec<Instr>, edits: Vec<Edit>, comments: &mut [(usize, Instr)]) {
+    let mut edits = edits.into_iter().peekable();
+    // new position of every instruction
+    let mut moved = Vec::with_capacity(instrs.len() + 1);
+    let (mut start, mut skip) = (0, 0);
+
+    for (i, instr) in std::mem::take(instrs).into_iter().enumerate() {
+        if let Some(edit) = edits.next_if(|edit| edit.start == i) {
+            (start, skip) = (instrs.len(), edit.consumed);
+            instrs.extend(edit.replacement);
+        }
+        if skip > 0 {
+            moved.push(start);
+            skip -= 1;
+        } else {
+            moved.push(instrs.len());
+            instrs.push(instr);
+        }
+    }
+    moved.push(instrs.len());
+
+    for (anchor, _) in comments.iter_mut() {
+        *anchor = moved[*anchor];
+    }
+}
+
+// computes the registers that are live after every instruction,
+// rewrites only ever shrink these sets so they stay valid for a whole round
+fn live_out(instrs: &[Instr]) -> Vec<RegSet> {
+    let labels: HashMap<&str, usize> = instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, instr)| match instr {
+            Instr::Label(label) => Some((label.as_str(), i)),
+            _ => None,
+        })
+        .collect();
+    let successors = |i: usize| -> Vec<usize> {
+        let target = |label: &String| labels[label.as_str()];
+        match &instrs[i] {
+            Instr::Jmp(label) => vec![target(label)],
+            Instr::Jcc(_, label) => vec![target(label), i + 1],
+            Instr::Ret => vec![],
+            _ => vec![i + 1],
+        }
+    };
+    let reads = |instr: &Instr| {
+        REGISTERS
+            .iter()
+            .filter(|r| instr.reads(**r))
+            .fold(0, |set, r| set | bit(*r))
+    };
+    let kills = |instr: &Instr| {
+        REGISTERS
+            .iter()
+            .filter(|r| instr.kills(**r))
+            .fold(0, |set, r| set | bit(*r))
+    };
+
+    let mut live_in: Vec<RegSet> = vec![0; instrs.len() + 1];
+    let mut live_out: Vec<RegSet> = vec![0; instrs.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..instrs.len()).rev() {
+            let out = successors(i).into_iter().fold(0, |set, s| set | live_in[s]);
+            let new_in = reads(&instrs[i]) | (out & !kills(&instrs[i]));
+
+            if out != live_out[i] || new_in != live_in[i] {
+                live_out[i] = out;
+                live_in[i] = new_in;
+                changed = true;
+            }
+        }
+    }
+    live_out
+}
+
+// lea only computes an address
+fn reads_memory(instr: &Instr) -> bool {
+    match instr {
+        Instr::Mov(src, _) | Instr::Movs(src, _) | Instr::Movz(src, _) => src.is_mem(),
+        _ => false,
+    }
+}
+fn is_dead(reg: Reg, live: RegSet) -> bool {
+    !matches!(reg, Reg::Rbp | Reg::Rsp) && live & bit(reg) == 0
+}
+fn fits_imm32(op: &Operand) -> bool {
+    match op {
+        Operand::Imm(n) => i32::try_from(*n).is_ok(),
+        _ => true,
+    }
+}
+// x86 instructions can't take two memory operands and only moves into registers
+// can take a 64bit immediate
+fn valid_operands(src: &Operand, dest: &Operand) -> bool {
+    !(src.is_mem() && dest.is_mem()) && (fits_imm32(src) || dest.reg().is_some())
+}
+
+// matches a pattern at the start of the instructions and returns its replacement
+// together with the amount of instructions it replaces
+fn rewrite(instrs: &[Instr], live: &[RegSet], keep_memory: bool) -> Option<(Vec<Instr>, usize)> {
+    match instrs {
+        [Instr::Mov(src, dest), ..] if src == dest => Some((vec![], 1)),
+
+        // writes into registers whose value is never read
+        [Instr::Mov(_, Operand::Reg(reg, _))
+        | Instr::Movs(_, Operand::Reg(reg, _))
+        | Instr::Movz(_, Operand::Reg(reg, _))
+        | Instr::Lea(_, Operand::Reg(reg, _))
+        | Instr::Set(_, Operand::Reg(reg, _)), ..]
+            if is_dead(*reg, live[0]) && !(keep_memory && reads_memory(&instrs[0])) =>
+        {
+            Some((vec![], 1))
+        }
+
+        // moving a value back to where it came from
+        [first @ Instr::Mov(a, b), Instr::Mov(c, d), ..]
+            if a == d
+                && b == c
+                && !b.reg().is_some_and(|reg| a.uses(reg))
+                && !(keep_memory && (a.is_mem() || b.is_mem())) =>
+        {
+            Some((vec![first.clone()], 2))
+        }
+
+        // forwards the source of a move to the only use of the moved register
+        [Instr::Mov(src, Operand::Reg(reg, size)), next, ..]
+            if is_dead(*reg, live[1]) && !src.uses(*reg) =>
+        {
+            let moved = Operand::Reg(*reg, *size);
+            let replacement = match next {
+                Instr::Mov(from, to) if *from == moved && !to.uses(*reg) => {
+                    valid_operands(src, to).then(|| Instr::Mov(src.clone(), to.clone()))
+                }
+                Instr::Alu(op, from, to @ Operand::Reg(..)) if *from == moved && !to.uses(*reg) => {
+                    fits_imm32(src).then(|| Instr::Alu(*op, src.clone(), to.clone()))
+                }
+                Instr::Cmp(left, right) if *left == moved && !right.uses(*reg) => {
+                    (valid_operands(src, right) && fits_imm32(src))
+                        .then(|| Instr::Cmp(src.clone(), right.clone()))
+                }
+                Instr::Cmp(left, right)
+                    if *right == moved && !left.uses(*reg) && !matches!(src, Operand::Imm(_)) =>
+                {
+                    valid_operands(left, src).then(|| Instr::Cmp(left.clone(), src.clone()))
+                }
+                _ => None,
+            }?;
+            Some((vec![replacement], 2))
+        }
+
+        [Instr::Cmp(Operand::Imm(0), reg @ Operand::Reg(..)), ..] => {
+            Some((vec![Instr::Test(reg.clone(), reg.clone())], 1))
+        }
+
+        [Instr::Alu(AluOp::Imul, Operand::Imm(n), dest), ..] if *n > 0 && n.count_ones() == 1 => {
+            match n.trailing_zeros() {
+                0 => Some((vec![], 1)),
+                shift => Some((
+                    vec![Instr::Shift(
+                        ShiftOp::Sal,
+                        Operand::Imm(shift as i64),
+                        dest.clone(),
+                    )],
+                    1,
+                )),
+            }
+        }
+
+        // branches on the flags directly instead of materializing the condition first
+        [Instr::Set(cond, Operand::Reg(Reg::Rax, Size::Byte)), Instr::Movz(Operand::Reg(Reg::Rax, Size::Byte), Operand::Reg(reg, _)), Instr::Test(Operand::Reg(a, _), Operand::Reg(b, _)), Instr::Jcc(jump_cond @ (Cond::E | Cond::Ne), label), ..]
+            if a == reg && b == reg && is_dead(*reg, live[3]) && is_dead(Reg::Rax, live[3]) =>
+        {
+            let cond = if *jump_cond == Cond::Ne {
+                *cond
+            } else {
+                cond.inverse()
+            };
+            Some((vec![Instr::Jcc(cond, label.clone())], 4))
+        }
+
+        _ => None,
+    }
+}
+
+// drops pushes and pops around calls for registers which aren't read after the call anymore
+fn remove_unneeded_spills(instrs: &[Instr]) -> Option<Edit> {
+    let live = live_out(instrs);
+    let stack_adjust = |instr: &Instr, op: AluOp| matches!(instr, Instr::Alu(o, Operand::Imm(8), Operand::Reg(Reg::Rsp, _)) if *o == op);
+
+    for call in 0..instrs.len() {
+        if !matches!(instrs[call], Instr::Call(..) | Instr::CallIndirect(..)) {
+            continue;
+        }
+        // pops directly follow the call, optionally after undoing the stack-alignment
+        let mut after = call + 1;
+        let add = after < instrs.len() && stack_adjust(&instrs[after], AluOp::Add);
+        if add {
+            after += 1;
+        }
+        let pops_start = after;
+        while let Some(Instr::Pop(_)) = instrs.get(after) {
+            after += 1;
+        }
+        if pops_start == after {
+            continue;
+        }
+
+        // pushes come before the argument moves and the stack-alignment
+        let mut before = call;
+        while before > 0 && matches!(instrs[before - 1], Instr::Mov(..) | Instr::Lea(..)) {
+            before -= 1;
+        }
+        let sub = before > 0 && stack_adjust(&instrs[before - 1], AluOp::Sub);
+        if sub {
+            before -= 1;
+        }
+        let pushes_end = before;
+        while before > 0 && matches!(instrs[before - 1], Instr::Push(_)) {
+            before -= 1;
+        }
+        if sub != add || pushes_end - before != after - pops_start {
+            continue;
+        }
+
+        let spilled: Vec<Reg> = instrs[before..pushes_end]
+            .iter()
+            .filter_map(|instr| match instr {
+                Instr::Push(reg) => Some(*reg),
+                _ => None,
+            })
+            .collect();
+        let needed: Vec<Reg> = spilled
+            .iter()
+            .copied()
+            .filter(|reg| !is_dead(*reg, live[after - 1]))
+            .collect();
+        if needed.len() == spilled.len() {
+            continue;
+        }
+
+        // rebuilds the sequence keeping the stack 16byte aligned
+        let align = !needed.len().is_multiple_of(2);
+        let rsp = Operand::Reg(Reg::Rsp, Size::Quad);
+        let mut replacement: Vec<Instr> = needed.iter().map(|reg| Instr::Push(*reg)).collect();
+        if align {
+            replacement.push(Instr::Alu(AluOp::Sub, Operand::Imm(8), rsp.clone()));
+        }
+        replacement.extend_from_slice(&instrs[pushes_end + sub as usize..=call]);
+        if align {
+            replacement.push(Instr::Alu(AluOp::Add, Operand::Imm(8), rsp));
+        }
+        replacement.extend(needed.iter().rev().map(|reg| Instr::Pop(*reg)));
+
+        return Some(Edit {
+            start: before,
+            consumed: after - before,
+            replacement,
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reg(reg: Reg) -> Operand {
+        Operand::Reg(reg, Size::Long)
+    }
+    fn local(offset: i64) -> Operand {
+        Operand::Mem {
+            base: Reg::Rbp,
+            offset,
+            size: Size::Long,
+        }
+    }
+    fn optimize(mut instrs: Vec<Instr>) -> Vec<Instr> {
+        peephole(&mut instrs, false);
+        instrs
+    }
+
+    #[test]
+    fn removes_redundant_moves() {
+        let actual = optimize(vec![
+            Instr::Mov(local(-8), reg(Reg::R8)),
+            Instr::Mov(reg(Reg::R8), reg(Reg::R9)),
+            Instr::Mov(reg(Reg::R9), reg(Reg::R8)),
+            Instr::Mov(reg(Reg::R9), reg(Reg::Rax)),
+            Instr::Ret,
+        ]);
+        let expected = vec![Instr::Mov(local(-8), reg(Reg::Rax)), Instr::Ret];
+
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn comments_dont_block_rewrites() {
+        let comment = |text: &str| Instr::Comment(text.to_string());
+        let actual = optimize(vec![
+            comment("a"),
+            Instr::Mov(local(-8), reg(Reg::R8)),
+            comment("b"),
+            Instr::Mov(reg(Reg::R8), reg(Reg::Rax)),
+            comment("c"),
+            Instr::Ret,
+        ]);
+        let expected = vec![
+            comment("a"),
+            comment("b"),
+            Instr::Mov(local(-8), reg(Reg::Rax)),
+            comment("c"),
+            Instr::Ret,
+        ];
+
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn folds_loads_and_strength_reduces_multiplication() {
+        let actual = optimize(vec![
+            Instr::Mov(local(-8), reg(Reg::R8)),
+            Instr::Mov(local(-12), reg(Reg::Rcx)),
+            Instr::Alu(AluOp::Add, reg(Reg::Rcx), reg(Reg::R8)),
+            Instr::Alu(AluOp::Imul, Operand::Imm(8), reg(Reg::R8)),
+            Instr::Mov(reg(Reg::R8), reg(Reg::Rax)),
+            Instr::Ret,
+        ]);
+        let expected = vec![
+            Instr::Mov(local(-8), reg(Reg::R8)),
+            Instr::Alu(AluOp::Add, local(-12), reg(Reg::R8)),
+            Instr::Shift(ShiftOp::Sal, Operand::Imm(3), reg(Reg::R8)),
+            Instr::Mov(reg(Reg::R8), reg(Reg::Rax)),
+            Instr::Ret,
+        ];
+
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn branches_on_flags() {
+        let al = Operand::Reg(Reg::Rax, Size::Byte);
+        let actual = optimize(vec![
+            Instr::Label(".L0".to_string()),
+            Instr::Mov(local(-8), reg(Reg::Rax)),
+            Instr::Cmp(Operand::Imm(10), reg(Reg::Rax)),
+            Instr::Set(Cond::L, al.clone()),
+            Instr::Movz(al, reg(Reg::R8)),
+            Instr::Cmp(Operand::Imm(0), reg(Reg::R8)),
+            Instr::Jcc(Cond::E, ".L1".to_string()),
+            Instr::Jmp(".L0".to_string()),
+            Instr::Label(".L1".to_string()),
+            Instr::Mov(Operand::Imm(0), reg(Reg::Rax)),
+            Instr::Ret,
+        ]);
+        let expected = vec![
+            Instr::Label(".L0".to_string()),
+            Instr::Cmp(Operand::Imm(10), local(-8)),
+            Instr::Jcc(Cond::Ge, ".L1".to_string()),
+            Instr::Jmp(".L0".to_string()),
+            Instr::Label(".L1".to_string()),
+            Instr::Mov(Operand::Imm(0), reg(Reg::Rax)),
+            Instr::Ret,
+        ];
+
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn removes_spills_of_dead_registers() {
+        let rsp = Operand::Reg(Reg::Rsp, Size::Quad);
+        let actual = optimize(vec![
+            Instr::Mov(local(-8), reg(Reg::R8)),
+            Instr::Mov(local(-12), reg(Reg::R10)),
+            Instr::Push(Reg::R8),
+            Instr::Push(Reg::R10),
+            Instr::Call("foo".to_string(), false),
+            Instr::Pop(Reg::R10),
+            Instr::Pop(Reg::R8),
+            Instr::Alu(AluOp::Add, reg(Reg::R8), reg(Reg::Rax)),
+            Instr::Ret,
+        ]);
+        let expected = vec![
+            Instr::Mov(local(-8), reg(Reg::R8)),
+            Instr::Push(Reg::R8),
+            Instr::Alu(AluOp::Sub, Operand::Imm(8), rsp.clone()),
+            Instr::Call("foo".to_string(), false),
+            Instr::Alu(AluOp::Add, Operand::Imm(8), rsp),
+            Instr::Pop(Reg::R8),
+            Instr::Alu(AluOp::Add, reg(Reg::R8), reg(Reg::Rax)),
+            Instr::Ret,
+        ];
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::codegen::asm::*;
+use crate::common::token::escape;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Syntax {
+    Att,
+    Intel,
+}
+
+// renders the instructions as assembly in the given syntax
+pub fn print(instrs: &[Instr], syntax: Syntax) -> String {
+    let mut output = String::new();
+    if syntax == Syntax::Intel {
+        output.push_str("\t.intel_syntax noprefix\n");
+    }
+    for instr in instrs {
+        let line = match syntax {
+            Syntax::Att => att::instr(instr),
+            Syntax::Intel => intel::instr(instr),
+        };
+        writeln!(output, "{}", line).expect("writing to string can't fail");
+    }
+    // marks the stack as non-executable like the object-writer does
+    output.push_str("\n\t.section .note.GNU-stack,\"\",@progbits\n");
+    output
+}
+
+fn directive(directive: &Directive) -> String {
+    match directive {
+        Directive::Text => "\n\t.text".to_string(),
+        Directive::Data => "\n\t.data".to_string(),
+        Directive::Bss => "\n\t.bss".to_string(),
+        Directive::Rodata => "\n\t.section .rodata".to_string(),
+        Directive::Globl(name) => format!("\t.globl {}", name),
+        Directive::String(data) => format!("\t.string \"{}\"", escape(data)),
+        Directive::Value(size, n) => format!("\t.{} {}", size.name(), n),
+        Directive::Address(label) => format!("\t.quad {}", label),
+        Directive::Zero(n) => format!("\t.zero {}", n),
+        Directive::Align(n) => format!("\t.balign {}", n),
+        Directive::Type(name, SymbolType::Function) => format!("\t.type {}, @function", name),
+        Directive::Type(name, SymbolType::Object) => format!("\t.type {}, @object", name),
+        Directive::Size(name) => format!("\t.size {}, .-{}", name, name),
+        Directive::Section(name) => format!("\n\t.section {}", name),
+        Directive::File(name) => format!("\t.file 1 \"{}\"", name),
+        Directive::Loc(line) => format!("\t.loc 1 {}", line),
+        Directive::Offset(label) => format!("\t.long {}", label),
+        Directive::Difference(size, end, start) => format!("\t.{} {}-{}", size.name(), end, start),
+    }
+}
+fn comment(text: &str) -> String {
+    format!("\t# {}", text)
+}
+fn alu_name(op: &AluOp) -> &'static str {
+    match op {
+        AluOp::Add => "add",
+        AluOp::Sub => "sub",
+        AluOp::Imul => "imul",
+        AluOp::And => "and",
+        AluOp::Or => "or",
+        AluOp::Xor => "xor",
+    }
+}
+fn shift_name(op: &ShiftOp) -> &'static str {
+    match op {
+        ShiftOp::Sal => "sal",
+        ShiftOp::Sar => "sar",
+    }
+}
+
+mod att {
+    use super::*;
+
+    fn operand(op: &Operand) -> String {
+        match op {
+            Operand::Reg(reg, size) => format!("%{}", reg.name(*size)),
+            Operand::Imm(n) => format!("${}", n),
+            Operand::Mem {
+                base, offset: 0, ..
+            } => format!("(%{})", base.name(Size::Quad)),
+            Operand::Mem { base, offset, .. } => {
+                format!("{}(%{})", offset, base.name(Size::Quad))
+            }
+            Operand::Label(name, _) => format!("{}(%rip)", name),
+            Operand::Got(name) => format!("{}@GOTPCREL(%rip)", name),
+        }
+    }
+    // suffix is taken from the first operand which isn't an immediate
+    fn suffix(ops: &[&Operand]) -> char {
+        ops.iter()
+            .find_map(|op| op.size())
+            .expect("instruction needs a sized operand")
+            .suffix()
+    }
+    fn binary(mnemonic: &str, suffix: char, src: &Operand, dest: &Operand) -> String {
+        format!(
+            "\t{}{} {}, {}",
+            mnemonic,
+            suffix,
+            operand(src),
+            operand(dest)
+        )
+    }
+
+    pub fn instr(instr: &Instr) -> String {
+        match instr {
+            Instr::Mov(src, dest) => binary("mov", suffix(&[dest]), src, dest),
+            Instr::Movs(src, dest) | Instr::Movz(src, dest) => format!(
+                "\tmov{}{}{} {}, {}",
+                if matches!(instr, Instr::Movs(..)) {
+                    's'
+                } else {
+                    'z'
+                },
+                suffix(&[src]),
+                suffix(&[dest]),
+                operand(src),
+                operand(dest)
+            ),
+            Instr::Lea(src, dest) => binary("lea", 'q', src, dest),
+            Instr::Alu(op, src, dest) => binary(alu_name(op), suffix(&[dest]), src, dest),
+            Instr::Shift(op, count, dest) => binary(shift_name(op), suffix(&[dest]), count, dest),
+            Instr::Neg(op) => format!("\tneg{} {}", suffix(&[op]), operand(op)),
+            Instr::Not(op) => format!("\tnot{} {}", suffix(&[op]), operand(op)),
+            Instr::Cmp(left, right) => binary("cmp", suffix(&[right, left]), left, right),
+            Instr::Test(left, right) => binary("test", suffix(&[right, left]), left, right),
+            Instr::Set(cond, op) => format!("\tset{} {}", cond.name(), operand(op)),
+            Instr::Cdq => "\tcdq".to_string(),
+            Instr::Cqo => "\tcqo".to_string(),
+            Instr::Idiv(op) => format!("\tidiv{} {}", suffix(&[op]), operand(op)),
+            Instr::Push(reg) => format!("\tpushq %{}", reg.name(Size::Quad)),
+            Instr::Pop(reg) => format!("\tpopq %{}", reg.name(Size::Quad)),
+            Instr::Jmp(label) => format!("\tjmp {}", label),
+            Instr::Jcc(cond, label) => format!("\tj{} {}", cond.name(), label),
+            Instr::Call(name, _) => format!("\tcall {}", name),
+            Instr::CallIndirect(reg, _) => format!("\tcall *%{}", reg.name(Size::Quad)),
+            Instr::Ret => "\tret".to_string(),
+            Instr::Label(label) => format!("{}:", label),
+            Instr::Directive(d) => directive(d),
+            Instr::Comment(text) => comment(text),
+        }
+    }
+}
+
+mod intel {
+    use super::*;
+
+    fn size_keyword(size: Size) -> &'static str {
+        match size.name() {
+            "byte" => "BYTE",
+            "long" => "DWORD",
+            _ => "QWORD",
+        }
+    }
+    fn address(op: &Operand) -> String {
+        match op {
+            Operand::Mem {
+                base, offset: 0, ..
+            } => format!("[{}]", base.name(Size::Quad)),
+            Operand::Mem { base, offset, .. } => {
+                format!("[{}{:+}]", base.name(Size::Quad), offset)
+            }
+            Operand::Label(name, _) => format!("[rip+{}]", name),
+            Operand::Got(name) => format!("[rip+{}@GOTPCREL]", name),
+            _ => unreachable!("only memory has an address"),
+        }
+    }
+    fn operand(op: &Operand) -> String {
+        match op {
+            Operand::Reg(reg, size) => reg.name(*size).to_string(),
+            Operand::Imm(n) => n.to_string(),
+            Operand::Mem { size, .. } | Operand::Label(_, size) => {
+                format!("{} PTR {}", size_keyword(*size), address(op))
+            }
+            Operand::Got(_) => format!("QWORD PTR {}", address(op)),
+        }
+    }
+    // intel syntax puts the destination first
+    fn binary(mnemonic: &str, src: &Operand, dest: &Operand) -> String {
+        format!("\t{} {}, {}", mnemonic, operand(dest), operand(src))
+    }
+
+    pub fn instr(instr: &Instr) -> String {
+        match instr {
+            Instr::Mov(src, dest) => binary("mov", src, dest),
+            // sign extending a dword uses its own mnemonic
+            Instr::Movs(src, dest) if src.size() == Some(Size::Long) => binary("movsxd", src, dest),
+            Instr::Movs(src, dest) => binary("movsx", src, dest),
+            Instr::Movz(src, dest) => binary("movzx", src, dest),
+            Instr::Lea(src, dest) => format!("\tlea {}, {}", operand(dest), address(src)),
+            Instr::Alu(op, src, dest) => binary(alu_name(op), src, dest),
+            Instr::Shift(op, count, dest) => binary(shift_name(op), count, dest),
+            Instr::Neg(op) => format!("\tneg {}", operand(op)),
+            Instr::Not(op) => format!("\tnot {}", operand(op)),
+            Instr::Cmp(left, right) => binary("cmp", left, right),
+            Instr::Test(left, right) => binary("test", left, right),
+            Instr::Set(cond, op) => format!("\tset{} {}", cond.name(), operand(op)),
+            Instr::Cdq => "\tcdq".to_string(),
+            Instr::Cqo => "\tcqo".to_string(),
+            Instr::Idiv(op) => format!("\tidiv {}", operand(op)),
+            Instr::Push(reg) => format!("\tpush {}", reg.name(Size::Quad)),
+            Instr::Pop(reg) => format!("\tpop {}", reg.name(Size::Quad)),
+            Instr::Jmp(label) => format!("\tjmp {}", label),
+            Instr::Jcc(cond, label) => format!("\tj{} {}", cond.name(), label),
+            Instr::Call(name, _) => format!("\tcall {}", name),
+            Instr::CallIndirect(reg, _) => format!("\tcall {}", reg.name(Size::Quad)),
+            Instr::Ret => "\tret".to_string(),
+            Instr::Label(label) => format!("{}:", label),
+            Instr::Directive(d) => directive(d),
+            Instr::Comment(text) => comment(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prints_both_syntaxes() {
+        let local = Operand::Mem {
+            base: Reg::Rbp,
+            offset: -8,
+            size: Size::Long,
+        };
+        let instrs = vec![
+            Instr::Mov(Operand::Imm(3), local.clone()),
+            Instr::Alu(AluOp::Add, local.clone(), Operand::Reg(Reg::R8, Size::Long)),
+            Instr::Movs(
+                Operand::Reg(Reg::R8, Size::Long),
+                Operand::Reg(Reg::Rax, Size::Quad),
+            ),
+            Instr::Lea(
+                Operand::Label(".LS0".to_string(), Size::Quad),
+                Operand::Reg(Reg::Rdi, Size::Quad),
+            ),
+        ];
+
+        assert_eq!(
+            print(&instrs, Syntax::Att),
+            "\tmovl $3, -8(%rbp)\n\taddl -8(%rbp), %r8d\n\tmovslq %r8d, %rax\n\tleaq .LS0(%rip), %rdi\n\n\t.section .note.GNU-stack,\"\",@progbits\n"
+        );
+        assert_eq!(
+            print(&instrs, Syntax::Intel),
+            "\t.intel_syntax noprefix\n\tmov DWORD PTR [rbp-8], 3\n\tadd r8d, DWORD PTR [rbp-8]\n\tmovsxd rax, r8d\n\tlea rdi, [rip+.LS0]\n\n\t.section .note.GNU-stack,\"\",@progbits\n"
+        );
+    }
+}
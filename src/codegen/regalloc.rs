@@ -0,0 +1,149 @@
+use crate::codegen::register::*;
+use crate::ir::{instr::*, liveness::*};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Clone)]
+pub enum Location {
+    Scratch(Rc<RefCell<ScratchRegister>>),
+    // value lives on the stack because all scratch-registers were taken
+    Spill,
+}
+
+pub struct Allocation {
+    pub locations: HashMap<usize, Location>,
+    // scratch-registers holding values across a call, indexed by (block-index, instr-index)
+    pub live_across_calls: HashMap<(usize, usize), Vec<Rc<RefCell<ScratchRegister>>>>,
+}
+
+struct Interval {
+    vreg: usize,
+    start: usize,
+    end: usize,
+}
+
+// computes the range of linearized positions in which every virtual register is live
+fn live_intervals(function: &Function) -> Vec<Interval> {
+    let liveness = liveness(function);
+    let mut ranges: HashMap<usize, (usize, usize)> = HashMap::new();
+    let mut extend = |vreg: usize, pos: usize| {
+        let range = ranges.entry(vreg).or_insert((pos, pos));
+        range.0 = range.0.min(pos);
+        range.1 = range.1.max(pos);
+    };
+
+    let mut pos = 0;
+    for block in function.blocks.iter() {
+        let block_start = pos;
+        for vreg in liveness.live_in[&block.id].iter() {
+            extend(*vreg, block_start);
+        }
+        for instr in block.instrs.iter() {
+            for reg in instr.operands().into_iter().filter_map(|v| v.as_reg()) {
+                extend(reg.id, pos);
+            }
+            if let Some(dest) = instr.dest() {
+                extend(dest.id, pos);
+            }
+            pos += 1;
+        }
+        for reg in block
+            .terminator
+            .operands()
+            .into_iter()
+            .filter_map(|v| v.as_reg())
+        {
+            extend(reg.id, pos);
+        }
+        for vreg in liveness.live_out[&block.id].iter() {
+            extend(*vreg, pos);
+        }
+        pos += 1;
+    }
+
+    let mut intervals: Vec<Interval> = ranges
+        .into_iter()
+        .map(|(vreg, (start, end))| Interval { vreg, start, end })
+        .collect();
+    intervals.sort_by_key(|i| (i.start, i.vreg));
+    intervals
+}
+
+// linear-scan register allocation over the scratch-registers,
+// spilling the interval which lives the longest when running out of registers
+pub fn allocate(function: &Function, scratch: &ScratchRegisters) -> Allocation {
+    let intervals = live_intervals(function);
+    let mut locations: HashMap<usize, Location> = HashMap::new();
+    let mut active: Vec<(usize, usize, Rc<RefCell<ScratchRegister>>)> = Vec::new();
+
+    for interval in intervals.iter() {
+        // values can't share a register with an operand of their defining instruction
+        active.retain(|(_, end, reg)| {
+            if *end < interval.start {
+                reg.borrow_mut().free();
+                false
+            } else {
+                true
+            }
+        });
+
+        if let Some(reg) = scratch.scratch_alloc() {
+            locations.insert(interval.vreg, Location::Scratch(Rc::clone(&reg)));
+            active.push((interval.vreg, interval.end, reg));
+            continue;
+        }
+        let (longest, _) = active
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, end, _))| *end)
+            .expect("has to exist when no register is free");
+
+        if active[longest].1 > interval.end {
+            let (vreg, _, reg) = active.remove(longest);
+            locations.insert(vreg, Location::Spill);
+            locations.insert(interval.vreg, Location::Scratch(Rc::clone(&reg)));
+            active.push((interval.vreg, interval.end, reg));
+        } else {
+            locations.insert(interval.vreg, Location::Spill);
+        }
+    }
+    for (_, _, reg) in active {
+        reg.borrow_mut().free();
+    }
+
+    Allocation {
+        live_across_calls: live_across_calls(function, &intervals, &locations),
+        locations,
+    }
+}
+
+fn live_across_calls(
+    function: &Function,
+    intervals: &[Interval],
+    locations: &HashMap<usize, Location>,
+) -> HashMap<(usize, usize), Vec<Rc<RefCell<ScratchRegister>>>> {
+    let mut result = HashMap::new();
+    let mut pos = 0;
+
+    for (block_index, block) in function.blocks.iter().enumerate() {
+        for (instr_index, instr) in block.instrs.iter().enumerate() {
+            if matches!(instr, Instr::Call { .. }) {
+                let mut regs: Vec<Rc<RefCell<ScratchRegister>>> = Vec::new();
+                for interval in intervals.iter() {
+                    if interval.start < pos && interval.end > pos {
+                        if let Location::Scratch(reg) = &locations[&interval.vreg] {
+                            if !regs.contains(reg) {
+                                regs.push(Rc::clone(reg));
+                            }
+                        }
+                    }
+                }
+                result.insert((block_index, instr_index), regs);
+            }
+            pos += 1;
+        }
+        pos += 1;
+    }
+    result
+}
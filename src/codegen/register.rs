@@ -1,131 +1,133 @@
-use crate::common::expr::ValueKind;
+use crate::codegen::asm::*;
 use crate::common::types::*;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-static ARG_REGISTER_MAP: &[[&str; 6]] = &[
-    ["%dil", "%sil", "%dl", "%cl", "%r8b", "%r9b"],
-    ["%edi", "%esi", "%edx", "%ecx", "%r8d", "%r9d"],
-    ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"],
-];
+pub static ARG_REGISTERS: [Reg; 6] = [Reg::Rdi, Reg::Rsi, Reg::Rdx, Reg::Rcx, Reg::R8, Reg::R9];
 
 #[derive(PartialEq, Clone)]
 pub enum Register {
-    Scratch(Rc<RefCell<ScratchRegister>>, NEWTypes, ValueKind),
+    Scratch(Rc<RefCell<ScratchRegister>>, NEWTypes),
+    // fixed registers which are only used inside of a single instruction
+    Temp(TempRegister, NEWTypes),
     Stack(StackRegister),
     Label(LabelRegister),
-    Literal(usize, NEWTypes),
+    Literal(i64, NEWTypes),
     Arg(usize, NEWTypes),
-    Void,
+    // memory at the address held by the inner register
+    Deref(Box<Register>, NEWTypes),
 }
 impl Register {
-    pub fn free(&self) {
+    pub fn operand(&self) -> Operand {
+        let size = Size::of(&self.get_type());
         match self {
-            Register::Void
-            | Register::Stack(_)
-            | Register::Arg(..)
-            | Register::Label(_)
-            | Register::Literal(..) => (),
-            Register::Scratch(reg, _, _) => reg.borrow_mut().free(),
-        }
-    }
-    pub fn name(&self) -> String {
-        match self {
-            Register::Void => unimplemented!(),
-            Register::Stack(reg) => reg.name(),
-            Register::Label(reg) => reg.name(),
-            Register::Literal(n, _) => format!("${n}"),
-            Register::Scratch(reg, type_decl, valuekind) => match valuekind {
-                ValueKind::Rvalue => reg.borrow().name(type_decl),
-                ValueKind::Lvalue => self.base_name(),
+            Register::Stack(reg) => Operand::Mem {
+                base: Reg::Rbp,
+                offset: -(reg.bp_offset as i64),
+                size,
             },
+            Register::Label(reg) => Operand::Label(reg.name(), size),
+            Register::Literal(n, _) => Operand::Imm(*n),
+            Register::Scratch(reg, _) => Operand::Reg(reg.borrow().reg, size),
+            Register::Temp(reg, _) => Operand::Reg(reg.reg(), size),
             Register::Arg(i, type_decl) => match type_decl {
-                NEWTypes::Primitive(Types::Char) => ARG_REGISTER_MAP[0][*i].to_string(),
-                NEWTypes::Primitive(Types::Int) => ARG_REGISTER_MAP[1][*i].to_string(),
-                NEWTypes::Primitive(Types::Long)
-                | NEWTypes::Pointer(_)
-                | NEWTypes::Array { .. } => ARG_REGISTER_MAP[2][*i].to_string(),
-                _ => unreachable!("cant pass void argument"),
+                NEWTypes::Primitive(Types::Void) => unreachable!("cant pass void argument"),
+                _ => Operand::Reg(ARG_REGISTERS[*i], size),
             },
-        }
-    }
-    // name as 64bit register
-    pub fn base_name(&self) -> String {
-        match self {
-            Register::Void => unimplemented!(),
-            Register::Stack(reg) => reg.name(),
-            Register::Label(reg) => reg.base_name(),
-            Register::Literal(index, ..) => format!("{index}"),
-            Register::Scratch(reg, _, valuekind) => match valuekind {
-                ValueKind::Rvalue => {
-                    reg.borrow()
-                        .name(&NEWTypes::Pointer(Box::new(NEWTypes::Primitive(
-                            Types::Void,
-                        ))))
-                }
-                ValueKind::Lvalue => {
-                    format!(
-                        "({})",
-                        reg.borrow()
-                            .name(&NEWTypes::Pointer(Box::new(NEWTypes::Primitive(
-                                Types::Void
-                            ))))
-                    )
-                }
+            Register::Deref(reg, _) => Operand::Mem {
+                base: reg
+                    .operand()
+                    .reg()
+                    .expect("address has to be in a register"),
+                offset: 0,
+                size,
             },
-            Register::Arg(i, _) => ARG_REGISTER_MAP[2][*i].to_string(),
         }
     }
     pub fn set_type(&mut self, type_decl: NEWTypes) {
         match self {
-            Register::Void => unimplemented!(),
-            Register::Label(_) => (),
-            Register::Literal(_, old_decl) => *old_decl = type_decl,
+            Register::Label(reg) => reg.type_decl = type_decl,
             Register::Stack(reg) => reg.type_decl = type_decl,
-            Register::Scratch(_, old_decl, _) => *old_decl = type_decl,
-            Register::Arg(_, old_decl) => *old_decl = type_decl,
+            Register::Literal(_, old_decl)
+            | Register::Scratch(_, old_decl)
+            | Register::Temp(_, old_decl)
+            | Register::Arg(_, old_decl)
+            | Register::Deref(_, old_decl) => *old_decl = type_decl,
         }
     }
     pub fn get_type(&self) -> NEWTypes {
         match self {
-            Register::Void => unimplemented!(),
-            Register::Label(reg) => reg.get_type(),
-            Register::Literal(_, type_decl) => type_decl.clone(),
+            Register::Label(reg) => reg.type_decl.clone(),
             Register::Stack(reg) => reg.type_decl.clone(),
-            Register::Scratch(_, type_decl, _) | Register::Arg(_, type_decl) => type_decl.clone(),
+            Register::Literal(_, type_decl)
+            | Register::Scratch(_, type_decl)
+            | Register::Temp(_, type_decl)
+            | Register::Arg(_, type_decl)
+            | Register::Deref(_, type_decl) => type_decl.clone(),
         }
     }
-    pub fn is_lval(&self) -> bool {
-        matches!(self, Register::Scratch(_, _, value_kind) if *value_kind == ValueKind::Lvalue)
+    pub fn with_type(&self, type_decl: NEWTypes) -> Register {
+        let mut reg = self.clone();
+        reg.set_type(type_decl);
+        reg
+    }
+    // returns true if register refers to memory
+    pub fn is_mem(&self) -> bool {
+        matches!(
+            self,
+            Register::Stack(_) | Register::Label(_) | Register::Deref(..)
+        )
+    }
+    // immediate that doesn't fit into the 32bits most instructions take
+    pub fn is_wide_literal(&self) -> bool {
+        matches!(self, Register::Literal(n, _) if i32::try_from(*n).is_err())
+    }
+    // returns true if both registers name the same hardware-register
+    pub fn same_reg(&self, other: &Register) -> bool {
+        match self.operand().reg() {
+            Some(reg) => other.operand().reg() == Some(reg),
+            None => false,
+        }
     }
-    pub fn set_value_kind(&mut self, new_val_kind: ValueKind) {
-        if let Register::Scratch(_, _, value_kind) = self {
-            *value_kind = new_val_kind
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TempRegister {
+    Rax,
+    Rcx,
+    Rdx,
+    // holds the target of an indirect call, isn't an argument-register
+    R11,
+}
+impl TempRegister {
+    pub fn reg(self) -> Reg {
+        match self {
+            TempRegister::Rax => Reg::Rax,
+            TempRegister::Rcx => Reg::Rcx,
+            TempRegister::Rdx => Reg::Rdx,
+            TempRegister::R11 => Reg::R11,
         }
     }
 }
+
 #[derive(PartialEq, Clone)]
-pub enum LabelRegister {
+pub enum LabelKind {
     String(usize),
-    Var(String, NEWTypes),
+    Var(String),
+}
+#[derive(PartialEq, Clone)]
+pub struct LabelRegister {
+    kind: LabelKind,
+    type_decl: NEWTypes,
 }
 impl LabelRegister {
-    fn get_type(&self) -> NEWTypes {
-        match self {
-            LabelRegister::String(_) => {
-                NEWTypes::Pointer(Box::new(NEWTypes::Primitive(Types::Char)))
-            }
-            LabelRegister::Var(_, type_decl) => type_decl.clone(),
-        }
+    pub fn new(kind: LabelKind, type_decl: NEWTypes) -> Self {
+        LabelRegister { kind, type_decl }
     }
     fn name(&self) -> String {
-        format!("{}(%rip)", self.base_name())
-    }
-
-    fn base_name(&self) -> String {
-        match self {
-            LabelRegister::String(index) => format!("LS{index}"),
-            LabelRegister::Var(name, _) => format!("_{name}"),
+        match &self.kind {
+            LabelKind::String(index) => format!(".LS{index}"),
+            LabelKind::Var(name) => name.clone(),
         }
     }
 }
@@ -142,23 +144,20 @@ impl StackRegister {
             type_decl,
         }
     }
-    pub fn name(&self) -> String {
-        format!("-{}(%rbp)", self.bp_offset)
+    pub fn bp_offset(&self) -> usize {
+        self.bp_offset
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ScratchRegister {
     pub in_use: bool,
-    pub base_name: &'static str,
+    pub reg: Reg,
 }
 impl ScratchRegister {
-    fn free(&mut self) {
+    pub fn free(&mut self) {
         self.in_use = false;
     }
-    fn name(&self, type_decl: &NEWTypes) -> String {
-        format!("{}{}", self.base_name, type_decl.reg_suffix())
-    }
 }
 
 #[derive(Debug)]
@@ -166,35 +165,41 @@ pub struct ScratchRegisters {
     pub registers: [Rc<RefCell<ScratchRegister>>; 4],
 }
 impl ScratchRegisters {
-    pub fn scratch_alloc(&self) -> Rc<RefCell<ScratchRegister>> {
-        for (i, r) in self.registers.iter().enumerate() {
+    // returns None if all registers are in use
+    pub fn scratch_alloc(&self) -> Option<Rc<RefCell<ScratchRegister>>> {
+        for r in self.registers.iter() {
             if !r.borrow().in_use {
                 r.borrow_mut().in_use = true;
-                return Rc::clone(&self.registers[i]);
+                return Some(Rc::clone(r));
             }
         }
-        panic!("no free register");
+        None
     }
     pub fn new() -> Self {
         ScratchRegisters {
             registers: [
                 Rc::new(RefCell::new(ScratchRegister {
                     in_use: false,
-                    base_name: "%r8",
+                    reg: Reg::R8,
                 })),
                 Rc::new(RefCell::new(ScratchRegister {
                     in_use: false,
-                    base_name: "%r9",
+                    reg: Reg::R9,
                 })),
                 Rc::new(RefCell::new(ScratchRegister {
                     in_use: false,
-                    base_name: "%r10",
+                    reg: Reg::R10,
                 })),
                 Rc::new(RefCell::new(ScratchRegister {
                     in_use: false,
-                    base_name: "%r11",
+                    reg: Reg::R11,
                 })),
             ],
         }
     }
 }
+impl Default for ScratchRegisters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
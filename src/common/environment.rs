@@ -7,19 +7,30 @@ pub enum FunctionKind {
 }
 #[derive(Clone, PartialEq)]
 pub struct Function {
-    pub params: Vec<(NEWTypes, Token)>,
+    pub params: Vec<NEWTypes>,
     pub return_type: NEWTypes,
+    // takes additional arguments after the named parameters
+    pub variadic: bool,
 }
 impl Function {
-    pub fn new(return_type: NEWTypes, params: Vec<(NEWTypes, Token)>) -> Self {
+    pub fn new(return_type: NEWTypes, params: Vec<NEWTypes>, variadic: bool) -> Self {
         Function {
             return_type,
             params,
+            variadic,
         }
     }
     pub fn arity(&self) -> usize {
         self.params.len()
     }
+    // type of the function when used as an expression
+    pub fn type_decl(&self) -> NEWTypes {
+        NEWTypes::Function {
+            return_type: Box::new(self.return_type.clone()),
+            params: self.params.clone(),
+            variadic: self.variadic,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -41,6 +52,11 @@ impl<T> Table<T> {
         }
     }
 }
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[derive(Clone, PartialEq)]
 pub struct Environment<T> {
@@ -77,10 +93,11 @@ impl<T: Clone> Environment<T> {
         &mut self,
         return_type: NEWTypes,
         name: &str,
-        params: Vec<(NEWTypes, Token)>,
+        params: Vec<NEWTypes>,
+        variadic: bool,
         kind: FunctionKind,
     ) {
-        let f = Function::new(return_type, params);
+        let f = Function::new(return_type, params, variadic);
 
         match kind {
             FunctionKind::Declaration => self.current.func_decl.insert(name.to_string(), f),
@@ -27,7 +27,13 @@ impl Error {
         }
     }
     pub fn print_error(&self) {
-        eprintln!("Error: {}", self.msg);
+        self.print("Error");
+    }
+    pub fn print_warning(&self) {
+        self.print("Warning");
+    }
+    fn print(&self, kind: &str) {
+        eprintln!("{}: {}", kind, self.msg);
 
         if self.line_index != -1 {
             let line_length = self.line_index.to_string().len();
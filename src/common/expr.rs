@@ -54,8 +54,14 @@ pub enum ExprKind {
         left: Box<Expr>,
         by_amount: usize,
     },
+    // va_start, va_arg or va_end depending on the keyword, va_arg reads the type-argument
+    Builtin {
+        token: Token,
+        args: Vec<Expr>,
+        type_arg: Option<NEWTypes>,
+    },
     String(Token),
-    Number(i32),
+    Number(i64),
     CharLit(i8),
     Ident(Token),
 }
@@ -100,6 +106,7 @@ impl Display for ExprKind {
                 ExprKind::ScaleDown { .. } => "'scaling-down'".to_string(),
                 ExprKind::String(token) => token.unwrap_string(),
                 ExprKind::PostUnary { .. } => "'postfix-expression'".to_string(),
+                ExprKind::Builtin { token, .. } => format!("'builtin': {}", token.token),
                 ExprKind::CompoundAssign { token, .. } =>
                     format!("'compound-assignment: {}'", token.token),
             }
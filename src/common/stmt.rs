@@ -1,18 +1,54 @@
 use crate::common::{expr::Expr, token::Token, types::NEWTypes};
 use std::fmt::Display;
 
+// storage-class specifier in front of a declaration
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum StorageClass {
+    // internal linkage for globals and functions, persistent storage for locals
+    Static,
+    // refers to a definition somewhere else, doesn't allocate storage
+    Extern,
+}
+impl Display for StorageClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                StorageClass::Static => "static",
+                StorageClass::Extern => "extern",
+            }
+        )
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum Stmt {
     Expr(Expr),
-    // bool is to indicate if global or not
-    DeclareVar(NEWTypes, Token, bool),
-    InitVar(NEWTypes, Token, Expr, bool),
-    InitList(NEWTypes, Token, Vec<Expr>, bool),
+    // bool is to indicate if global or not, storage-class is None if not specified
+    DeclareVar(NEWTypes, Token, bool, Option<StorageClass>),
+    InitVar(NEWTypes, Token, Expr, bool, Option<StorageClass>),
+    InitList(NEWTypes, Token, Vec<Expr>, bool, Option<StorageClass>),
     Block(Vec<Stmt>),
     If(Token, Expr, Box<Stmt>, Box<Option<Stmt>>),
     While(Token, Expr, Box<Stmt>),
-    Function(NEWTypes, Token, Vec<(NEWTypes, Token)>, Vec<Stmt>),
-    FunctionDeclaration(NEWTypes, Token, Vec<(NEWTypes, Token)>),
+    // bool is to indicate if the parameter-list ends with '...'
+    Function(
+        NEWTypes,
+        Token,
+        Vec<(NEWTypes, Token)>,
+        bool,
+        Option<StorageClass>,
+        Vec<Stmt>,
+    ),
+    // parameter-names are optional in prototypes
+    FunctionDeclaration(
+        NEWTypes,
+        Token,
+        Vec<(NEWTypes, Option<Token>)>,
+        bool,
+        Option<StorageClass>,
+    ),
     Return(Token, Option<Expr>),
 }
 
@@ -31,7 +67,7 @@ impl Display for Stmt {
                 Stmt::FunctionDeclaration(..) => "'function-declaration'",
                 Stmt::Return(..) => "'return-statement'",
                 Stmt::InitList(..) => "'initializer-list'",
-                Stmt::Expr(_) => unimplemented!(),
+                Stmt::Expr(_) => "'expression-statement'",
             }
         )
     }
@@ -48,6 +48,7 @@ pub enum TokenKind {
     PipePipe,
     Xor,
     XorEqual,
+    Ellipsis,
 
     // Literals.
     Ident,
@@ -65,6 +66,15 @@ pub enum TokenKind {
     If,
     Return,
     While,
+    Static,
+    Extern,
+    Const,
+    Volatile,
+    Restrict,
+    VaList,
+    VaStart,
+    VaArg,
+    VaEnd,
 }
 
 impl From<&TokenType> for TokenKind {
@@ -103,12 +113,17 @@ impl From<&TokenType> for TokenKind {
             TokenType::LessLessEqual => TokenKind::LessLessEqual,
             TokenType::Ident(_) => TokenKind::Ident,
             TokenType::String(_) => TokenKind::String,
-            TokenType::Number(_) => TokenKind::Number,
+            TokenType::Number(..) => TokenKind::Number,
             TokenType::Else => TokenKind::Else,
             TokenType::For => TokenKind::For,
             TokenType::If => TokenKind::If,
             TokenType::Return => TokenKind::Return,
             TokenType::While => TokenKind::While,
+            TokenType::Static => TokenKind::Static,
+            TokenType::Extern => TokenKind::Extern,
+            TokenType::Const => TokenKind::Const,
+            TokenType::Volatile => TokenKind::Volatile,
+            TokenType::Restrict => TokenKind::Restrict,
             TokenType::PlusPlus => TokenKind::PlusPlus,
             TokenType::MinusMinus => TokenKind::MinusMinus,
             TokenType::Amp => TokenKind::Amp,
@@ -125,6 +140,11 @@ impl From<&TokenType> for TokenKind {
             TokenType::Long => TokenKind::Long,
             TokenType::Void => TokenKind::Void,
             TokenType::Tilde => TokenKind::Tilde,
+            TokenType::Ellipsis => TokenKind::Ellipsis,
+            TokenType::VaList => TokenKind::VaList,
+            TokenType::VaStart => TokenKind::VaStart,
+            TokenType::VaArg => TokenKind::VaArg,
+            TokenType::VaEnd => TokenKind::VaEnd,
         }
     }
 }
@@ -176,12 +196,15 @@ pub enum TokenType {
     PipePipe,
     Xor,
     XorEqual,
+    Ellipsis,
 
     // Literals.
     Ident(String),
+    // escape-sequences are already decoded, every char represents a single byte
     String(String),
     CharLit(i8),
-    Number(i32),
+    // value and the type chosen by the literal's suffix and range
+    Number(i64, Types),
 
     // Keywords.
     Void,
@@ -193,6 +216,15 @@ pub enum TokenType {
     If,
     Return,
     While,
+    Static,
+    Extern,
+    Const,
+    Volatile,
+    Restrict,
+    VaList,
+    VaStart,
+    VaArg,
+    VaEnd,
 }
 impl Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -247,25 +279,46 @@ impl Display for TokenType {
                 TokenType::LessLessEqual => "'<<='",
                 TokenType::Ident(_) => "identifier",
                 TokenType::String(_) => "string",
-                TokenType::Number(_) => "number",
+                TokenType::Number(..) => "number",
                 TokenType::Else => "'else'",
                 TokenType::For => "'for'",
                 TokenType::If => "'if'",
                 TokenType::Return => "'return'",
                 TokenType::While => "'while'",
+                TokenType::Static => "'static'",
+                TokenType::Extern => "'extern'",
+                TokenType::Const => "'const'",
+                TokenType::Volatile => "'volatile'",
+                TokenType::Restrict => "'restrict'",
                 TokenType::Void => "'void'",
                 TokenType::Tilde => "'~'",
+                TokenType::Ellipsis => "'...'",
+                TokenType::VaList => "'va_list'",
+                TokenType::VaStart => "'va_start'",
+                TokenType::VaArg => "'va_arg'",
+                TokenType::VaEnd => "'va_end'",
             }
         )
     }
 }
 
+// source-comment which is kept as trivia of the token following it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    pub text: String,
+    pub line_index: i32,
+    // comment is on the same line as the token before it
+    pub trailing: bool,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub token: TokenType,
     pub line_index: i32,
     pub column: i32,
     pub line_string: String,
+    // comments between the previous token and this one
+    pub comments: Vec<Comment>,
 }
 impl Token {
     pub fn new(token: TokenType, line_index: i32, column: i32, line_string: String) -> Self {
@@ -274,6 +327,7 @@ impl Token {
             line_index,
             column,
             line_string,
+            comments: Vec::new(),
         }
     }
     pub fn unwrap_string(&self) -> String {
@@ -283,9 +337,9 @@ impl Token {
             _ => panic!("cant unwrap string on {} token", self.token),
         }
     }
-    pub fn unwrap_num(&self) -> i32 {
+    pub fn unwrap_num(&self) -> (i64, Types) {
         match &self.token {
-            TokenType::Number(n) => *n,
+            TokenType::Number(n, type_decl) => (*n, type_decl.clone()),
             _ => panic!("cant unwrap number on {} token", self.token),
         }
     }
@@ -296,10 +350,22 @@ impl Token {
         }
     }
     pub fn is_type(&self) -> bool {
-        Types::into_vec().contains(&TokenKind::from(&self.token))
+        self.token == TokenType::VaList || Types::into_vec().contains(&TokenKind::from(&self.token))
+    }
+    pub fn is_storage_class(&self) -> bool {
+        matches!(self.token, TokenType::Static | TokenType::Extern)
+    }
+    pub fn is_qualifier(&self) -> bool {
+        matches!(
+            self.token,
+            TokenType::Const | TokenType::Volatile | TokenType::Restrict
+        )
     }
     pub fn into_type(self) -> NEWTypes {
         assert!(self.is_type());
+        if self.token == TokenType::VaList {
+            return NEWTypes::va_list();
+        }
 
         NEWTypes::Primitive(match self.token {
             TokenType::Int => Types::Int,
@@ -325,3 +391,21 @@ impl Token {
         }
     }
 }
+
+// escapes decoded string-data so that it can be printed inside of double-quotes,
+// the result is valid in both C and assembler
+pub fn escape(data: &str) -> String {
+    let mut escaped = String::new();
+    for c in data.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            ' '..='~' => escaped.push(c),
+            // octal escapes always take 3 digits so a following digit isn't read as part of them
+            _ => escaped.push_str(&format!("\\{:03o}", c as u32)),
+        }
+    }
+    escaped
+}
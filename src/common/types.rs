@@ -1,30 +1,68 @@
 use crate::common::token::TokenKind;
 use std::fmt::Display;
 
-static RETURN_REG: &[&str; 3] = &["%al", "%eax", "%rax"];
-
 pub trait TypeInfo {
     // returns size in bytes of type
     fn size(&self) -> usize;
 
-    // returns the correct suffix for a register of type
-    fn reg_suffix(&self) -> &str;
-
-    // returns the instruction-suffixes
-    fn suffix(&self) -> &str;
-
     // returns the instruction-suffixes spelled out
     fn complete_suffix(&self) -> &str;
+}
 
-    // returns the return register name of type
-    fn return_reg(&self) -> &str;
+// restrict how an object can be accessed, they don't change its representation
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Qualifiers {
+    pub is_const: bool,
+    pub is_volatile: bool,
+    pub is_restrict: bool,
+}
+impl Qualifiers {
+    pub fn is_empty(&self) -> bool {
+        *self == Qualifiers::default()
+    }
+    // other has no qualifier that self doesn't also have
+    pub fn contains(&self, other: &Qualifiers) -> bool {
+        (self.is_const || !other.is_const)
+            && (self.is_volatile || !other.is_volatile)
+            && (self.is_restrict || !other.is_restrict)
+    }
+    pub fn union(self, other: Qualifiers) -> Qualifiers {
+        Qualifiers {
+            is_const: self.is_const || other.is_const,
+            is_volatile: self.is_volatile || other.is_volatile,
+            is_restrict: self.is_restrict || other.is_restrict,
+        }
+    }
+}
+impl Display for Qualifiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<&str> = [
+            (self.is_const, "const"),
+            (self.is_volatile, "volatile"),
+            (self.is_restrict, "restrict"),
+        ]
+        .into_iter()
+        .filter_map(|(is_set, name)| is_set.then_some(name))
+        .collect();
+        write!(f, "{}", names.join(" "))
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum NEWTypes {
     Primitive(Types),
-    Array { amount: usize, of: Box<NEWTypes> },
+    Array {
+        amount: usize,
+        of: Box<NEWTypes>,
+    },
     Pointer(Box<NEWTypes>),
+    Function {
+        return_type: Box<NEWTypes>,
+        params: Vec<NEWTypes>,
+        variadic: bool,
+    },
+    // never nested and never holds empty qualifiers, arrays are qualified through their elements
+    Qualified(Qualifiers, Box<NEWTypes>),
 }
 
 impl TypeInfo for NEWTypes {
@@ -36,30 +74,16 @@ impl TypeInfo for NEWTypes {
                 amount,
                 of: element_type,
             } => amount * element_type.size(),
-        }
-    }
-    fn reg_suffix(&self) -> &str {
-        match self {
-            NEWTypes::Primitive(t) => t.reg_suffix(),
-            NEWTypes::Pointer(_) | NEWTypes::Array { .. } => "",
-        }
-    }
-    fn suffix(&self) -> &str {
-        match self {
-            NEWTypes::Primitive(t) => t.suffix(),
-            NEWTypes::Pointer(_) | NEWTypes::Array { .. } => "q",
+            // functions only exist as pointers at runtime
+            NEWTypes::Function { .. } => 8,
+            NEWTypes::Qualified(_, inner) => inner.size(),
         }
     }
     fn complete_suffix(&self) -> &str {
         match self {
             NEWTypes::Primitive(t) => t.complete_suffix(),
-            NEWTypes::Pointer(_) | NEWTypes::Array { .. } => "quad",
-        }
-    }
-    fn return_reg(&self) -> &str {
-        match self {
-            NEWTypes::Primitive(t) => t.return_reg(),
-            NEWTypes::Pointer(_) | NEWTypes::Array { .. } => RETURN_REG[2],
+            NEWTypes::Qualified(_, inner) => inner.complete_suffix(),
+            NEWTypes::Pointer(_) | NEWTypes::Array { .. } | NEWTypes::Function { .. } => "quad",
         }
     }
 }
@@ -72,6 +96,22 @@ impl Display for NEWTypes {
                 NEWTypes::Primitive(t) => t.fmt().to_string(),
                 NEWTypes::Array { of, amount } => format!("{}[{}]", of, amount),
                 NEWTypes::Pointer(to) => format!("{}*", to),
+                NEWTypes::Function {
+                    return_type,
+                    params,
+                    variadic,
+                } => {
+                    let mut params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                    if *variadic {
+                        params.push("...".to_string());
+                    }
+                    format!("{}({})", return_type, params.join(", "))
+                }
+                // qualifiers of pointers follow the '*'
+                NEWTypes::Qualified(qualifiers, inner) => match **inner {
+                    NEWTypes::Pointer(_) => format!("{} {}", inner, qualifiers),
+                    _ => format!("{} {}", qualifiers, inner),
+                },
             }
         )
     }
@@ -80,9 +120,19 @@ impl Display for NEWTypes {
 #[macro_export]
 macro_rules! arr_decay {
     ($arr:expr,$ast:expr,$token:expr) => {
-        if let NEWTypes::Array { of, .. } = $arr {
-            $arr = NEWTypes::Pointer(of);
-
+        if let Some(decayed) = $crate::arr_decay!(&$arr => $ast, $token) {
+            $arr = decayed;
+        }
+    };
+    // only rewrites the expression, evaluates to the decayed type if there is one
+    (&$arr:expr => $ast:expr, $token:expr) => {{
+        // arrays decay into pointers to their first element and functions into function-pointers
+        let decayed = match &$arr {
+            NEWTypes::Array { of, .. } => Some(NEWTypes::Pointer(of.clone())),
+            NEWTypes::Function { .. } => Some(NEWTypes::Pointer(Box::new($arr.clone()))),
+            _ => None,
+        };
+        if decayed.is_some() {
             $ast.kind = ExprKind::Unary {
                 token: Token::new(
                     TokenType::Amp,
@@ -93,26 +143,83 @@ macro_rules! arr_decay {
                 right: Box::new($ast.clone()),
             };
         }
-    };
+        decayed
+    }};
 }
 impl NEWTypes {
+    // System V va_list is an array of a single struct holding gp_offset, fp_offset,
+    // overflow_arg_area and reg_save_area, it's 8-byte aligned and 24 bytes large
+    pub fn va_list() -> NEWTypes {
+        NEWTypes::Array {
+            amount: 3,
+            of: Box::new(NEWTypes::Primitive(Types::Long)),
+        }
+    }
+    // scalars are aligned by their size and arrays like their elements
+    pub fn alignment(&self) -> usize {
+        match self {
+            NEWTypes::Array { of, .. } => of.alignment(),
+            NEWTypes::Qualified(_, inner) => inner.alignment(),
+            _ => self.size(),
+        }
+    }
     pub fn pointer_to(&mut self) {
         *self = NEWTypes::Pointer(Box::new(self.clone()));
     }
-    pub fn deref_at(&self) -> Option<NEWTypes> {
+    // adds the qualifiers to the type, merging them with existing ones
+    pub fn qualify(self, qualifiers: Qualifiers) -> NEWTypes {
+        match self {
+            _ if qualifiers.is_empty() => self,
+            NEWTypes::Array { amount, of } => NEWTypes::Array {
+                amount,
+                of: Box::new(of.qualify(qualifiers)),
+            },
+            NEWTypes::Qualified(existing, inner) => {
+                NEWTypes::Qualified(existing.union(qualifiers), inner)
+            }
+            _ => NEWTypes::Qualified(qualifiers, Box::new(self)),
+        }
+    }
+    // qualifiers of the object itself, an array is qualified like its elements
+    pub fn qualifiers(&self) -> Qualifiers {
+        match self {
+            NEWTypes::Qualified(qualifiers, _) => *qualifiers,
+            NEWTypes::Array { of, .. } => of.qualifiers(),
+            _ => Qualifiers::default(),
+        }
+    }
+    // the type of the value held by an object, without its top-level qualifiers
+    pub fn unqualified(&self) -> &NEWTypes {
         match self {
+            NEWTypes::Qualified(_, inner) => inner,
+            _ => self,
+        }
+    }
+    pub fn deref_at(&self) -> Option<NEWTypes> {
+        match self.unqualified() {
             NEWTypes::Pointer(inner) => Some(*inner.clone()),
             _ => None,
         }
     }
     pub fn is_void(&self) -> bool {
-        *self == NEWTypes::Primitive(Types::Void)
+        *self.unqualified() == NEWTypes::Primitive(Types::Void)
+    }
+    pub fn is_func(&self) -> bool {
+        matches!(*self, NEWTypes::Function { .. })
     }
     pub fn is_ptr(&self) -> bool {
-        matches!(*self, NEWTypes::Pointer(_)) || matches!(*self, NEWTypes::Array { .. })
+        matches!(
+            self.unqualified(),
+            NEWTypes::Pointer(_) | NEWTypes::Array { .. }
+        )
     }
+    // top-level qualifiers don't matter when assigning values, but a pointer can't lose the
+    // qualifiers of what it points to
     pub fn type_compatible(&self, other: &NEWTypes) -> bool {
-        match (self, other) {
+        let pointee_compatible = |to: &NEWTypes, from: &NEWTypes| {
+            to.unqualified() == from.unqualified() && to.qualifiers().contains(&from.qualifiers())
+        };
+        match (self.unqualified(), other.unqualified()) {
             (NEWTypes::Primitive(Types::Void), NEWTypes::Primitive(Types::Void)) => true,
 
             (NEWTypes::Primitive(Types::Void), NEWTypes::Primitive(_))
@@ -120,9 +227,9 @@ impl NEWTypes {
 
             (NEWTypes::Primitive(_), NEWTypes::Primitive(_)) => true,
 
-            (NEWTypes::Pointer(_), NEWTypes::Pointer(_)) => *self == *other,
+            (NEWTypes::Pointer(to), NEWTypes::Pointer(from)) => pointee_compatible(to, from),
 
-            (NEWTypes::Pointer(to), NEWTypes::Array { of, .. }) => *to == *of,
+            (NEWTypes::Pointer(to), NEWTypes::Array { of, .. }) => pointee_compatible(to, of),
 
             _ => false,
         }
@@ -145,17 +252,6 @@ impl TypeInfo for Types {
             Types::Long => 8,
         }
     }
-    fn reg_suffix(&self) -> &str {
-        match self {
-            Types::Void => unreachable!(),
-            Types::Char => "b",
-            Types::Int => "d",
-            Types::Long => "",
-        }
-    }
-    fn suffix(&self) -> &str {
-        &self.complete_suffix().get(0..1).unwrap()
-    }
     fn complete_suffix(&self) -> &str {
         match self {
             Types::Void => unreachable!(),
@@ -164,14 +260,6 @@ impl TypeInfo for Types {
             Types::Long => "quad",
         }
     }
-    fn return_reg(&self) -> &str {
-        match self {
-            Types::Void => unreachable!("doesnt have return register when returning void"),
-            Types::Char => RETURN_REG[0],
-            Types::Int => RETURN_REG[1],
-            Types::Long => RETURN_REG[2],
-        }
-    }
 }
 impl Types {
     pub fn into_vec() -> Vec<TokenKind> {
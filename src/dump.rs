@@ -0,0 +1,526 @@
+use crate::cli::DumpFormat;
+use crate::common::{error::*, expr::*, stmt::*, token::*, types::*};
+use std::fmt::{Display, Write};
+
+// structure every dump is built as, the text-form is rendered from the same value
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+impl Json {
+    fn string(s: impl Display) -> Json {
+        Json::String(s.to_string())
+    }
+    fn is_scalar(&self) -> bool {
+        !matches!(self, Json::Array(_) | Json::Object(_))
+    }
+}
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "\"{}\":{}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// renders scalars inline, strings are only quoted if they would be ambiguous
+fn scalar(value: &Json) -> String {
+    match value {
+        Json::String(s) if s.is_empty() || s.contains(|c: char| c.is_whitespace() || c == '"') => {
+            value.to_string()
+        }
+        Json::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+// prints a node with its scalar fields on one line and its child-nodes indented below
+fn tree(node: &Json, label: Option<&str>, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let label = label.map(|l| format!("{}: ", l)).unwrap_or_default();
+    match node {
+        Json::Object(fields) => {
+            let mut header = Vec::new();
+            for (key, value) in fields.iter().filter(|(_, v)| v.is_scalar()) {
+                match (key, value) {
+                    (_, Json::Null) => (),
+                    (&"kind", value) => header.insert(0, scalar(value)),
+                    (key, value) => header.push(format!("{}={}", key, scalar(value))),
+                }
+            }
+            writeln!(out, "{}{}{}", indent, label, header.join(" ")).unwrap();
+
+            for (key, value) in fields.iter().filter(|(_, v)| !v.is_scalar()) {
+                match value {
+                    Json::Array(values) => {
+                        for value in values {
+                            tree(value, Some(key), depth + 1, out);
+                        }
+                    }
+                    value => tree(value, Some(key), depth + 1, out),
+                }
+            }
+        }
+        Json::Array(values) => {
+            for value in values {
+                tree(value, None, depth, out);
+            }
+        }
+        value => writeln!(out, "{}{}{}", indent, label, scalar(value)).unwrap(),
+    }
+}
+
+pub fn render(value: &Json, format: DumpFormat) -> String {
+    match format {
+        DumpFormat::Text => {
+            let mut out = String::new();
+            tree(value, None, 0, &mut out);
+            out
+        }
+        DumpFormat::Json => format!("{}\n", value),
+    }
+}
+
+// one row per token, scan-errors are interleaved at the position they occurred at
+pub fn tokens(tokens: &[Token], errors: &[Error], format: DumpFormat) -> String {
+    let mut rows: Vec<(i32, i32, Json)> = tokens
+        .iter()
+        .map(|t| (t.line_index, t.column, token(t)))
+        .chain(errors.iter().map(|e| {
+            let error = Json::Object(vec![
+                ("kind", Json::string("Error")),
+                ("payload", Json::string(&e.msg)),
+                ("line", Json::Number(e.line_index as i64)),
+                ("column", Json::Number(e.column as i64)),
+            ]);
+            (e.line_index, e.column, error)
+        }))
+        .collect();
+    rows.sort_by_key(|(line, column, _)| (*line, *column));
+
+    let mut out = String::new();
+    match format {
+        DumpFormat::Text => {
+            let field = |row: &Json, name| match row {
+                Json::Object(fields) => fields
+                    .iter()
+                    .find(|(key, value)| *key == name && !matches!(value, Json::Null))
+                    .map(|(_, value)| scalar(value))
+                    .unwrap_or_default(),
+                _ => unreachable!("token rows are objects"),
+            };
+            let width = rows
+                .iter()
+                .map(|(_, _, row)| field(row, "kind").len())
+                .max()
+                .unwrap_or(0);
+            for (line, column, row) in rows {
+                let kind = field(&row, "kind");
+                let payload = field(&row, "payload");
+                let type_decl = field(&row, "type");
+                let position = format!("{}:{}", line, column);
+                let row = format!("{:<8}{:<width$}  {} {}", position, kind, payload, type_decl);
+                writeln!(out, "{}", row.trim_end()).unwrap();
+            }
+        }
+        DumpFormat::Json => {
+            for (_, _, row) in rows {
+                writeln!(out, "{}", row).unwrap();
+            }
+        }
+    }
+    out
+}
+
+fn token(token: &Token) -> Json {
+    // variant name without its payload
+    let kind = format!("{:?}", token.token);
+    let kind = kind.split('(').next().unwrap().to_string();
+    let payload = match &token.token {
+        TokenType::Ident(name) => Json::string(name),
+        TokenType::String(s) => Json::String(s.clone()),
+        TokenType::Number(n, _) => Json::Number(*n),
+        TokenType::CharLit(c) => Json::Number(*c as i64),
+        _ => Json::Null,
+    };
+    let mut fields = vec![("kind", Json::String(kind)), ("payload", payload)];
+    // integer literals differ in type depending on their value and suffix
+    if let TokenType::Number(_, type_decl) = &token.token {
+        fields.push((
+            "type",
+            Json::String(NEWTypes::Primitive(type_decl.clone()).to_string()),
+        ));
+    }
+    fields.push(("line", Json::Number(token.line_index as i64)));
+    fields.push(("column", Json::Number(token.column as i64)));
+    Json::Object(fields)
+}
+
+// lexeme of an operator without the quotes of its Display impl
+fn operator(token: &Token) -> Json {
+    Json::string(token.token.to_string().trim_matches('\''))
+}
+
+pub fn ast(statements: &[Stmt]) -> Json {
+    Json::Array(statements.iter().map(stmt).collect())
+}
+
+fn stmt(statement: &Stmt) -> Json {
+    let storage = |storage_class: &Option<StorageClass>| {
+        storage_class.as_ref().map_or(Json::Null, Json::string)
+    };
+    let variable = |kind,
+                    type_decl: &NEWTypes,
+                    name: &Token,
+                    is_global: &bool,
+                    storage_class: &Option<StorageClass>| {
+        vec![
+            ("kind", Json::String(kind)),
+            ("name", Json::string(name.unwrap_string())),
+            ("type", Json::string(type_decl)),
+            ("global", Json::Bool(*is_global)),
+            ("storage", storage(storage_class)),
+            ("line", Json::Number(name.line_index as i64)),
+        ]
+    };
+    // parameters of prototypes don't need a name
+    let params = |params: Vec<(&NEWTypes, Option<&Token>)>| {
+        Json::Array(
+            params
+                .into_iter()
+                .map(|(type_decl, name)| {
+                    Json::Object(vec![
+                        ("kind", Json::string("Param")),
+                        (
+                            "name",
+                            name.map_or(Json::Null, |name| Json::string(name.unwrap_string())),
+                        ),
+                        ("type", Json::string(type_decl)),
+                    ])
+                })
+                .collect(),
+        )
+    };
+
+    Json::Object(match statement {
+        Stmt::Expr(e) => vec![("kind", Json::string("ExprStmt")), ("expr", expr(e))],
+        Stmt::DeclareVar(type_decl, name, is_global, storage_class) => variable(
+            "DeclareVar".to_string(),
+            type_decl,
+            name,
+            is_global,
+            storage_class,
+        ),
+        Stmt::InitVar(type_decl, name, e, is_global, storage_class) => {
+            let mut fields = variable(
+                "InitVar".to_string(),
+                type_decl,
+                name,
+                is_global,
+                storage_class,
+            );
+            fields.push(("init", expr(e)));
+            fields
+        }
+        Stmt::InitList(type_decl, name, exprs, is_global, storage_class) => {
+            let mut fields = variable(
+                "InitList".to_string(),
+                type_decl,
+                name,
+                is_global,
+                storage_class,
+            );
+            fields.push(("init", Json::Array(exprs.iter().map(expr).collect())));
+            fields
+        }
+        Stmt::Block(body) => vec![
+            ("kind", Json::string("Block")),
+            ("body", Json::Array(body.iter().map(stmt).collect())),
+        ],
+        Stmt::If(token, cond, then_branch, else_branch) => vec![
+            ("kind", Json::string("If")),
+            ("line", Json::Number(token.line_index as i64)),
+            ("cond", expr(cond)),
+            ("then", stmt(then_branch)),
+            (
+                "else",
+                else_branch.as_ref().as_ref().map_or(Json::Null, stmt),
+            ),
+        ],
+        Stmt::While(token, cond, body) => vec![
+            ("kind", Json::string("While")),
+            ("line", Json::Number(token.line_index as i64)),
+            ("cond", expr(cond)),
+            ("body", stmt(body)),
+        ],
+        Stmt::Function(return_type, name, parameters, variadic, storage_class, body) => vec![
+            ("kind", Json::string("Function")),
+            ("name", Json::string(name.unwrap_string())),
+            ("return_type", Json::string(return_type)),
+            ("line", Json::Number(name.line_index as i64)),
+            (
+                "params",
+                params(parameters.iter().map(|(t, name)| (t, Some(name))).collect()),
+            ),
+            ("variadic", Json::Bool(*variadic)),
+            ("storage", storage(storage_class)),
+            ("body", Json::Array(body.iter().map(stmt).collect())),
+        ],
+        Stmt::FunctionDeclaration(return_type, name, parameters, variadic, storage_class) => vec![
+            ("kind", Json::string("FunctionDeclaration")),
+            ("name", Json::string(name.unwrap_string())),
+            ("return_type", Json::string(return_type)),
+            ("line", Json::Number(name.line_index as i64)),
+            (
+                "params",
+                params(
+                    parameters
+                        .iter()
+                        .map(|(t, name)| (t, name.as_ref()))
+                        .collect(),
+                ),
+            ),
+            ("variadic", Json::Bool(*variadic)),
+            ("storage", storage(storage_class)),
+        ],
+        Stmt::Return(token, value) => vec![
+            ("kind", Json::string("Return")),
+            ("line", Json::Number(token.line_index as i64)),
+            ("value", value.as_ref().map_or(Json::Null, expr)),
+        ],
+    })
+}
+
+fn expr(ast: &Expr) -> Json {
+    let (kind, mut fields) = match &ast.kind {
+        ExprKind::Binary { left, token, right } => (
+            "Binary",
+            vec![
+                ("operator", operator(token)),
+                ("left", expr(left)),
+                ("right", expr(right)),
+            ],
+        ),
+        ExprKind::Logical { left, token, right } => (
+            "Logical",
+            vec![
+                ("operator", operator(token)),
+                ("left", expr(left)),
+                ("right", expr(right)),
+            ],
+        ),
+        ExprKind::Unary { token, right } => (
+            "Unary",
+            vec![("operator", operator(token)), ("operand", expr(right))],
+        ),
+        ExprKind::Grouping { expr: e } => ("Grouping", vec![("expr", expr(e))]),
+        ExprKind::Assign { l_expr, r_expr, .. } => (
+            "Assign",
+            vec![("left", expr(l_expr)), ("right", expr(r_expr))],
+        ),
+        ExprKind::CompoundAssign {
+            l_expr,
+            token,
+            r_expr,
+        } => (
+            "CompoundAssign",
+            vec![
+                ("operator", operator(token)),
+                ("left", expr(l_expr)),
+                ("right", expr(r_expr)),
+            ],
+        ),
+        ExprKind::Call { callee, args, .. } => (
+            "Call",
+            vec![
+                ("callee", expr(callee)),
+                ("args", Json::Array(args.iter().map(expr).collect())),
+            ],
+        ),
+        ExprKind::Builtin {
+            token,
+            args,
+            type_arg,
+        } => (
+            "Builtin",
+            vec![
+                ("name", operator(token)),
+                ("args", Json::Array(args.iter().map(expr).collect())),
+                (
+                    "type_arg",
+                    type_arg.as_ref().map_or(Json::Null, Json::string),
+                ),
+            ],
+        ),
+        ExprKind::CastUp { expr: e } => ("CastUp", vec![("expr", expr(e))]),
+        ExprKind::CastDown { expr: e } => ("CastDown", vec![("expr", expr(e))]),
+        ExprKind::ScaleUp { by, expr: e } => (
+            "ScaleUp",
+            vec![("by", Json::Number(*by as i64)), ("expr", expr(e))],
+        ),
+        ExprKind::ScaleDown {
+            shift_amount,
+            expr: e,
+        } => (
+            "ScaleDown",
+            vec![
+                ("shift_amount", Json::Number(*shift_amount as i64)),
+                ("expr", expr(e)),
+            ],
+        ),
+        ExprKind::PostUnary {
+            token,
+            left,
+            by_amount,
+        } => (
+            "PostUnary",
+            vec![
+                ("operator", operator(token)),
+                ("by_amount", Json::Number(*by_amount as i64)),
+                ("operand", expr(left)),
+            ],
+        ),
+        ExprKind::String(token) => (
+            "String",
+            vec![("value", Json::string(token.unwrap_string()))],
+        ),
+        ExprKind::Number(n) => ("Number", vec![("value", Json::Number(*n))]),
+        ExprKind::CharLit(c) => ("CharLit", vec![("value", Json::Number(*c as i64))]),
+        ExprKind::Ident(token) => ("Ident", vec![("name", Json::string(token.unwrap_string()))]),
+    };
+
+    // type and value-kind go right after the node-specific scalars
+    let position = fields.iter().take_while(|(_, v)| v.is_scalar()).count();
+    fields.insert(
+        position,
+        (
+            "type",
+            ast.type_decl.as_ref().map_or(Json::Null, Json::string),
+        ),
+    );
+    fields.insert(
+        position + 1,
+        (
+            "value_kind",
+            Json::string(match ast.value_kind {
+                ValueKind::Lvalue => "lvalue",
+                ValueKind::Rvalue => "rvalue",
+            }),
+        ),
+    );
+    fields.insert(0, ("kind", Json::string(kind)));
+    Json::Object(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+    use crate::test_utils::typecheck;
+
+    fn dump_source(source: &str, format: DumpFormat) -> String {
+        render(&ast(&typecheck(source)), format)
+    }
+
+    #[test]
+    fn dumps_typed_ast_as_tree() {
+        let actual = dump_source(
+            "int main() {\nint a = 1;\nint *p = &a;\nreturn *(p + 1);\n}",
+            DumpFormat::Text,
+        );
+        let expected = "\
+Function name=main return_type=int line=1 variadic=false
+  body: InitVar name=a type=int global=false line=2
+    init: Number value=1 type=int value_kind=rvalue
+  body: InitVar name=p type=int* global=false line=3
+    init: Unary operator=& type=int* value_kind=rvalue
+      operand: Ident name=a type=int value_kind=rvalue
+  body: Return line=4
+    value: Unary operator=* type=int value_kind=lvalue
+      operand: Grouping type=int* value_kind=lvalue
+        expr: Binary operator=+ type=int* value_kind=rvalue
+          left: Ident name=p type=int* value_kind=rvalue
+          right: CastUp type=int* value_kind=rvalue
+            expr: ScaleUp by=4 type=int value_kind=rvalue
+              expr: Number value=1 type=int value_kind=rvalue
+";
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn dumps_ast_as_json() {
+        let actual = dump_source(
+            "void f(char *s);\nint main() {\nf(\"a b\");\nreturn 0;\n}",
+            DumpFormat::Json,
+        );
+        let expected = r#"[{"kind":"FunctionDeclaration","name":"f","return_type":"void","line":1,"params":[{"kind":"Param","name":"s","type":"char*"}],"variadic":false,"storage":null},{"kind":"Function","name":"main","return_type":"int","line":2,"params":[],"variadic":false,"storage":null,"body":[{"kind":"ExprStmt","expr":{"kind":"Call","type":"void","value_kind":"rvalue","callee":{"kind":"Ident","name":"f","type":"void(char*)","value_kind":"lvalue"},"args":[{"kind":"Unary","operator":"&","type":"char[4]","value_kind":"rvalue","operand":{"kind":"String","value":"a b","type":"char[4]","value_kind":"rvalue"}}]}},{"kind":"Return","line":4,"value":{"kind":"Number","value":0,"type":"int","value_kind":"rvalue"}}]}]
+"#;
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn dumps_tokens_alongside_scan_errors() {
+        let (scanned, errors) = Scanner::new("int a = 1 @;\nf(\"x y\", 10L);").scan();
+
+        let expected = "\
+1:1     Int
+1:5     Ident       a
+1:7     Equal
+1:9     Number      1 int
+1:11    Error       \"Unexpected character: @\"
+1:12    Semicolon
+2:1     Ident       f
+2:2     LeftParen
+2:3     String      \"x y\"
+2:8     Comma
+2:10    Number      10 long
+2:13    RightParen
+2:14    Semicolon
+";
+        assert_eq!(tokens(&scanned, &errors, DumpFormat::Text), expected);
+
+        let expected = r#"{"kind":"Number","payload":1,"type":"int","line":1,"column":9}
+{"kind":"Error","payload":"Unexpected character: @","line":1,"column":11}
+"#;
+        let actual = tokens(&scanned[3..4], &errors, DumpFormat::Json);
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::common::{error::*, expr::*, token::*, types::*};
+
+// folds an already typechecked expression if its operands are known at compile-time,
+// subexpressions have to be folded before their parent
+pub fn fold(expr: &mut Expr, warnings: &mut Vec<Error>) {
+    let type_decl = match &expr.type_decl {
+        Some(type_decl @ NEWTypes::Primitive(t)) if *t != Types::Void => type_decl.clone(),
+        _ => return,
+    };
+
+    let folded = match &mut expr.kind {
+        ExprKind::Binary { left, token, right } => {
+            if matches!(token.token, TokenType::Slash | TokenType::Mod)
+                && constant_value(right) == Some(0)
+            {
+                warnings.push(Error::new(token, "Division by zero"));
+            }
+            match (constant_value(left), constant_value(right)) {
+                (Some(l), Some(r)) => eval_binary(&token.token, l, r, &type_decl),
+                (l, r) => {
+                    if let Some(simplified) = simplify(&token.token, left, l, right, r, &type_decl)
+                    {
+                        *expr = simplified;
+                    }
+                    return;
+                }
+            }
+        }
+        ExprKind::Unary { token, right } => {
+            constant_value(right).and_then(|v| eval_unary(&token.token, v, &type_decl))
+        }
+        ExprKind::Logical { left, token, right } => {
+            match (constant_value(left), constant_value(right)) {
+                // right side isn't evaluated if left side decides the result
+                (Some(0), _) if token.token == TokenType::AmpAmp => Some(0),
+                (Some(l), _) if l != 0 && token.token == TokenType::PipePipe => Some(1),
+                (Some(_), Some(r)) => Some((r != 0) as i64),
+                _ => None,
+            }
+        }
+        ExprKind::Grouping { expr } => constant_value(expr),
+        _ => None,
+    };
+
+    if let Some(value) = folded {
+        *expr = make_constant(value, type_decl);
+    }
+}
+
+// evaluates an integer constant-expression which hasn't been typechecked yet
+pub fn const_eval(expr: &Expr) -> Option<i64> {
+    const_eval_typed(expr).map(|(value, _)| value)
+}
+// operands are promoted to int unless a long-literal is involved
+fn const_eval_typed(expr: &Expr) -> Option<(i64, NEWTypes)> {
+    let int_type = NEWTypes::Primitive(Types::Int);
+    match &expr.kind {
+        ExprKind::Number(n) => Some((*n, expr.type_decl.clone().unwrap_or(int_type))),
+        ExprKind::CharLit(c) => Some((*c as i64, int_type)),
+        ExprKind::Grouping { expr } => const_eval_typed(expr),
+        ExprKind::Unary { token, right } => {
+            let (value, type_decl) = const_eval_typed(right)?;
+            Some((eval_unary(&token.token, value, &type_decl)?, type_decl))
+        }
+        ExprKind::Binary { left, token, right } => {
+            let (l, left_type) = const_eval_typed(left)?;
+            let (r, right_type) = const_eval_typed(right)?;
+            // shifts have the type of their left operand
+            let type_decl = match token.token {
+                TokenType::LessLess | TokenType::GreaterGreater => left_type,
+                _ if right_type.size() > left_type.size() => right_type,
+                _ => left_type,
+            };
+            Some((eval_binary(&token.token, l, r, &type_decl)?, type_decl))
+        }
+        ExprKind::Logical { left, token, right } => {
+            let value = match (token.token.clone(), const_eval(left)?) {
+                (TokenType::AmpAmp, 0) => 0,
+                (TokenType::PipePipe, l) if l != 0 => 1,
+                _ => (const_eval(right)? != 0) as i64,
+            };
+            Some((value, int_type))
+        }
+        _ => None,
+    }
+}
+
+// returns the value of a folded expression, looking through implicit conversions
+pub fn constant_value(expr: &Expr) -> Option<i64> {
+    match &expr.kind {
+        ExprKind::Number(n) => Some(*n),
+        ExprKind::CharLit(c) => Some(*c as i64),
+        ExprKind::Grouping { expr } => constant_value(expr),
+        ExprKind::CastUp { expr: inner } | ExprKind::CastDown { expr: inner } => {
+            match &expr.type_decl {
+                Some(NEWTypes::Array { .. }) | None => None,
+                Some(type_decl) => Some(wrap(constant_value(inner)?, type_decl)),
+            }
+        }
+        ExprKind::ScaleUp { by, expr } => Some(constant_value(expr)? * *by as i64),
+        _ => None,
+    }
+}
+
+fn make_constant(value: i64, type_decl: NEWTypes) -> Expr {
+    Expr {
+        kind: ExprKind::Number(value),
+        type_decl: Some(type_decl),
+        value_kind: ValueKind::Rvalue,
+    }
+}
+
+// truncates a value to the range of the type, signed overflow wraps around
+fn wrap(value: i64, type_decl: &NEWTypes) -> i64 {
+    match type_decl.size() {
+        1 => value as i8 as i64,
+        4 => value as i32 as i64,
+        _ => value,
+    }
+}
+fn bits(type_decl: &NEWTypes) -> i64 {
+    type_decl.size() as i64 * 8
+}
+
+// evaluates the operation the same way it would at runtime,
+// returns None if the result is undefined behaviour
+fn eval_binary(op: &TokenType, left: i64, right: i64, type_decl: &NEWTypes) -> Option<i64> {
+    let min = wrap(1 << (bits(type_decl) - 1), type_decl);
+
+    Some(match op {
+        TokenType::Plus => wrap(left.wrapping_add(right), type_decl),
+        TokenType::Minus => wrap(left.wrapping_sub(right), type_decl),
+        TokenType::Star => wrap(left.wrapping_mul(right), type_decl),
+        TokenType::Slash | TokenType::Mod if right == 0 || (left == min && right == -1) => {
+            return None
+        }
+        TokenType::Slash => left / right,
+        TokenType::Mod => left % right,
+        TokenType::LessLess | TokenType::GreaterGreater
+            if right < 0 || right >= bits(type_decl) =>
+        {
+            return None
+        }
+        TokenType::LessLess => wrap(left.wrapping_shl(right as u32), type_decl),
+        TokenType::GreaterGreater => left >> right,
+        TokenType::Amp => left & right,
+        TokenType::Pipe => left | right,
+        TokenType::Xor => left ^ right,
+        TokenType::EqualEqual => (left == right) as i64,
+        TokenType::BangEqual => (left != right) as i64,
+        TokenType::Less => (left < right) as i64,
+        TokenType::LessEqual => (left <= right) as i64,
+        TokenType::Greater => (left > right) as i64,
+        TokenType::GreaterEqual => (left >= right) as i64,
+        _ => return None,
+    })
+}
+fn eval_unary(op: &TokenType, right: i64, type_decl: &NEWTypes) -> Option<i64> {
+    Some(match op {
+        TokenType::Minus => wrap(right.wrapping_neg(), type_decl),
+        TokenType::Tilde => wrap(!right, type_decl),
+        TokenType::Bang => (right == 0) as i64,
+        _ => return None,
+    })
+}
+
+// applies algebraic identities when only one side of a binary expression is constant
+fn simplify(
+    op: &TokenType,
+    left: &Expr,
+    l: Option<i64>,
+    right: &Expr,
+    r: Option<i64>,
+    type_decl: &NEWTypes,
+) -> Option<Expr> {
+    // operand can only replace the whole expression if it already has the resulting type
+    let keep = |expr: &Expr| (expr.type_decl.as_ref() == Some(type_decl)).then(|| expr.clone());
+    // discarding an operand is only valid if evaluating it has no effect
+    let zero = |expr: &Expr| {
+        if has_side_effects(expr) {
+            None
+        } else {
+            Some(make_constant(0, type_decl.clone()))
+        }
+    };
+
+    match (op, l, r) {
+        (TokenType::Plus, Some(0), _) | (TokenType::Star, Some(1), _) => keep(right),
+        (TokenType::Plus | TokenType::Minus, _, Some(0))
+        | (TokenType::Star | TokenType::Slash, _, Some(1))
+        | (TokenType::LessLess | TokenType::GreaterGreater, _, Some(0)) => keep(left),
+        (TokenType::Star, Some(0), _) => zero(right),
+        (TokenType::Star, _, Some(0)) => zero(left),
+        _ => None,
+    }
+}
+
+fn has_side_effects(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Assign { .. }
+        | ExprKind::CompoundAssign { .. }
+        | ExprKind::Call { .. }
+        | ExprKind::Builtin { .. }
+        | ExprKind::PostUnary { .. } => true,
+        ExprKind::Binary { left, right, .. } | ExprKind::Logical { left, right, .. } => {
+            has_side_effects(left) || has_side_effects(right)
+        }
+        ExprKind::Unary { right: expr, .. }
+        | ExprKind::Grouping { expr }
+        | ExprKind::CastUp { expr }
+        | ExprKind::CastDown { expr }
+        | ExprKind::ScaleUp { expr, .. }
+        | ExprKind::ScaleDown { expr, .. } => has_side_effects(expr),
+        ExprKind::String(_) | ExprKind::Number(_) | ExprKind::CharLit(_) | ExprKind::Ident(_) => {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_signed_overflow() {
+        let int_type = NEWTypes::Primitive(Types::Int);
+
+        assert_eq!(
+            eval_binary(&TokenType::Plus, i32::MAX as i64, 1, &int_type),
+            Some(i32::MIN as i64)
+        );
+        assert_eq!(
+            eval_binary(&TokenType::LessLess, 1, 31, &int_type),
+            Some(i32::MIN as i64)
+        );
+        assert_eq!(eval_unary(&TokenType::Tilde, 0, &int_type), Some(-1));
+    }
+    #[test]
+    fn doesnt_fold_undefined_behaviour() {
+        let int_type = NEWTypes::Primitive(Types::Int);
+
+        assert_eq!(eval_binary(&TokenType::Slash, 1, 0, &int_type), None);
+        assert_eq!(eval_binary(&TokenType::Mod, 1, 0, &int_type), None);
+        assert_eq!(
+            eval_binary(&TokenType::Slash, i32::MIN as i64, -1, &int_type),
+            None
+        );
+        assert_eq!(eval_binary(&TokenType::LessLess, 1, 32, &int_type), None);
+        assert_eq!(
+            eval_binary(&TokenType::GreaterGreater, 1, -1, &int_type),
+            None
+        );
+    }
+    #[test]
+    fn truncates_division_towards_zero() {
+        let long_type = NEWTypes::Primitive(Types::Long);
+
+        assert_eq!(eval_binary(&TokenType::Slash, -17, 5, &long_type), Some(-3));
+        assert_eq!(eval_binary(&TokenType::Mod, -17, 5, &long_type), Some(-2));
+    }
+    #[test]
+    fn folds_values_outside_of_int_range() {
+        let long_type = NEWTypes::Primitive(Types::Long);
+        let shift = Expr::new(
+            ExprKind::Binary {
+                left: Box::new(Expr {
+                    type_decl: Some(long_type.clone()),
+                    ..Expr::new(ExprKind::Number(1), ValueKind::Rvalue)
+                }),
+                token: Token::new(TokenType::LessLess, 1, 1, String::new()),
+                right: Box::new(Expr::new(ExprKind::Number(40), ValueKind::Rvalue)),
+            },
+            ValueKind::Rvalue,
+        );
+
+        assert_eq!(const_eval(&shift), Some(1 << 40));
+    }
+}
@@ -0,0 +1,691 @@
+use crate::common::{error::*, expr::*, stmt::*, token::*, types::*};
+use crate::parser::*;
+use crate::scanner::Scanner;
+
+// pretty-prints a whole source-file, comments are carried over from the tokens they precede
+pub fn format(source: &str) -> Result<String, Vec<Error>> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_token()?;
+    let statements = Parser::new(tokens.clone()).parse_statements()?;
+
+    let mut printer = Printer {
+        tokens,
+        current: 0,
+        out: String::new(),
+        indent: 0,
+        line_start: true,
+        last_line: 0,
+        in_declaration: false,
+    };
+    for statement in statements.iter() {
+        printer.stmt(statement);
+    }
+    printer.comments(&scanner.comments);
+    printer.newline();
+
+    Ok(printer.out)
+}
+
+// walks the AST while consuming the source-tokens in the same order, this way the parts the
+// parser desugared can still be printed as they were written
+struct Printer {
+    tokens: Vec<Token>,
+    current: usize,
+    out: String,
+    indent: usize,
+    // indentation for the current line hasn't been written yet
+    line_start: bool,
+    // source-line of the last printed token or comment
+    last_line: i32,
+    // declarator-list continues with the next statement
+    in_declaration: bool,
+}
+impl Printer {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+    fn at(&self, kind: TokenKind) -> bool {
+        self.tokens
+            .get(self.current)
+            .is_some_and(|t| TokenKind::from(&t.token) == kind)
+    }
+    fn next(&mut self) -> Token {
+        self.current += 1;
+        self.tokens[self.current - 1].clone()
+    }
+
+    fn write(&mut self, mut s: &str) {
+        if self.line_start {
+            self.out.push_str(&"    ".repeat(self.indent));
+            self.line_start = false;
+        } else if self.out.ends_with(' ') {
+            // an inline comment is already followed by a space
+            s = s.strip_prefix(' ').unwrap_or(s);
+        }
+        self.out.push_str(s);
+    }
+    fn newline(&mut self) {
+        if !self.line_start {
+            self.out.truncate(self.out.trim_end_matches(' ').len());
+            self.out.push('\n');
+            self.line_start = true;
+        }
+    }
+    // keeps at most a single empty line from the source, but never directly after an opening brace
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("{\n") && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+    fn comments(&mut self, comments: &[Comment]) {
+        for comment in comments {
+            if comment.trailing && !self.out.is_empty() {
+                // stays at the end of the line it was written on
+                let end = self.out.trim_end().len();
+                self.out.insert_str(end, &format!(" {}", comment.text));
+
+                // nothing can follow a line-comment, block-comments can stay inline
+                match comment.text.starts_with("//") {
+                    true => self.newline(),
+                    false if !self.line_start => self.write(" "),
+                    false => (),
+                }
+            } else {
+                self.newline();
+                if comment.line_index > self.last_line + 1 {
+                    self.blank_line();
+                }
+                self.write(&comment.text);
+                self.newline();
+            }
+            // block-comments can span multiple lines
+            self.last_line = comment.line_index + comment.text.matches('\n').count() as i32;
+        }
+    }
+
+    // consumes the next source-token which has to match the token printed
+    fn take(&mut self, kind: TokenKind) {
+        let token = self.next();
+        assert_eq!(
+            TokenKind::from(&token.token),
+            kind,
+            "printed token doesn't match source at line {}",
+            token.line_index
+        );
+        self.token(token);
+    }
+    fn token(&mut self, token: Token) {
+        self.comments(&token.comments);
+        if self.line_start
+            && token.line_index > self.last_line + 1
+            && token.token != TokenType::RightBrace
+        {
+            self.blank_line();
+        }
+        self.write(&lexeme(&token));
+        self.last_line = token.line_index;
+    }
+    // source-tokens which aren't printed, their comments are kept though
+    fn skip(&mut self, kind: TokenKind) {
+        let token = self.next();
+        assert_eq!(TokenKind::from(&token.token), kind);
+        self.comments(&token.comments);
+    }
+
+    fn stmt(&mut self, statement: &Stmt) {
+        if !self.in_declaration {
+            self.newline();
+        }
+        self.stmt_inline(statement);
+        if !self.in_declaration {
+            self.newline();
+        }
+    }
+    fn stmt_inline(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expr(expr) => {
+                self.expr(expr);
+                self.take(TokenKind::Semicolon);
+            }
+            Stmt::DeclareVar(..) => {
+                self.declarator();
+                self.declaration_end();
+            }
+            Stmt::InitVar(_, _, expr, ..) => {
+                self.declarator();
+                self.spaced(TokenKind::Equal);
+                self.expr(expr);
+                self.declaration_end();
+            }
+            Stmt::InitList(type_decl, _, assignments, ..) => {
+                self.declarator();
+                self.spaced(TokenKind::Equal);
+
+                // the elements are the right side of the desugared assignments
+                let mut elements = assignments.iter().map(|assign| match &assign.kind {
+                    ExprKind::Assign { r_expr, .. } => &**r_expr,
+                    _ => unreachable!("initializer-lists are desugared into assignments"),
+                });
+                self.init_list(type_decl, &mut elements);
+                self.declaration_end();
+            }
+            Stmt::Block(_) | Stmt::While(..) if self.at(TokenKind::For) => self.for_loop(statement),
+            Stmt::Block(statements) => self.block(statements),
+            Stmt::If(_, cond, then_branch, else_branch) => {
+                self.take(TokenKind::If);
+                self.write(" ");
+                self.condition(cond);
+
+                let braced = self.body(then_branch);
+                if let Some(else_branch) = &**else_branch {
+                    match braced {
+                        true => self.write(" "),
+                        false => self.newline(),
+                    }
+                    self.take(TokenKind::Else);
+                    if let Stmt::If(..) = else_branch {
+                        self.write(" ");
+                        self.stmt_inline(else_branch);
+                    } else {
+                        self.body(else_branch);
+                    }
+                }
+            }
+            Stmt::While(_, cond, body) => {
+                self.take(TokenKind::While);
+                self.write(" ");
+                self.condition(cond);
+                self.body(body);
+            }
+            Stmt::Function(.., body) => {
+                self.declarator();
+                self.write(" ");
+                self.block(body);
+            }
+            Stmt::FunctionDeclaration(..) => {
+                self.declarator();
+                self.take(TokenKind::Semicolon);
+            }
+            Stmt::Return(_, expr) => {
+                self.take(TokenKind::Return);
+                if let Some(expr) = expr {
+                    self.write(" ");
+                    self.expr(expr);
+                }
+                self.take(TokenKind::Semicolon);
+            }
+        }
+    }
+    fn condition(&mut self, cond: &Expr) {
+        self.take(TokenKind::LeftParen);
+        self.expr(cond);
+        self.take(TokenKind::RightParen);
+    }
+    // braced bodies stay on the same line, otherwise the statement is indented on the next line
+    fn body(&mut self, statement: &Stmt) -> bool {
+        match statement {
+            Stmt::Block(statements) if self.at(TokenKind::LeftBrace) => {
+                self.write(" ");
+                self.block(statements);
+                true
+            }
+            _ => {
+                self.indent += 1;
+                self.stmt(statement);
+                self.indent -= 1;
+                false
+            }
+        }
+    }
+    fn block(&mut self, statements: &[Stmt]) {
+        self.take(TokenKind::LeftBrace);
+        self.indent += 1;
+        for statement in statements {
+            self.stmt(statement);
+        }
+
+        // comments before the closing brace still belong inside the block
+        let right_brace = self.next();
+        self.comments(&right_brace.comments);
+        self.indent -= 1;
+        self.newline();
+        self.write("}");
+        self.last_line = right_brace.line_index;
+    }
+    fn for_loop(&mut self, statement: &Stmt) {
+        // for (init; cond; inc) body <=> { init; while (cond) { body; inc; } }
+        let (init, while_loop) = match statement {
+            Stmt::Block(statements) => {
+                let (init, while_loop) = statements.split_at(statements.len() - 1);
+                (init, &while_loop[0])
+            }
+            while_loop => (&[][..], while_loop),
+        };
+        let Stmt::While(_, cond, body) = while_loop else {
+            unreachable!("for-loop always contains a while-loop")
+        };
+
+        self.take(TokenKind::For);
+        self.write(" ");
+        self.take(TokenKind::LeftParen);
+        match init.is_empty() {
+            // a declaration can have multiple declarators
+            false => init.iter().for_each(|init| self.stmt_inline(init)),
+            true => self.take(TokenKind::Semicolon),
+        }
+        // missing condition is desugared into '1'
+        if !self.at(TokenKind::Semicolon) {
+            self.write(" ");
+            self.expr(cond);
+        }
+        self.take(TokenKind::Semicolon);
+
+        let mut body = &**body;
+        if !self.at(TokenKind::RightParen) {
+            let Stmt::Block(statements) = body else {
+                unreachable!("increment is appended to the loop-body")
+            };
+            let Stmt::Expr(inc) = &statements[1] else {
+                unreachable!("increment is an expression-statement")
+            };
+            self.write(" ");
+            self.expr(inc);
+            body = &statements[0];
+        }
+        self.take(TokenKind::RightParen);
+        self.body(body);
+    }
+
+    // the type-specifier is only printed for the first declarator in a list
+    fn declarator(&mut self) {
+        if !self.in_declaration {
+            if self.at(TokenKind::Static) || self.at(TokenKind::Extern) {
+                let storage_class = self.next();
+                self.token(storage_class);
+                self.write(" ");
+            }
+            self.type_specifier();
+            self.write(" ");
+        }
+        self.in_declaration = false;
+        self.declarator_tokens();
+    }
+    // pointers, name, array-dimensions and parameter-lists as written in the source
+    fn declarator_tokens(&mut self) {
+        while self.at(TokenKind::Star) {
+            self.take(TokenKind::Star);
+            // abstract declarators can end right after the qualifiers
+            if self.qualifiers() && !self.at(TokenKind::Comma) && !self.at(TokenKind::RightParen) {
+                self.write(" ");
+            }
+        }
+        if self.at(TokenKind::LeftParen) {
+            self.take(TokenKind::LeftParen);
+            self.declarator_tokens();
+            self.take(TokenKind::RightParen);
+        } else if self.at(TokenKind::Ident) {
+            // names of parameters in prototypes are optional
+            self.take(TokenKind::Ident);
+        }
+
+        while self.at(TokenKind::LeftParen) {
+            self.params();
+        }
+        // array-sizes are folded by the parser so they're printed token by token
+        while self.at(TokenKind::LeftBracket) {
+            self.take(TokenKind::LeftBracket);
+            let mut depth = 0;
+            let mut first = true;
+            while depth > 0 || !self.at(TokenKind::RightBracket) {
+                let token = self.next();
+                match token.token {
+                    TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                    TokenType::RightParen | TokenType::RightBracket => depth -= 1,
+                    _ => (),
+                }
+                if !first
+                    && !matches!(token.token, TokenType::RightParen)
+                    && !self.out.ends_with('(')
+                {
+                    self.write(" ");
+                }
+                first = false;
+                self.token(token);
+            }
+            self.take(TokenKind::RightBracket);
+        }
+    }
+    // type-token together with the qualifiers written before and after it
+    fn type_specifier(&mut self) {
+        if self.qualifiers() {
+            self.write(" ");
+        }
+        let type_token = self.next();
+        self.token(type_token);
+        if !self.peek().is_qualifier() {
+            return;
+        }
+        self.write(" ");
+        self.qualifiers();
+    }
+    // prints a space-separated list of qualifiers, returns whether there were any
+    fn qualifiers(&mut self) -> bool {
+        let mut found = false;
+        while self.peek().is_qualifier() {
+            if found {
+                self.write(" ");
+            }
+            let qualifier = self.next();
+            self.token(qualifier);
+            found = true;
+        }
+        found
+    }
+    // type-specifier followed by an optional abstract declarator
+    fn type_name(&mut self) {
+        self.type_specifier();
+        if !self.at(TokenKind::Comma) && !self.at(TokenKind::RightParen) {
+            self.write(" ");
+            self.declarator_tokens();
+        }
+    }
+    fn params(&mut self) {
+        self.take(TokenKind::LeftParen);
+        while !self.at(TokenKind::RightParen) {
+            if self.at(TokenKind::Ellipsis) {
+                self.take(TokenKind::Ellipsis);
+            } else {
+                self.type_name();
+            }
+            if self.at(TokenKind::Comma) {
+                self.take(TokenKind::Comma);
+                self.write(" ");
+            }
+        }
+        self.take(TokenKind::RightParen);
+    }
+    // the following declaration is part of the same list if it's separated by a comma
+    fn declaration_end(&mut self) {
+        if self.at(TokenKind::Comma) {
+            self.take(TokenKind::Comma);
+            self.write(" ");
+            self.in_declaration = true;
+        } else {
+            self.take(TokenKind::Semicolon);
+        }
+    }
+    // braces are printed as in the source, all elements which were filled up with zero skipped
+    fn init_list<'a>(
+        &mut self,
+        type_decl: &NEWTypes,
+        elements: &mut impl Iterator<Item = &'a Expr>,
+    ) {
+        let NEWTypes::Array { of, .. } = type_decl else {
+            unreachable!("only arrays can be initialized with lists")
+        };
+        self.take(TokenKind::LeftBrace);
+
+        let mut count = 0;
+        while !self.at(TokenKind::RightBrace) {
+            if self.at(TokenKind::LeftBrace) {
+                self.init_list(of, elements);
+                count += array_element_count(*of.clone());
+            } else {
+                self.expr(elements.next().expect("element for every expression"));
+                count += 1;
+            }
+            if self.at(TokenKind::Comma) {
+                // trailing comma is dropped
+                if TokenKind::from(&self.tokens[self.current + 1].token) == TokenKind::RightBrace {
+                    self.skip(TokenKind::Comma);
+                } else {
+                    self.take(TokenKind::Comma);
+                    self.write(" ");
+                }
+            }
+        }
+        for _ in count..array_element_count(type_decl.clone()) {
+            elements.next();
+        }
+        self.take(TokenKind::RightBrace);
+    }
+
+    // operator surrounded by spaces
+    fn spaced(&mut self, kind: TokenKind) {
+        self.write(" ");
+        self.take(kind);
+        self.write(" ");
+    }
+    fn expr(&mut self, ast: &Expr) {
+        match &ast.kind {
+            ExprKind::CompoundAssign { l_expr, token, .. }
+                if matches!(token.token, TokenType::PlusPlus | TokenType::MinusMinus) =>
+            {
+                // ++a is desugared into a += 1
+                self.take(TokenKind::from(&token.token));
+                self.expr(l_expr);
+            }
+            ExprKind::Binary { left, token, right }
+            | ExprKind::Logical { left, token, right }
+            | ExprKind::Assign {
+                l_expr: left,
+                token,
+                r_expr: right,
+            }
+            | ExprKind::CompoundAssign {
+                l_expr: left,
+                token,
+                r_expr: right,
+            } => {
+                self.expr(left);
+                self.spaced(TokenKind::from(&token.token));
+                self.expr(right);
+            }
+            ExprKind::Unary { token, right } if self.is_source(token) => {
+                let operator = lexeme(token);
+                self.take(TokenKind::from(&token.token));
+                // '- -a' can't be printed as '--a'
+                if lexeme(self.peek()).starts_with(&operator) {
+                    self.write(" ");
+                }
+                self.expr(right);
+            }
+            ExprKind::Unary { right, .. } => {
+                // a[i] is desugared into *(a + i)
+                let ExprKind::Grouping { expr } = &right.kind else {
+                    unreachable!("index is desugared into grouping")
+                };
+                let ExprKind::Binary { left, right, .. } = &expr.kind else {
+                    unreachable!("index is desugared into addition")
+                };
+                self.expr(left);
+                self.take(TokenKind::LeftBracket);
+                self.expr(right);
+                self.take(TokenKind::RightBracket);
+            }
+            ExprKind::Grouping { expr } => {
+                self.take(TokenKind::LeftParen);
+                self.expr(expr);
+                self.take(TokenKind::RightParen);
+            }
+            ExprKind::Call { callee, args, .. } => {
+                self.expr(callee);
+                self.take(TokenKind::LeftParen);
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.take(TokenKind::Comma);
+                        self.write(" ");
+                    }
+                    self.expr(arg);
+                }
+                self.take(TokenKind::RightParen);
+            }
+            ExprKind::PostUnary { token, left, .. } => {
+                self.expr(left);
+                self.take(TokenKind::from(&token.token));
+            }
+            ExprKind::Builtin {
+                token,
+                args,
+                type_arg,
+            } => {
+                self.take(TokenKind::from(&token.token));
+                self.take(TokenKind::LeftParen);
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.take(TokenKind::Comma);
+                        self.write(" ");
+                    }
+                    self.expr(arg);
+                }
+                if type_arg.is_some() {
+                    self.take(TokenKind::Comma);
+                    self.write(" ");
+                    self.type_name();
+                }
+                self.take(TokenKind::RightParen);
+            }
+            ExprKind::String(_)
+            | ExprKind::Number(_)
+            | ExprKind::CharLit(_)
+            | ExprKind::Ident(_) => {
+                let token = self.next();
+                self.token(token);
+            }
+            ExprKind::CastUp { .. }
+            | ExprKind::CastDown { .. }
+            | ExprKind::ScaleUp { .. }
+            | ExprKind::ScaleDown { .. } => {
+                unreachable!("formatter only prints untyped AST")
+            }
+        }
+    }
+    // tokens synthesized by the parser don't appear at their position in the source
+    fn is_source(&self, token: &Token) -> bool {
+        let source = self.peek();
+        source.line_index == token.line_index && source.column == token.column
+    }
+}
+
+fn lexeme(token: &Token) -> String {
+    match &token.token {
+        TokenType::Ident(name) => name.clone(),
+        TokenType::String(_) | TokenType::CharLit(_) | TokenType::Number(..) => {
+            literal_source(token)
+        }
+        TokenType::LeftParen => "(".to_string(),
+        token => token.to_string().trim_matches('\'').to_string(),
+    }
+}
+
+// literals are printed like in the source so that escape-sequences, prefixes and
+// suffixes keep their spelling
+fn literal_source(token: &Token) -> String {
+    let mut chars = token.line_string.chars().skip((token.column - 1) as usize);
+    if let TokenType::Number(..) = token.token {
+        return chars.take_while(|c| c.is_ascii_alphanumeric()).collect();
+    }
+    let delimiter = chars.next().expect("literal starts with its delimiter");
+    let mut literal = String::from(delimiter);
+    while let Some(c) = chars.next() {
+        literal.push(c);
+        if c == '\\' {
+            literal.extend(chars.next());
+        } else if c == delimiter {
+            break;
+        }
+    }
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_statements() {
+        let actual = format(
+            "int  a[2*3]={1,2,};\nint *f(int*p,char c){\nfor(int i=0;i<3;i++)if(p[i]==-1)return p;else{a[i]=- -c;}\n\n\n    while(a[0])++a[1];\nreturn &a[1];}",
+        )
+        .unwrap();
+        let expected = "\
+int a[2 * 3] = {1, 2};
+int *f(int *p, char c) {
+    for (int i = 0; i < 3; i++)
+        if (p[i] == -1)
+            return p;
+        else {
+            a[i] = - -c;
+        }
+
+    while (a[0])
+        ++a[1];
+    return &a[1];
+}
+";
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn keeps_declarator_lists() {
+        let actual = format("int a,*b=&a , (*c)[2];\nint main(){for(int i=0,j;;){}}").unwrap();
+        let expected = "\
+int a, *b = &a, (*c)[2];
+int main() {
+    for (int i = 0, j;;) {
+    }
+}
+";
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn keeps_comments() {
+        let actual = format(
+            "// header\n\nint main() { // entry\n  int a; // counter\n  /* nothing\n     else */\n\n  for (;;) {} return 0 /* zero */ + 0; // done\n  // end of main\n}\n// trailer",
+        )
+        .unwrap();
+        let expected = "\
+// header
+
+int main() { // entry
+    int a; // counter
+    /* nothing
+     else */
+
+    for (;;) {
+    }
+    return 0 /* zero */ + 0; // done
+    // end of main
+}
+// trailer
+";
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn fixtures_round_trip() {
+        for entry in std::fs::read_dir("tests/fixtures").unwrap() {
+            let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            let Ok(formatted) = format(&source) else {
+                continue;
+            };
+            // formatting only changes whitespace and trailing commas, and is idempotent
+            let tokens = |s: &str| -> Vec<TokenType> {
+                let tokens: Vec<TokenType> = Scanner::new(s)
+                    .scan_token()
+                    .unwrap()
+                    .into_iter()
+                    .map(|t| t.token)
+                    .collect();
+                tokens
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, t)| {
+                        **t != TokenType::Comma || tokens.get(i + 1) != Some(&TokenType::RightBrace)
+                    })
+                    .map(|(_, t)| t.clone())
+                    .collect()
+            };
+            assert_eq!(tokens(&formatted), tokens(&source));
+            assert_eq!(format(&formatted).unwrap(), formatted);
+        }
+    }
+}
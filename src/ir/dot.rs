@@ -0,0 +1,80 @@
+use crate::ir::instr::*;
+use std::fmt::Write;
+
+// renders the control-flow graph of every function as a graphviz digraph
+pub fn cfg_dot(module: &Module) -> String {
+    let mut out = String::new();
+    for function in module.functions.iter() {
+        function_graph(function, &mut out);
+    }
+    out
+}
+
+fn function_graph(function: &Function, out: &mut String) {
+    // every return jumps to the shared epilogue in the generated assembly
+    let epilogue = format!("{}_epilogue", function.name);
+
+    writeln!(out, "digraph \"{}\" {{", function.name).unwrap();
+    writeln!(out, "  node [shape=box, fontname=monospace];").unwrap();
+    for block in function.blocks.iter() {
+        let mut label = format!("bb{}:\\l", block.id);
+        for instr in block.instrs.iter() {
+            write!(label, "  {}\\l", escape(&instr.to_string())).unwrap();
+        }
+        write!(label, "  {}\\l", escape(&block.terminator.to_string())).unwrap();
+        writeln!(out, "  bb{} [label=\"{}\"];", block.id, label).unwrap();
+    }
+    writeln!(out, "  \"{}\" [shape=ellipse];", epilogue).unwrap();
+
+    for block in function.blocks.iter() {
+        match &block.terminator {
+            Terminator::Jump(target) => writeln!(out, "  bb{} -> bb{};", block.id, target),
+            Terminator::Branch {
+                then_block,
+                else_block,
+                ..
+            } => {
+                writeln!(out, "  bb{} -> bb{} [label=true];", block.id, then_block).unwrap();
+                writeln!(out, "  bb{} -> bb{} [label=false];", block.id, else_block)
+            }
+            Terminator::Return(_) => writeln!(out, "  bb{} -> \"{}\";", block.id, epilogue),
+        }
+        .unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+// quotes and backslashes have to be escaped inside of a dot-string
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::lower;
+
+    fn cfg_source(source: &str) -> String {
+        cfg_dot(&lower(source))
+    }
+
+    #[test]
+    fn branches_and_returns_become_edges() {
+        let actual = cfg_source("int main() {\nint a = 1;\nwhile (a) a = 0;\nreturn a;\n}");
+        let expected = r#"digraph "main" {
+  node [shape=box, fontname=monospace];
+  bb0 [label="bb0:\l  store 1, local.0\l  jmp bb1\l"];
+  bb1 [label="bb1:\l  %0: int = load local.0\l  br %0, bb2, bb3\l"];
+  bb2 [label="bb2:\l  store 0, local.0\l  jmp bb1\l"];
+  bb3 [label="bb3:\l  %1: int = load local.0\l  ret %1\l"];
+  "main_epilogue" [shape=ellipse];
+  bb0 -> bb1;
+  bb1 -> bb2 [label=true];
+  bb1 -> bb3 [label=false];
+  bb2 -> bb1;
+  bb3 -> "main_epilogue";
+}
+"#;
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::common::{token::escape, types::*};
+use std::fmt::Display;
+
+// virtual register, holds a single typed value inside a function
+#[derive(Clone, PartialEq, Debug)]
+pub struct VReg {
+    pub id: usize,
+    pub type_decl: NEWTypes,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Reg(VReg),
+    Const(i64, NEWTypes),
+    // address of the stack-slot with the given index
+    Local(usize),
+    // address of a global variable or function
+    Global(String),
+    // address of a string-literal label
+    String(usize),
+}
+impl Value {
+    pub fn as_reg(&self) -> Option<&VReg> {
+        match self {
+            Value::Reg(reg) => Some(reg),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Sar,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnaryOp {
+    Neg,
+    BitNot,
+    Not,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Instr {
+    // dest = src; addresses are materialized
+    Copy {
+        dest: VReg,
+        src: Value,
+    },
+    Binary {
+        dest: VReg,
+        op: BinOp,
+        left: Value,
+        right: Value,
+    },
+    Unary {
+        dest: VReg,
+        op: UnaryOp,
+        operand: Value,
+    },
+    // sign-extends or truncates src to the type of dest
+    Cast {
+        dest: VReg,
+        src: Value,
+    },
+    Load {
+        dest: VReg,
+        addr: Value,
+        // volatile loads are never removed, merged or moved
+        volatile: bool,
+    },
+    Store {
+        addr: Value,
+        value: Value,
+    },
+    // callee is the address of the function, a global for direct calls
+    Call {
+        dest: Option<VReg>,
+        callee: Value,
+        args: Vec<Value>,
+        // callee takes a variable amount of arguments
+        variadic: bool,
+    },
+    // initializes the va_list at the given address for the current function
+    VaStart {
+        list: Value,
+    },
+    // source line the following instructions belong to, only emitted for debug-info
+    Line(usize),
+    // annotation which is carried into the assembly, only emitted for --asm-comments
+    Comment(String),
+}
+impl Instr {
+    pub fn dest(&self) -> Option<&VReg> {
+        match self {
+            Instr::Copy { dest, .. }
+            | Instr::Binary { dest, .. }
+            | Instr::Unary { dest, .. }
+            | Instr::Cast { dest, .. }
+            | Instr::Load { dest, .. } => Some(dest),
+            Instr::Call { dest, .. } => dest.as_ref(),
+            Instr::Store { .. } | Instr::VaStart { .. } | Instr::Line(_) | Instr::Comment(_) => {
+                None
+            }
+        }
+    }
+    pub fn operands(&self) -> Vec<&Value> {
+        match self {
+            Instr::Copy { src, .. } | Instr::Cast { src, .. } => vec![src],
+            Instr::Binary { left, right, .. } => vec![left, right],
+            Instr::Unary { operand, .. } => vec![operand],
+            Instr::Load { addr, .. } => vec![addr],
+            Instr::Store { addr, value } => vec![addr, value],
+            Instr::Call { callee, args, .. } => std::iter::once(callee).chain(args).collect(),
+            Instr::VaStart { list } => vec![list],
+            Instr::Line(_) | Instr::Comment(_) => vec![],
+        }
+    }
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Instr::Copy { src, .. } | Instr::Cast { src, .. } => vec![src],
+            Instr::Binary { left, right, .. } => vec![left, right],
+            Instr::Unary { operand, .. } => vec![operand],
+            Instr::Load { addr, .. } => vec![addr],
+            Instr::Store { addr, value } => vec![addr, value],
+            Instr::Call { callee, args, .. } => std::iter::once(callee).chain(args).collect(),
+            Instr::VaStart { list } => vec![list],
+            Instr::Line(_) | Instr::Comment(_) => vec![],
+        }
+    }
+}
+
+pub type BlockId = usize;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch {
+        cond: Value,
+        then_block: BlockId,
+        else_block: BlockId,
+    },
+    Return(Option<Value>),
+}
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch {
+                then_block,
+                else_block,
+                ..
+            } => vec![*then_block, *else_block],
+            Terminator::Return(_) => vec![],
+        }
+    }
+    pub fn operands(&self) -> Vec<&Value> {
+        match self {
+            Terminator::Branch { cond, .. } => vec![cond],
+            Terminator::Return(Some(value)) => vec![value],
+            _ => vec![],
+        }
+    }
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Branch { cond, .. } => vec![cond],
+            Terminator::Return(Some(value)) => vec![value],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BasicBlock {
+    pub id: BlockId,
+    pub instrs: Vec<Instr>,
+    pub terminator: Terminator,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Function {
+    pub name: String,
+    // line of the function-name in the source
+    pub line: usize,
+    pub return_type: NEWTypes,
+    // parameter type and the stack-slot it gets stored into
+    pub params: Vec<(NEWTypes, usize)>,
+    // parameter-list ends with '...'
+    pub variadic: bool,
+    // internal linkage, the symbol isn't visible to other object-files
+    pub is_static: bool,
+    // types of all stack-slots, indexed by Value::Local
+    pub slots: Vec<NEWTypes>,
+    // name of the variable which owns a slot
+    pub slot_names: Vec<String>,
+    // blocks in layout-order, entry block is always first
+    pub blocks: Vec<BasicBlock>,
+    pub vreg_count: usize,
+}
+#[derive(Clone, PartialEq, Debug)]
+pub struct Global {
+    pub name: String,
+    pub type_decl: NEWTypes,
+    // initializer values in memory-order, None if zero-initialized
+    pub init: Option<Vec<Value>>,
+    // internal linkage, also true for static locals
+    pub is_static: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Module {
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+    // string-literal data and its label index
+    pub strings: Vec<(String, usize)>,
+}
+
+impl Display for VReg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "%{}", self.id)
+    }
+}
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Reg(reg) => write!(f, "{}", reg),
+            Value::Const(n, _) => write!(f, "{}", n),
+            Value::Local(index) => write!(f, "local.{}", index),
+            Value::Global(name) => write!(f, "@{}", name),
+            Value::String(index) => write!(f, "@LS{}", index),
+        }
+    }
+}
+impl Display for BinOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                BinOp::Add => "add",
+                BinOp::Sub => "sub",
+                BinOp::Mul => "mul",
+                BinOp::Div => "div",
+                BinOp::Mod => "mod",
+                BinOp::And => "and",
+                BinOp::Or => "or",
+                BinOp::Xor => "xor",
+                BinOp::Shl => "shl",
+                BinOp::Sar => "sar",
+                BinOp::Eq => "eq",
+                BinOp::Ne => "ne",
+                BinOp::Lt => "lt",
+                BinOp::Le => "le",
+                BinOp::Gt => "gt",
+                BinOp::Ge => "ge",
+            }
+        )
+    }
+}
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                UnaryOp::Neg => "neg",
+                UnaryOp::BitNot => "not",
+                UnaryOp::Not => "lnot",
+            }
+        )
+    }
+}
+impl Display for Instr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instr::Copy { dest, src } => write!(f, "{}: {} = copy {}", dest, dest.type_decl, src),
+            Instr::Binary {
+                dest,
+                op,
+                left,
+                right,
+            } => write!(
+                f,
+                "{}: {} = {} {}, {}",
+                dest, dest.type_decl, op, left, right
+            ),
+            Instr::Unary { dest, op, operand } => {
+                write!(f, "{}: {} = {} {}", dest, dest.type_decl, op, operand)
+            }
+            Instr::Cast { dest, src } => write!(f, "{}: {} = cast {}", dest, dest.type_decl, src),
+            Instr::Load {
+                dest,
+                addr,
+                volatile,
+            } => write!(
+                f,
+                "{}: {} = load{} {}",
+                dest,
+                dest.type_decl,
+                if *volatile { " volatile" } else { "" },
+                addr
+            ),
+            Instr::Store { addr, value } => write!(f, "store {}, {}", value, addr),
+            Instr::Call {
+                dest,
+                callee,
+                args,
+                variadic,
+            } => {
+                let args = args
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let call = match variadic {
+                    true => "call variadic",
+                    false => "call",
+                };
+                match dest {
+                    Some(dest) => write!(
+                        f,
+                        "{}: {} = {} {}({})",
+                        dest, dest.type_decl, call, callee, args
+                    ),
+                    None => write!(f, "{} {}({})", call, callee, args),
+                }
+            }
+            Instr::VaStart { list } => write!(f, "va_start {}", list),
+            Instr::Line(line) => write!(f, "line {}", line),
+            Instr::Comment(text) => write!(f, "; {}", text),
+        }
+    }
+}
+impl Display for Terminator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jmp bb{}", target),
+            Terminator::Branch {
+                cond,
+                then_block,
+                else_block,
+            } => write!(f, "br {}, bb{}, bb{}", cond, then_block, else_block),
+            Terminator::Return(Some(value)) => write!(f, "ret {}", value),
+            Terminator::Return(None) => write!(f, "ret"),
+        }
+    }
+}
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|(type_decl, slot)| format!("{} local.{}", type_decl, slot))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let variadic = match (self.variadic, self.params.is_empty()) {
+            (true, true) => "...",
+            (true, false) => ", ...",
+            (false, _) => "",
+        };
+        writeln!(
+            f,
+            "function {}({}{}) -> {} {{",
+            self.name, params, variadic, self.return_type
+        )?;
+        for (i, type_decl) in self.slots.iter().enumerate() {
+            writeln!(f, "  local.{}: {} ; {}", i, type_decl, self.slot_names[i])?;
+        }
+        for block in self.blocks.iter() {
+            writeln!(f, "bb{}:", block.id)?;
+            for instr in block.instrs.iter() {
+                writeln!(f, "  {}", instr)?;
+            }
+            writeln!(f, "  {}", block.terminator)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+impl Display for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (data, index) in self.strings.iter() {
+            writeln!(f, "string @LS{} = \"{}\"", index, escape(data))?;
+        }
+        for global in self.globals.iter() {
+            match &global.init {
+                Some(values) => writeln!(
+                    f,
+                    "global @{}: {} = [{}]",
+                    global.name,
+                    global.type_decl,
+                    values
+                        .iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )?,
+                None => writeln!(f, "global @{}: {} = zeroed", global.name, global.type_decl)?,
+            }
+        }
+        for function in self.functions.iter() {
+            writeln!(f)?;
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use crate::ir::instr::*;
+use std::collections::{HashMap, HashSet};
+
+// virtual registers which are live at the boundaries of every basic block
+pub struct Liveness {
+    pub live_in: HashMap<BlockId, HashSet<usize>>,
+    pub live_out: HashMap<BlockId, HashSet<usize>>,
+}
+
+fn uses_and_defs(block: &BasicBlock) -> (HashSet<usize>, HashSet<usize>) {
+    let mut uses = HashSet::new();
+    let mut defs = HashSet::new();
+
+    for instr in block.instrs.iter() {
+        for reg in instr.operands().into_iter().filter_map(|v| v.as_reg()) {
+            if !defs.contains(&reg.id) {
+                uses.insert(reg.id);
+            }
+        }
+        if let Some(dest) = instr.dest() {
+            defs.insert(dest.id);
+        }
+    }
+    for reg in block
+        .terminator
+        .operands()
+        .into_iter()
+        .filter_map(|v| v.as_reg())
+    {
+        if !defs.contains(&reg.id) {
+            uses.insert(reg.id);
+        }
+    }
+    (uses, defs)
+}
+
+// classic backwards dataflow-analysis iterated until a fixpoint is reached
+pub fn liveness(function: &Function) -> Liveness {
+    let local: HashMap<BlockId, (HashSet<usize>, HashSet<usize>)> = function
+        .blocks
+        .iter()
+        .map(|b| (b.id, uses_and_defs(b)))
+        .collect();
+
+    let mut live_in: HashMap<BlockId, HashSet<usize>> = function
+        .blocks
+        .iter()
+        .map(|b| (b.id, HashSet::new()))
+        .collect();
+    let mut live_out = live_in.clone();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for block in function.blocks.iter().rev() {
+            let out: HashSet<usize> = block
+                .terminator
+                .successors()
+                .iter()
+                .filter_map(|succ| live_in.get(succ))
+                .flatten()
+                .copied()
+                .collect();
+
+            let (uses, defs) = &local[&block.id];
+            let new_in: HashSet<usize> = uses.iter().chain(out.difference(defs)).copied().collect();
+
+            if new_in != live_in[&block.id] || out != live_out[&block.id] {
+                changed = true;
+                live_in.insert(block.id, new_in);
+                live_out.insert(block.id, out);
+            }
+        }
+    }
+    Liveness { live_in, live_out }
+}
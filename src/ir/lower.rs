@@ -0,0 +1,1053 @@
+use crate::common::{environment::Environment, expr::*, stmt::*, token::*, types::*};
+use crate::fold::constant_value;
+use crate::ir::instr::*;
+use std::collections::{HashMap, HashSet};
+
+// state of the function which is currently lowered
+struct FunctionBuilder {
+    name: String,
+    line: usize,
+    return_type: NEWTypes,
+    params: Vec<(NEWTypes, usize)>,
+    variadic: bool,
+    is_static: bool,
+    slots: Vec<NEWTypes>,
+    slot_names: Vec<String>,
+    blocks: Vec<BasicBlock>,
+    // block which instructions are appended to, None after a terminator
+    current: Option<(BlockId, Vec<Instr>)>,
+    block_count: usize,
+    vreg_count: usize,
+}
+impl FunctionBuilder {
+    fn new(
+        name: String,
+        line: usize,
+        return_type: NEWTypes,
+        variadic: bool,
+        is_static: bool,
+    ) -> Self {
+        FunctionBuilder {
+            name,
+            line,
+            return_type,
+            params: Vec::new(),
+            variadic,
+            is_static,
+            slots: Vec::new(),
+            slot_names: Vec::new(),
+            blocks: Vec::new(),
+            current: Some((0, Vec::new())),
+            block_count: 1,
+            vreg_count: 0,
+        }
+    }
+    fn new_block(&mut self) -> BlockId {
+        self.block_count += 1;
+        self.block_count - 1
+    }
+    fn new_vreg(&mut self, type_decl: NEWTypes) -> VReg {
+        self.vreg_count += 1;
+        VReg {
+            id: self.vreg_count - 1,
+            type_decl: value_type(type_decl),
+        }
+    }
+    fn new_slot(&mut self, type_decl: NEWTypes, name: String) -> usize {
+        self.slots.push(type_decl);
+        self.slot_names.push(name);
+        self.slots.len() - 1
+    }
+    fn emit(&mut self, instr: Instr) {
+        // code after a terminator lives in its own block without predecessors
+        if self.current.is_none() {
+            let id = self.new_block();
+            self.current = Some((id, Vec::new()));
+        }
+        self.current.as_mut().unwrap().1.push(instr);
+    }
+    fn terminate(&mut self, terminator: Terminator) {
+        let (id, instrs) = match self.current.take() {
+            Some(current) => current,
+            None => (self.new_block(), Vec::new()),
+        };
+        self.blocks.push(BasicBlock {
+            id,
+            instrs,
+            terminator,
+        });
+    }
+    // falls through into the new block if the current one isn't terminated yet
+    fn start_block(&mut self, id: BlockId) {
+        self.jump(id);
+        self.current = Some((id, Vec::new()));
+    }
+    fn jump(&mut self, target: BlockId) {
+        if self.current.is_some() {
+            self.terminate(Terminator::Jump(target));
+        }
+    }
+    fn finish(mut self) -> Function {
+        if self.current.is_some() {
+            self.terminate(Terminator::Return(None));
+        }
+        Function {
+            name: self.name,
+            line: self.line,
+            return_type: self.return_type,
+            params: self.params,
+            variadic: self.variadic,
+            is_static: self.is_static,
+            slots: self.slots,
+            slot_names: self.slot_names,
+            blocks: self.blocks,
+            vreg_count: self.vreg_count,
+        }
+    }
+}
+
+// lowers the typechecked AST into the three-address IR
+pub struct Lowering<'a> {
+    env: Environment<Value>,
+    const_labels: &'a HashMap<String, usize>,
+    builder: Option<FunctionBuilder>,
+    globals: Vec<Global>,
+    functions: Vec<Function>,
+    // static locals get a unique symbol-name so that they don't clash with other globals
+    static_count: usize,
+    // symbols of volatile globals, locals know their qualifiers through their slot
+    volatile_globals: HashSet<String>,
+    // whether to mark the source line of each statement for debug-info
+    emit_lines: bool,
+    // whether to annotate the code with source lines and what it does
+    emit_comments: bool,
+}
+impl<'a> Lowering<'a> {
+    pub fn new(
+        const_labels: &'a HashMap<String, usize>,
+        emit_lines: bool,
+        emit_comments: bool,
+    ) -> Self {
+        Lowering {
+            env: Environment::new(None),
+            const_labels,
+            emit_lines,
+            emit_comments,
+            builder: None,
+            globals: Vec::new(),
+            functions: Vec::new(),
+            static_count: 0,
+            volatile_globals: HashSet::new(),
+        }
+    }
+    pub fn lower(mut self, statements: &Vec<Stmt>) -> Module {
+        self.lower_stmts(statements);
+
+        let mut strings: Vec<(String, usize)> = self
+            .const_labels
+            .iter()
+            .map(|(data, index)| (data.clone(), *index))
+            .collect();
+        strings.sort_by_key(|(_, index)| *index);
+
+        Module {
+            globals: self.globals,
+            functions: self.functions,
+            strings,
+        }
+    }
+    fn builder(&mut self) -> &mut FunctionBuilder {
+        self.builder
+            .as_mut()
+            .expect("typechecker only allows statements inside functions")
+    }
+    // marks the start of a statement in the source
+    fn line(&mut self, token: &Token) {
+        if self.emit_lines {
+            self.builder().emit(Instr::Line(token.line_index as usize));
+        }
+        self.comment(format!(
+            "{}: {}",
+            token.line_index,
+            token.line_string.trim()
+        ));
+    }
+    fn comment(&mut self, text: String) {
+        if self.emit_comments {
+            self.builder().emit(Instr::Comment(text));
+        }
+    }
+    fn lower_stmts(&mut self, statements: &Vec<Stmt>) {
+        for s in statements {
+            self.visit(s);
+        }
+    }
+    fn visit(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expr(expr) => {
+                if let Some(token) = expr_token(expr) {
+                    self.line(token);
+                }
+                self.rvalue(expr);
+            }
+            Stmt::DeclareVar(type_decl, name, is_global, storage_class) => {
+                self.declare_var(type_decl, name.unwrap_string(), *is_global, *storage_class)
+            }
+            Stmt::InitVar(type_decl, name, expr, is_global, storage_class) => {
+                let symbol = self.static_symbol(name.unwrap_string(), *is_global, *storage_class);
+                if symbol.is_none() {
+                    self.line(name);
+                }
+                self.init_var(
+                    type_decl,
+                    name.unwrap_string(),
+                    expr,
+                    symbol,
+                    *storage_class,
+                )
+            }
+            Stmt::InitList(type_decl, name, exprs, is_global, storage_class) => {
+                let symbol = self.static_symbol(name.unwrap_string(), *is_global, *storage_class);
+                if symbol.is_none() {
+                    self.line(name);
+                }
+                self.init_list(
+                    type_decl,
+                    name.unwrap_string(),
+                    exprs,
+                    symbol,
+                    *storage_class,
+                )
+            }
+            Stmt::Block(statements) => {
+                self.env = Environment::new(Some(Box::new(self.env.clone())));
+                self.lower_stmts(statements);
+                self.env = *self.env.enclosing.as_ref().unwrap().clone();
+            }
+            Stmt::FunctionDeclaration(_, name, ..) => self
+                .env
+                .declare_var(name.unwrap_string(), Value::Global(name.unwrap_string())),
+            Stmt::Function(return_type, name, params, variadic, storage_class, body) => {
+                self.env
+                    .declare_var(name.unwrap_string(), Value::Global(name.unwrap_string()));
+                self.function_definition(
+                    return_type,
+                    name,
+                    params,
+                    *variadic,
+                    *storage_class == Some(StorageClass::Static),
+                    body,
+                )
+            }
+            Stmt::Return(token, expr) => {
+                self.line(token);
+                let value = expr.as_ref().map(|expr| self.rvalue(expr));
+                self.builder().terminate(Terminator::Return(value));
+            }
+            Stmt::If(token, cond, then_branch, else_branch) => {
+                self.line(token);
+                self.if_statement(cond, then_branch, else_branch)
+            }
+            Stmt::While(token, cond, body) => self.while_statement(token, cond, body),
+        }
+    }
+    fn function_definition(
+        &mut self,
+        return_type: &NEWTypes,
+        name: &Token,
+        params: &[(NEWTypes, Token)],
+        variadic: bool,
+        is_static: bool,
+        body: &Vec<Stmt>,
+    ) {
+        self.builder = Some(FunctionBuilder::new(
+            name.unwrap_string(),
+            name.line_index as usize,
+            return_type.clone(),
+            variadic,
+            is_static,
+        ));
+        self.env = Environment::new(Some(Box::new(self.env.clone())));
+
+        for (type_decl, param_name) in params {
+            let slot = self
+                .builder()
+                .new_slot(type_decl.clone(), param_name.unwrap_string());
+            self.builder().params.push((type_decl.clone(), slot));
+            self.env
+                .init_var(param_name.unwrap_string(), Value::Local(slot));
+        }
+        self.lower_stmts(body);
+
+        self.env = *self.env.enclosing.as_ref().unwrap().clone();
+        let function = self.builder.take().unwrap().finish();
+        self.functions.push(function);
+    }
+    // symbol-name of variables which are stored in the data-section instead of the stack
+    fn static_symbol(
+        &mut self,
+        name: String,
+        is_global: bool,
+        storage_class: Option<StorageClass>,
+    ) -> Option<String> {
+        match (is_global, storage_class) {
+            (true, _) => Some(name),
+            (false, Some(StorageClass::Static)) => {
+                self.static_count += 1;
+                Some(format!("{}.{}", name, self.static_count - 1))
+            }
+            _ => None,
+        }
+    }
+    fn declare_var(
+        &mut self,
+        type_decl: &NEWTypes,
+        name: String,
+        is_global: bool,
+        storage_class: Option<StorageClass>,
+    ) {
+        // extern variables are defined somewhere else and only resolved by the linker
+        if storage_class == Some(StorageClass::Extern) {
+            let var = self.global_value(name.clone(), type_decl);
+            self.env.declare_var(name, var);
+            return;
+        }
+        let var = match self.static_symbol(name.clone(), is_global, storage_class) {
+            Some(symbol) => {
+                self.define_global(Global {
+                    name: symbol.clone(),
+                    type_decl: type_decl.clone(),
+                    init: None,
+                    is_static: storage_class == Some(StorageClass::Static),
+                });
+                self.global_value(symbol, type_decl)
+            }
+            None => Value::Local(self.builder().new_slot(type_decl.clone(), name.clone())),
+        };
+        self.env.declare_var(name, var);
+    }
+    fn global_value(&mut self, symbol: String, type_decl: &NEWTypes) -> Value {
+        if type_decl.qualifiers().is_volatile {
+            self.volatile_globals.insert(symbol.clone());
+        }
+        Value::Global(symbol)
+    }
+    // tentative definitions of the same global all refer to a single variable, the one with
+    // an initializer wins
+    fn define_global(&mut self, global: Global) {
+        match self.globals.iter_mut().find(|g| g.name == global.name) {
+            Some(existing) => {
+                if global.init.is_some() {
+                    *existing = global;
+                }
+            }
+            None => self.globals.push(global),
+        }
+    }
+    fn init_var(
+        &mut self,
+        type_decl: &NEWTypes,
+        name: String,
+        expr: &Expr,
+        symbol: Option<String>,
+        storage_class: Option<StorageClass>,
+    ) {
+        if let Some(symbol) = symbol {
+            let init = match string_initializer(expr) {
+                Some(data) if matches!(type_decl, NEWTypes::Array { .. }) => {
+                    string_bytes(&data, type_decl.size())
+                }
+                _ => vec![const_value(expr, self.const_labels)],
+            };
+            self.define_global(Global {
+                name: symbol.clone(),
+                type_decl: type_decl.clone(),
+                init: Some(init),
+                is_static: storage_class == Some(StorageClass::Static),
+            });
+            let var = self.global_value(symbol, type_decl);
+            self.env.declare_var(name, var);
+            return;
+        }
+
+        // char s[n] = "literal" copies the string into the array
+        if let (NEWTypes::Array { .. }, Some(data)) = (type_decl, string_initializer(expr)) {
+            let slot = self.builder().new_slot(type_decl.clone(), name.clone());
+            for (i, byte) in string_bytes(&data, type_decl.size())
+                .into_iter()
+                .enumerate()
+            {
+                let addr = self.element_addr(Value::Local(slot), i);
+                self.builder().emit(Instr::Store { addr, value: byte });
+            }
+            self.env.declare_var(name, Value::Local(slot));
+            return;
+        }
+
+        let value = self.rvalue(expr);
+        let slot = self.builder().new_slot(type_decl.clone(), name.clone());
+        self.builder().emit(Instr::Store {
+            addr: Value::Local(slot),
+            value,
+        });
+        self.env.declare_var(name, Value::Local(slot));
+    }
+    fn init_list(
+        &mut self,
+        type_decl: &NEWTypes,
+        name: String,
+        exprs: &[Expr],
+        symbol: Option<String>,
+        storage_class: Option<StorageClass>,
+    ) {
+        if let Some(symbol) = symbol {
+            let init = exprs
+                .iter()
+                .map(|e| const_value(e, self.const_labels))
+                .collect();
+            self.define_global(Global {
+                name: symbol.clone(),
+                type_decl: type_decl.clone(),
+                init: Some(init),
+                is_static: storage_class == Some(StorageClass::Static),
+            });
+            let var = self.global_value(symbol, type_decl);
+            self.env.declare_var(name, var);
+        } else {
+            self.declare_var(type_decl, name, false, None);
+
+            // init-list is assignment syntax sugar
+            for e in exprs {
+                self.rvalue(e);
+            }
+        }
+    }
+    fn if_statement(&mut self, cond: &Expr, then_branch: &Stmt, else_branch: &Option<Stmt>) {
+        let cond = self.rvalue(cond);
+
+        let then_block = self.builder().new_block();
+        let done_block = self.builder().new_block();
+        let else_block = match else_branch {
+            Some(_) => self.builder().new_block(),
+            None => done_block,
+        };
+        self.builder().terminate(Terminator::Branch {
+            cond,
+            then_block,
+            else_block,
+        });
+
+        self.builder().start_block(then_block);
+        self.visit(then_branch);
+        self.builder().jump(done_block);
+
+        if let Some(else_branch) = else_branch {
+            self.builder().start_block(else_block);
+            self.visit(else_branch);
+        }
+        self.builder().start_block(done_block);
+    }
+    fn while_statement(&mut self, token: &Token, cond: &Expr, body: &Stmt) {
+        let cond_block = self.builder().new_block();
+        let body_block = self.builder().new_block();
+        let done_block = self.builder().new_block();
+
+        self.builder().start_block(cond_block);
+        self.line(token);
+        let cond = self.rvalue(cond);
+        self.builder().terminate(Terminator::Branch {
+            cond,
+            then_block: body_block,
+            else_block: done_block,
+        });
+
+        self.builder().start_block(body_block);
+        self.visit(body);
+        self.builder().jump(cond_block);
+
+        self.builder().start_block(done_block);
+    }
+
+    // returns the address of an lvalue-expression
+    fn lvalue(&mut self, ast: &Expr) -> Value {
+        match &ast.kind {
+            // implicitly declared functions are only resolved by the linker
+            ExprKind::Ident(name) => self
+                .env
+                .get_var(name)
+                .unwrap_or_else(|_| Value::Global(name.unwrap_string())),
+            ExprKind::Unary { token, right } if token.token == TokenType::Star => {
+                self.rvalue(right)
+            }
+            ExprKind::Grouping { expr }
+            | ExprKind::CastUp { expr }
+            | ExprKind::CastDown { expr } => self.lvalue(expr),
+            ExprKind::String(token) => Value::String(self.const_labels[&token.unwrap_string()]),
+            _ => unreachable!("typechecker only allows lvalues here: {}", ast.kind),
+        }
+    }
+    // returns the value an expression evaluates to
+    fn rvalue(&mut self, ast: &Expr) -> Value {
+        // implicit conversions of constants aren't folded by the typechecker
+        if let Some(n) = constant_value(ast) {
+            return Value::Const(n, expr_type(ast));
+        }
+        match &ast.kind {
+            ExprKind::Number(n) => Value::Const(*n, expr_type(ast)),
+            ExprKind::CharLit(c) => Value::Const(*c as i64, NEWTypes::Primitive(Types::Char)),
+            ExprKind::String(token) => Value::String(self.const_labels[&token.unwrap_string()]),
+            ExprKind::Ident(_) => {
+                let addr = self.lvalue(ast);
+                let volatile = self.is_volatile(ast);
+                self.load(addr, expr_type(ast), volatile)
+            }
+            ExprKind::Grouping { expr } => self.rvalue(expr),
+            ExprKind::Unary { token, right } => self.unary(ast, token, right),
+            ExprKind::Binary { left, token, right } => {
+                let left = self.rvalue(left);
+                let right = self.rvalue(right);
+                self.binary(expr_type(ast), bin_op(&token.token), left, right)
+            }
+            ExprKind::Logical { left, token, right } => self.logical(left, token, right),
+            ExprKind::Assign { l_expr, r_expr, .. } => {
+                let addr = self.lvalue(l_expr);
+                let value = self.rvalue(r_expr);
+                self.builder().emit(Instr::Store {
+                    addr,
+                    value: value.clone(),
+                });
+                value
+            }
+            ExprKind::CompoundAssign {
+                l_expr,
+                token,
+                r_expr,
+            } => self.compound_assign(l_expr, token, r_expr),
+            ExprKind::PostUnary {
+                token,
+                left,
+                by_amount,
+            } => self.postunary(token, left, *by_amount),
+            ExprKind::Call { callee, args, .. } => self.call(ast, callee, args),
+            ExprKind::Builtin { token, args, .. } => self.builtin(ast, token, args),
+            ExprKind::CastUp { expr } | ExprKind::CastDown { expr } => {
+                let value = self.rvalue(expr);
+                self.cast(value, expr_type(ast))
+            }
+            ExprKind::ScaleUp { by, expr } => {
+                let value = self.rvalue(expr);
+                self.comment(format!("scale up by {}", by));
+                let type_decl = expr_type(expr);
+                self.binary(
+                    type_decl.clone(),
+                    BinOp::Mul,
+                    value,
+                    Value::Const(*by as i64, type_decl),
+                )
+            }
+            ExprKind::ScaleDown { shift_amount, expr } => {
+                let value = self.rvalue(expr);
+                self.comment(format!("scale down by {}", 1 << shift_amount));
+                let type_decl = expr_type(ast);
+                self.binary(
+                    type_decl.clone(),
+                    BinOp::Sar,
+                    value,
+                    Value::Const(*shift_amount as i64, type_decl),
+                )
+            }
+        }
+    }
+    fn load(&mut self, addr: Value, type_decl: NEWTypes, volatile: bool) -> Value {
+        // arrays and functions decay into their address
+        if matches!(
+            type_decl,
+            NEWTypes::Array { .. } | NEWTypes::Function { .. }
+        ) {
+            return addr;
+        }
+        let dest = self.builder().new_vreg(type_decl);
+        self.builder().emit(Instr::Load {
+            dest: dest.clone(),
+            addr,
+            volatile,
+        });
+        Value::Reg(dest)
+    }
+    // whether the object an lvalue refers to is volatile-qualified
+    fn is_volatile(&mut self, ast: &Expr) -> bool {
+        match &ast.kind {
+            ExprKind::Ident(name) => match self.env.get_var(name) {
+                Ok(Value::Local(slot)) => self.builder().slots[slot].qualifiers().is_volatile,
+                Ok(Value::Global(symbol)) => self.volatile_globals.contains(&symbol),
+                _ => false,
+            },
+            ExprKind::Unary { token, right } if token.token == TokenType::Star => {
+                match expr_type(right) {
+                    NEWTypes::Pointer(to) | NEWTypes::Array { of: to, .. } => {
+                        to.qualifiers().is_volatile
+                    }
+                    _ => false,
+                }
+            }
+            ExprKind::Grouping { expr } => self.is_volatile(expr),
+            _ => false,
+        }
+    }
+    fn binary(&mut self, type_decl: NEWTypes, op: BinOp, left: Value, right: Value) -> Value {
+        let dest = self.builder().new_vreg(type_decl);
+        self.builder().emit(Instr::Binary {
+            dest: dest.clone(),
+            op,
+            left,
+            right,
+        });
+        Value::Reg(dest)
+    }
+    fn cast(&mut self, value: Value, type_decl: NEWTypes) -> Value {
+        let dest = self.builder().new_vreg(type_decl);
+        self.builder().emit(Instr::Cast {
+            dest: dest.clone(),
+            src: value,
+        });
+        Value::Reg(dest)
+    }
+    // casts value to type if sizes differ
+    fn coerce(&mut self, value: Value, from: &NEWTypes, to: &NEWTypes) -> Value {
+        if from.size() != to.size() {
+            self.cast(value, to.clone())
+        } else {
+            value
+        }
+    }
+    fn element_addr(&mut self, base: Value, offset: usize) -> Value {
+        if offset == 0 {
+            return base;
+        }
+        let char_ptr = NEWTypes::Pointer(Box::new(NEWTypes::Primitive(Types::Char)));
+        self.binary(
+            char_ptr,
+            BinOp::Add,
+            base,
+            Value::Const(offset as i64, NEWTypes::Primitive(Types::Long)),
+        )
+    }
+    fn unary(&mut self, ast: &Expr, token: &Token, right: &Expr) -> Value {
+        let op = match token.token {
+            TokenType::Amp => return self.lvalue(right),
+            TokenType::Star => {
+                let addr = self.rvalue(right);
+                let volatile = self.is_volatile(ast);
+                return self.load(addr, expr_type(ast), volatile);
+            }
+            TokenType::Minus => UnaryOp::Neg,
+            TokenType::Tilde => UnaryOp::BitNot,
+            TokenType::Bang => UnaryOp::Not,
+            _ => unreachable!(),
+        };
+        let operand = self.rvalue(right);
+        let dest = self.builder().new_vreg(expr_type(ast));
+        self.builder().emit(Instr::Unary {
+            dest: dest.clone(),
+            op,
+            operand,
+        });
+        Value::Reg(dest)
+    }
+    fn builtin(&mut self, ast: &Expr, token: &Token, args: &[Expr]) -> Value {
+        let list = self.rvalue(&args[0]);
+        match token.token {
+            TokenType::VaStart => self.builder().emit(Instr::VaStart { list }),
+            TokenType::VaArg => return self.va_arg(list, expr_type(ast)),
+            _ => (),
+        }
+        Value::Const(0, NEWTypes::Primitive(Types::Void))
+    }
+    // reads the next argument from the register-save-area while there are
+    // general-purpose registers left, otherwise from the overflow-area on the stack
+    fn va_arg(&mut self, list: Value, type_decl: NEWTypes) -> Value {
+        let int = NEWTypes::Primitive(Types::Int);
+        let char_ptr = NEWTypes::Pointer(Box::new(NEWTypes::Primitive(Types::Char)));
+        let addr = self.builder().new_vreg(char_ptr.clone());
+        let reg_block = self.builder().new_block();
+        let stack_block = self.builder().new_block();
+        let done_block = self.builder().new_block();
+
+        let gp_offset = self.load(list.clone(), int.clone(), false);
+        let cond = self.binary(
+            int.clone(),
+            BinOp::Lt,
+            gp_offset.clone(),
+            Value::Const(48, int.clone()),
+        );
+        self.builder().terminate(Terminator::Branch {
+            cond,
+            then_block: reg_block,
+            else_block: stack_block,
+        });
+
+        self.builder().start_block(reg_block);
+        let save_area_addr = self.element_addr(list.clone(), 16);
+        let save_area = self.load(save_area_addr, char_ptr.clone(), false);
+        let offset = self.cast(gp_offset.clone(), NEWTypes::Primitive(Types::Long));
+        let reg_addr = self.binary(char_ptr.clone(), BinOp::Add, save_area, offset);
+        self.builder().emit(Instr::Copy {
+            dest: addr.clone(),
+            src: reg_addr,
+        });
+        let next = self.binary(int.clone(), BinOp::Add, gp_offset, Value::Const(8, int));
+        self.builder().emit(Instr::Store {
+            addr: list.clone(),
+            value: next,
+        });
+        self.builder().terminate(Terminator::Jump(done_block));
+
+        // every argument takes up a full eightbyte on the stack
+        self.builder().start_block(stack_block);
+        let overflow_addr = self.element_addr(list, 8);
+        let overflow_area = self.load(overflow_addr.clone(), char_ptr.clone(), false);
+        self.builder().emit(Instr::Copy {
+            dest: addr.clone(),
+            src: overflow_area.clone(),
+        });
+        let next = self.binary(
+            char_ptr,
+            BinOp::Add,
+            overflow_area,
+            Value::Const(8, NEWTypes::Primitive(Types::Long)),
+        );
+        self.builder().emit(Instr::Store {
+            addr: overflow_addr,
+            value: next,
+        });
+        self.builder().start_block(done_block);
+
+        self.load(Value::Reg(addr), type_decl, false)
+    }
+    fn logical(&mut self, left: &Expr, token: &Token, right: &Expr) -> Value {
+        let result = self.builder().new_vreg(NEWTypes::Primitive(Types::Int));
+        let right_block = self.builder().new_block();
+        let true_block = self.builder().new_block();
+        let false_block = self.builder().new_block();
+        let done_block = self.builder().new_block();
+
+        // short circuit if the left side already decides the result
+        let left = self.rvalue(left);
+        self.builder().terminate(match token.token {
+            TokenType::AmpAmp => Terminator::Branch {
+                cond: left,
+                then_block: right_block,
+                else_block: false_block,
+            },
+            TokenType::PipePipe => Terminator::Branch {
+                cond: left,
+                then_block: true_block,
+                else_block: right_block,
+            },
+            _ => unreachable!(),
+        });
+
+        self.builder().start_block(right_block);
+        let right = self.rvalue(right);
+        self.builder().terminate(Terminator::Branch {
+            cond: right,
+            then_block: true_block,
+            else_block: false_block,
+        });
+
+        for (block, value) in [(true_block, 1), (false_block, 0)] {
+            self.builder().start_block(block);
+            self.builder().emit(Instr::Copy {
+                dest: result.clone(),
+                src: Value::Const(value, NEWTypes::Primitive(Types::Int)),
+            });
+            self.builder().terminate(Terminator::Jump(done_block));
+        }
+        self.builder().start_block(done_block);
+
+        Value::Reg(result)
+    }
+    fn compound_assign(&mut self, l_expr: &Expr, token: &Token, r_expr: &Expr) -> Value {
+        let l_type = expr_type(l_expr);
+        let r_type = expr_type(r_expr);
+
+        let addr = self.lvalue(l_expr);
+        let volatile = self.is_volatile(l_expr);
+        let old = self.load(addr.clone(), l_type.clone(), volatile);
+        let right = self.rvalue(r_expr);
+
+        // have to do integer-promotion since the binary-operation happens in at least int
+        let op_type = if l_type.is_ptr() {
+            l_type.clone()
+        } else {
+            [&l_type, &r_type, &NEWTypes::Primitive(Types::Int)]
+                .into_iter()
+                .max_by_key(|t| t.size())
+                .unwrap()
+                .clone()
+        };
+        let left = self.coerce(old, &l_type, &op_type);
+        let right = self.coerce(right, &r_type, &op_type);
+        let result = self.binary(
+            op_type.clone(),
+            bin_op(&token.comp_to_binary()),
+            left,
+            right,
+        );
+        let result = self.coerce(result, &op_type, &l_type);
+
+        self.builder().emit(Instr::Store {
+            addr,
+            value: result.clone(),
+        });
+        result
+    }
+    fn postunary(&mut self, token: &Token, left: &Expr, by_amount: usize) -> Value {
+        let type_decl = expr_type(left);
+        let addr = self.lvalue(left);
+        let volatile = self.is_volatile(left);
+        let old = self.load(addr.clone(), type_decl.clone(), volatile);
+
+        let op = match token.token {
+            TokenType::PlusPlus => BinOp::Add,
+            TokenType::MinusMinus => BinOp::Sub,
+            _ => unreachable!(),
+        };
+        let amount_type = match type_decl.is_ptr() {
+            true => NEWTypes::Primitive(Types::Long),
+            false => type_decl.clone(),
+        };
+        let new = self.binary(
+            type_decl,
+            op,
+            old.clone(),
+            Value::Const(by_amount as i64, amount_type),
+        );
+        self.builder().emit(Instr::Store { addr, value: new });
+
+        old
+    }
+    fn call(&mut self, ast: &Expr, callee: &Expr, args: &[Expr]) -> Value {
+        // a function designator already is the address of the function
+        let (callee, variadic) = match expr_type(callee) {
+            NEWTypes::Function { variadic, .. } => (self.lvalue(callee), variadic),
+            NEWTypes::Pointer(to) => match *to {
+                NEWTypes::Function { variadic, .. } => (self.rvalue(callee), variadic),
+                _ => unreachable!("typechecker only allows calling functions"),
+            },
+            _ => unreachable!("typechecker only allows calling functions"),
+        };
+        let args = args.iter().map(|arg| self.rvalue(arg)).collect();
+
+        let return_type = expr_type(ast);
+        let dest = match return_type.is_void() {
+            true => None,
+            false => Some(self.builder().new_vreg(return_type.clone())),
+        };
+        self.builder().emit(Instr::Call {
+            dest: dest.clone(),
+            callee,
+            args,
+            variadic,
+        });
+
+        match dest {
+            Some(dest) => Value::Reg(dest),
+            // void result can't be used, typechecker guarantees that
+            None => Value::Const(0, return_type),
+        }
+    }
+}
+
+fn expr_type(ast: &Expr) -> NEWTypes {
+    match (&ast.type_decl, &ast.kind) {
+        (Some(type_decl), _) => type_decl.clone(),
+        // inner expression of scale-down (ptr - ptr) isn't annotated by typechecker
+        (None, ExprKind::Binary { .. }) => NEWTypes::Primitive(Types::Long),
+        _ => unreachable!("typechecker annotates every expression"),
+    }
+}
+
+// arrays can only be held by registers as pointers to their first element
+fn value_type(type_decl: NEWTypes) -> NEWTypes {
+    match type_decl {
+        NEWTypes::Array { of, .. } => NEWTypes::Pointer(of),
+        NEWTypes::Qualified(_, inner) => value_type(*inner),
+        _ => type_decl,
+    }
+}
+
+fn bin_op(token: &TokenType) -> BinOp {
+    match token {
+        TokenType::Plus => BinOp::Add,
+        TokenType::Minus => BinOp::Sub,
+        TokenType::Star => BinOp::Mul,
+        TokenType::Slash => BinOp::Div,
+        TokenType::Mod => BinOp::Mod,
+        TokenType::Amp => BinOp::And,
+        TokenType::Pipe => BinOp::Or,
+        TokenType::Xor => BinOp::Xor,
+        TokenType::LessLess => BinOp::Shl,
+        TokenType::GreaterGreater => BinOp::Sar,
+        TokenType::EqualEqual => BinOp::Eq,
+        TokenType::BangEqual => BinOp::Ne,
+        TokenType::Less => BinOp::Lt,
+        TokenType::LessEqual => BinOp::Le,
+        TokenType::Greater => BinOp::Gt,
+        TokenType::GreaterEqual => BinOp::Ge,
+        _ => unreachable!("not a binary operator"),
+    }
+}
+
+// returns the string-literal an array gets initialized with
+fn string_initializer(expr: &Expr) -> Option<String> {
+    match &expr.kind {
+        ExprKind::String(token) => Some(token.unwrap_string()),
+        ExprKind::Unary { right: expr, .. }
+        | ExprKind::CastUp { expr }
+        | ExprKind::CastDown { expr }
+        | ExprKind::Grouping { expr } => string_initializer(expr),
+        _ => None,
+    }
+}
+// leftmost token in the expression, literals don't carry a token
+fn expr_token(expr: &Expr) -> Option<&Token> {
+    match &expr.kind {
+        ExprKind::Binary { left, token, .. } | ExprKind::Logical { left, token, .. } => {
+            expr_token(left).or(Some(token))
+        }
+        ExprKind::Assign { l_expr, token, .. } | ExprKind::CompoundAssign { l_expr, token, .. } => {
+            expr_token(l_expr).or(Some(token))
+        }
+        ExprKind::Call {
+            left_paren, callee, ..
+        } => expr_token(callee).or(Some(left_paren)),
+        ExprKind::PostUnary { left, token, .. } => expr_token(left).or(Some(token)),
+        ExprKind::Unary { token, .. }
+        | ExprKind::Builtin { token, .. }
+        | ExprKind::String(token)
+        | ExprKind::Ident(token) => Some(token),
+        ExprKind::Grouping { expr }
+        | ExprKind::CastUp { expr }
+        | ExprKind::CastDown { expr }
+        | ExprKind::ScaleUp { expr, .. }
+        | ExprKind::ScaleDown { expr, .. } => expr_token(expr),
+        ExprKind::Number(_) | ExprKind::CharLit(_) => None,
+    }
+}
+fn string_bytes(data: &str, size: usize) -> Vec<Value> {
+    let mut bytes: Vec<Value> = data
+        .chars()
+        .take(size)
+        .map(|b| Value::Const(b as u8 as i8 as i64, NEWTypes::Primitive(Types::Char)))
+        .collect();
+    // fill rest with 0's, also adds the null-terminator
+    bytes.resize(size, Value::Const(0, NEWTypes::Primitive(Types::Char)));
+    bytes
+}
+
+// evaluates an expression the typechecker marked as compile-time constant
+fn const_value(expr: &Expr, const_labels: &HashMap<String, usize>) -> Value {
+    if let Some(n) = constant_value(expr) {
+        return Value::Const(n, expr_type(expr));
+    }
+    match &expr.kind {
+        ExprKind::String(token) => Value::String(const_labels[&token.unwrap_string()]),
+        ExprKind::CastUp { expr } | ExprKind::CastDown { expr } => const_value(expr, const_labels),
+        ExprKind::Assign { r_expr, .. } => const_value(r_expr, const_labels),
+        ExprKind::Unary { right, .. } => match &right.kind {
+            ExprKind::Ident(name) => Value::Global(name.unwrap_string()),
+            _ => unreachable!("only the address of a function is constant"),
+        },
+        _ => unreachable!("typechecker only allows constant global initializers"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::lower;
+
+    fn lower_source(source: &str) -> String {
+        lower(source).to_string()
+    }
+
+    #[test]
+    fn lowers_if_else_into_blocks() {
+        let actual =
+            lower_source("int main() {\nint a = 1;\nif (a) a = 2; else a = 3;\nreturn a;\n}");
+        let expected = "
+function main() -> int {
+  local.0: int ; a
+bb0:
+  store 1, local.0
+  %0: int = load local.0
+  br %0, bb1, bb3
+bb1:
+  store 2, local.0
+  jmp bb2
+bb3:
+  store 3, local.0
+  jmp bb2
+bb2:
+  %1: int = load local.0
+  ret %1
+}
+";
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn promotes_variadic_call_arguments() {
+        let actual = lower_source(
+            "int printf(char *format, ...);\nint main() {\nchar c = 'a';\nprintf(\"%c\", c);\n}",
+        );
+        let expected = "string @LS0 = \"%c\"
+
+function main() -> int {
+  local.0: char ; c
+bb0:
+  store 97, local.0
+  %0: char = load local.0
+  %1: int = cast %0
+  %2: int = call variadic @printf(@LS0, %1)
+  ret 0
+}
+";
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn lowers_while_and_short_circuit() {
+        let actual =
+            lower_source("int main() {\nint i = 0;\nwhile (i < 3) i++;\nreturn i && 1;\n}");
+        let expected = "
+function main() -> int {
+  local.0: int ; i
+bb0:
+  store 0, local.0
+  jmp bb1
+bb1:
+  %0: int = load local.0
+  %1: int = lt %0, 3
+  br %1, bb2, bb3
+bb2:
+  %2: int = load local.0
+  %3: int = add %2, 1
+  store %3, local.0
+  jmp bb1
+bb3:
+  %5: int = load local.0
+  br %5, bb4, bb6
+bb4:
+  br 1, bb5, bb6
+bb5:
+  %4: int = copy 1
+  jmp bb7
+bb6:
+  %4: int = copy 0
+  jmp bb7
+bb7:
+  ret %4
+}
+";
+        assert_eq!(actual, expected);
+    }
+}
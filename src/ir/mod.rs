@@ -0,0 +1,5 @@
+pub mod dot;
+pub mod instr;
+pub mod liveness;
+pub mod lower;
+pub mod opt;
@@ -0,0 +1,64 @@
+use crate::common::types::*;
+use crate::ir::instr::*;
+use crate::ir::opt::*;
+use std::collections::HashMap;
+
+// replaces uses of copies with their source, also forwards values stored into
+// stack-slots to following loads of that slot in the same block
+pub fn propagate_copies(function: &mut Function) -> bool {
+    let defs = def_counts(function);
+    let escaping = escaping_slots(function);
+    let mut copies: HashMap<usize, Value> = HashMap::new();
+
+    for block in function.blocks.iter() {
+        // value each stack-slot is known to hold
+        let mut slot_values: HashMap<usize, Value> = HashMap::new();
+
+        for instr in block.instrs.iter() {
+            match instr {
+                Instr::Copy { dest, src } if defs[&dest.id] == 1 && is_stable(src, &defs) => {
+                    copies.insert(dest.id, retype(src, &dest.type_decl));
+                }
+                Instr::Load {
+                    dest,
+                    addr: Value::Local(slot),
+                    volatile: false,
+                } if defs[&dest.id] == 1 => {
+                    if let Some(value) = slot_values.get(slot) {
+                        if value_size(value) == dest.type_decl.size() {
+                            copies.insert(dest.id, retype(value, &dest.type_decl));
+                        }
+                    }
+                }
+                Instr::Store {
+                    addr: Value::Local(slot),
+                    value,
+                } if !escaping.contains(slot) => {
+                    if is_stable(value, &defs) {
+                        slot_values.insert(*slot, value.clone());
+                    } else {
+                        slot_values.remove(slot);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    replace_uses(function, &copies)
+}
+
+fn retype(value: &Value, type_decl: &NEWTypes) -> Value {
+    match value {
+        Value::Const(n, _) => Value::Const(*n, type_decl.clone()),
+        _ => value.clone(),
+    }
+}
+
+// size of the value that gets stored
+fn value_size(value: &Value) -> usize {
+    match value {
+        Value::Reg(reg) => reg.type_decl.size(),
+        Value::Const(_, type_decl) => type_decl.size(),
+        Value::Local(_) | Value::Global(_) | Value::String(_) => 8,
+    }
+}
@@ -0,0 +1,84 @@
+use crate::ir::instr::*;
+use crate::ir::opt::*;
+use std::collections::HashMap;
+
+// reuses the result of an earlier identical computation in the same block
+pub fn eliminate_common_subexpressions(function: &mut Function) -> bool {
+    let defs = def_counts(function);
+    let escaping = escaping_slots(function);
+    let mut replacements: HashMap<usize, Value> = HashMap::new();
+
+    for block in function.blocks.iter_mut() {
+        // computations available at the current instruction and the register holding their result
+        let mut available: Vec<(Instr, VReg)> = Vec::new();
+
+        for instr in block.instrs.iter_mut() {
+            // operands have to be up to date so that equal computations also look equal
+            for operand in instr.operands_mut() {
+                if let Some(value) = operand.as_reg().and_then(|reg| replacements.get(&reg.id)) {
+                    *operand = value.clone();
+                }
+            }
+
+            match instr {
+                Instr::Store { addr, .. } => {
+                    // store can only alias loads from the same slot if the slot doesn't escape
+                    available.retain(|(instr, _)| match instr {
+                        Instr::Load {
+                            addr: Value::Local(slot),
+                            ..
+                        } => !escaping.contains(slot) && *addr != Value::Local(*slot),
+                        Instr::Load { .. } => false,
+                        _ => true,
+                    });
+                }
+                Instr::Call { .. } | Instr::VaStart { .. } => {
+                    available.retain(|(instr, _)| match instr {
+                        Instr::Load {
+                            addr: Value::Local(slot),
+                            ..
+                        } => !escaping.contains(slot),
+                        Instr::Load { .. } => false,
+                        _ => true,
+                    })
+                }
+                Instr::Copy { .. }
+                | Instr::Load { volatile: true, .. }
+                | Instr::Line(_)
+                | Instr::Comment(_) => (),
+                _ => {
+                    let dest = instr.dest().unwrap().clone();
+                    if defs[&dest.id] != 1 || !instr.operands().iter().all(|v| is_stable(v, &defs))
+                    {
+                        continue;
+                    }
+                    let key = with_dest(instr, &dest);
+                    match available.iter().find(|(other, _)| *other == key) {
+                        Some((_, reg)) => {
+                            replacements.insert(dest.id, Value::Reg(reg.clone()));
+                        }
+                        None => available.push((key, dest)),
+                    }
+                }
+            }
+        }
+    }
+    replace_uses(function, &replacements)
+}
+
+// instructions are compared without their destination but with its type
+fn with_dest(instr: &Instr, dest: &VReg) -> Instr {
+    let mut key = instr.clone();
+    let placeholder = VReg {
+        id: usize::MAX,
+        type_decl: dest.type_decl.clone(),
+    };
+    match &mut key {
+        Instr::Binary { dest, .. }
+        | Instr::Unary { dest, .. }
+        | Instr::Cast { dest, .. }
+        | Instr::Load { dest, .. } => *dest = placeholder,
+        _ => unreachable!("only pure instructions are keys"),
+    }
+    key
+}
@@ -0,0 +1,72 @@
+use crate::ir::instr::*;
+use std::collections::HashSet;
+
+// removes blocks which can't be reached from the entry, like code following a return
+pub fn remove_unreachable_blocks(function: &mut Function) -> bool {
+    let mut changed = false;
+
+    // branches on a constant always take the same edge
+    for block in function.blocks.iter_mut() {
+        if let Terminator::Branch {
+            cond: Value::Const(n, _),
+            then_block,
+            else_block,
+        } = block.terminator
+        {
+            block.terminator = Terminator::Jump(if n != 0 { then_block } else { else_block });
+            changed = true;
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    let mut worklist = vec![function.blocks[0].id];
+    while let Some(id) = worklist.pop() {
+        if reachable.insert(id) {
+            let block = function.blocks.iter().find(|b| b.id == id).unwrap();
+            worklist.extend(block.terminator.successors());
+        }
+    }
+
+    let block_count = function.blocks.len();
+    function.blocks.retain(|b| reachable.contains(&b.id));
+
+    changed || block_count != function.blocks.len()
+}
+
+// removes instructions whose result is never used and which have no side-effects
+pub fn eliminate_dead_code(function: &mut Function) -> bool {
+    let mut changed = false;
+
+    // removing an instruction can make its operands dead too
+    loop {
+        let mut used = HashSet::new();
+        for block in function.blocks.iter() {
+            let operands = block
+                .instrs
+                .iter()
+                .flat_map(|instr| instr.operands())
+                .chain(block.terminator.operands());
+            used.extend(operands.filter_map(|v| v.as_reg()).map(|reg| reg.id));
+        }
+
+        let mut removed = false;
+        for block in function.blocks.iter_mut() {
+            let instr_count = block.instrs.len();
+            block.instrs.retain(|instr| match instr {
+                Instr::Store { .. }
+                | Instr::Load { volatile: true, .. }
+                | Instr::Call { .. }
+                | Instr::VaStart { .. }
+                | Instr::Line(_)
+                | Instr::Comment(_) => true,
+                _ => instr.dest().is_some_and(|dest| used.contains(&dest.id)),
+            });
+            removed |= instr_count != block.instrs.len();
+        }
+
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
@@ -0,0 +1,198 @@
+use crate::ir::instr::*;
+use crate::ir::opt::*;
+use std::collections::{HashMap, HashSet};
+
+// moves computations which produce the same value in every loop-iteration in front of the loop
+pub fn hoist_loop_invariants(function: &mut Function) -> bool {
+    let dominators = dominators(function);
+    let predecessors = predecessors(function);
+    let mut changed = false;
+
+    // a loop is formed by an edge to a block which dominates the edge's source
+    let mut back_edges = Vec::new();
+    for block in function.blocks.iter() {
+        for succ in block.terminator.successors() {
+            if dominators[&block.id].contains(&succ) {
+                back_edges.push((block.id, succ));
+            }
+        }
+    }
+
+    for (tail, header) in back_edges {
+        let body = loop_body(tail, header, &predecessors);
+
+        // invariants can only be hoisted into a single block jumping into the loop
+        let preheader = match predecessors[&header]
+            .iter()
+            .filter(|p| !body.contains(p))
+            .collect::<Vec<_>>()[..]
+        {
+            [preheader] => *preheader,
+            _ => continue,
+        };
+        let preheader_index = function
+            .blocks
+            .iter()
+            .position(|b| b.id == preheader)
+            .unwrap();
+        if function.blocks[preheader_index].terminator != Terminator::Jump(header) {
+            continue;
+        }
+
+        let hoisted = find_invariants(function, &body);
+        if hoisted.is_empty() {
+            continue;
+        }
+        changed = true;
+
+        let mut moved = Vec::new();
+        for block in function.blocks.iter_mut().filter(|b| body.contains(&b.id)) {
+            let (invariant, rest) = block.instrs.drain(..).partition(|instr: &Instr| {
+                instr.dest().is_some_and(|dest| hoisted.contains(&dest.id))
+            });
+            moved.extend(invariant);
+            block.instrs = rest;
+        }
+        // invariants are sorted by their position in the loop so operands are defined first
+        moved.sort_by_key(|instr: &Instr| {
+            hoisted
+                .iter()
+                .position(|id| Some(id) == instr.dest().map(|dest| &dest.id))
+        });
+        function.blocks[preheader_index].instrs.extend(moved);
+    }
+    changed
+}
+
+// returns the destinations of loop-invariant instructions in the order they were found
+fn find_invariants(function: &Function, body: &HashSet<BlockId>) -> Vec<usize> {
+    let defs = def_counts(function);
+    let escaping = escaping_slots(function);
+
+    let loop_blocks: Vec<&BasicBlock> = function
+        .blocks
+        .iter()
+        .filter(|b| body.contains(&b.id))
+        .collect();
+    let defined_in_loop: HashSet<usize> = loop_blocks
+        .iter()
+        .flat_map(|b| b.instrs.iter().filter_map(|instr| instr.dest()))
+        .map(|dest| dest.id)
+        .collect();
+    let stored_in_loop: HashSet<usize> = loop_blocks
+        .iter()
+        .flat_map(|b| b.instrs.iter())
+        .filter_map(|instr| match instr {
+            Instr::Store {
+                addr: Value::Local(slot),
+                ..
+            } => Some(*slot),
+            _ => None,
+        })
+        .collect();
+
+    let mut hoisted: Vec<usize> = Vec::new();
+    let mut found = true;
+    while found {
+        found = false;
+
+        for instr in loop_blocks.iter().flat_map(|b| b.instrs.iter()) {
+            let is_pure = match instr {
+                Instr::Load {
+                    addr: Value::Local(slot),
+                    volatile: false,
+                    ..
+                } => !escaping.contains(slot) && !stored_in_loop.contains(slot),
+                // division could trap if the loop never executes
+                Instr::Binary {
+                    op: BinOp::Div | BinOp::Mod,
+                    ..
+                } => false,
+                Instr::Binary { .. } | Instr::Unary { .. } | Instr::Cast { .. } => true,
+                _ => false,
+            };
+            let dest = match instr.dest() {
+                Some(dest) if is_pure && defs[&dest.id] == 1 && !hoisted.contains(&dest.id) => dest,
+                _ => continue,
+            };
+            let operands_invariant = instr.operands().iter().all(|v| match v {
+                Value::Reg(reg) => !defined_in_loop.contains(&reg.id) || hoisted.contains(&reg.id),
+                _ => true,
+            });
+
+            if operands_invariant {
+                hoisted.push(dest.id);
+                found = true;
+            }
+        }
+    }
+    hoisted
+}
+
+// blocks which can reach the back-edge without going through the loop-header
+fn loop_body(
+    tail: BlockId,
+    header: BlockId,
+    predecessors: &HashMap<BlockId, Vec<BlockId>>,
+) -> HashSet<BlockId> {
+    let mut body = HashSet::from([header]);
+    let mut worklist = vec![tail];
+
+    while let Some(id) = worklist.pop() {
+        if body.insert(id) {
+            worklist.extend(predecessors[&id].iter());
+        }
+    }
+    body
+}
+
+fn predecessors(function: &Function) -> HashMap<BlockId, Vec<BlockId>> {
+    let mut preds: HashMap<BlockId, Vec<BlockId>> =
+        function.blocks.iter().map(|b| (b.id, Vec::new())).collect();
+
+    for block in function.blocks.iter() {
+        for succ in block.terminator.successors() {
+            preds.get_mut(&succ).unwrap().push(block.id);
+        }
+    }
+    preds
+}
+
+// every block maps to the set of blocks which are on all paths from the entry to it
+fn dominators(function: &Function) -> HashMap<BlockId, HashSet<BlockId>> {
+    let predecessors = predecessors(function);
+    let all: HashSet<BlockId> = function.blocks.iter().map(|b| b.id).collect();
+    let entry = function.blocks[0].id;
+
+    let mut dominators: HashMap<BlockId, HashSet<BlockId>> = function
+        .blocks
+        .iter()
+        .map(|b| {
+            if b.id == entry {
+                (b.id, HashSet::from([entry]))
+            } else {
+                (b.id, all.clone())
+            }
+        })
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for block in function.blocks.iter().skip(1) {
+            let mut new: HashSet<BlockId> = predecessors[&block.id]
+                .iter()
+                .map(|p| dominators[p].clone())
+                .reduce(|acc, d| acc.intersection(&d).copied().collect())
+                .unwrap_or_default();
+            new.insert(block.id);
+
+            if new != dominators[&block.id] {
+                dominators.insert(block.id, new);
+                changed = true;
+            }
+        }
+    }
+    dominators
+}
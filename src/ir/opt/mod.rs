@@ -0,0 +1,270 @@
+pub mod copy_prop;
+pub mod cse;
+pub mod dce;
+pub mod licm;
+
+use crate::ir::instr::*;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+}
+
+struct Pass {
+    name: &'static str,
+    // returns true if the function was changed
+    run: fn(&mut Function) -> bool,
+}
+
+static PASSES: &[Pass] = &[
+    Pass {
+        name: "unreachable-blocks",
+        run: dce::remove_unreachable_blocks,
+    },
+    Pass {
+        name: "copy-prop",
+        run: copy_prop::propagate_copies,
+    },
+    Pass {
+        name: "cse",
+        run: cse::eliminate_common_subexpressions,
+    },
+    Pass {
+        name: "licm",
+        run: licm::hoist_loop_invariants,
+    },
+    Pass {
+        name: "dce",
+        run: dce::eliminate_dead_code,
+    },
+];
+
+// runs the passes of an optimization-level over every function in the module
+pub struct PassManager {
+    passes: Vec<&'static Pass>,
+    timings: Vec<Duration>,
+    print_passes: bool,
+    time_passes: bool,
+}
+impl PassManager {
+    pub fn new(level: OptLevel, print_passes: bool, time_passes: bool) -> Self {
+        let names: &[&str] = match level {
+            OptLevel::O0 => &[],
+            OptLevel::O1 => &["unreachable-blocks", "copy-prop", "dce"],
+            // hoisted invariants give copy-propagation and cse new opportunities
+            OptLevel::O2 => &[
+                "unreachable-blocks",
+                "copy-prop",
+                "cse",
+                "licm",
+                "copy-prop",
+                "cse",
+                "dce",
+            ],
+        };
+        let passes: Vec<&'static Pass> = names
+            .iter()
+            .map(|name| PASSES.iter().find(|p| p.name == *name).unwrap())
+            .collect();
+
+        PassManager {
+            timings: vec![Duration::ZERO; passes.len()],
+            passes,
+            print_passes,
+            time_passes,
+        }
+    }
+    pub fn run(&mut self, module: &mut Module) {
+        for function in module.functions.iter_mut() {
+            for (i, pass) in self.passes.iter().enumerate() {
+                let start = Instant::now();
+                let changed = (pass.run)(function);
+                self.timings[i] += start.elapsed();
+
+                if self.print_passes {
+                    eprintln!(
+                        "{}: {}{}",
+                        function.name,
+                        pass.name,
+                        if changed { " (changed)" } else { "" }
+                    );
+                }
+            }
+        }
+        if self.time_passes {
+            self.print_timings();
+        }
+    }
+    fn print_timings(&self) {
+        eprintln!("{:<20}{:>12}", "pass", "time");
+        for (pass, time) in self.passes.iter().zip(self.timings.iter()) {
+            eprintln!("{:<20}{:>12.2?}", pass.name, time);
+        }
+        eprintln!(
+            "{:<20}{:>12.2?}",
+            "total",
+            self.timings.iter().sum::<Duration>()
+        );
+    }
+}
+
+// returns how often every virtual register is assigned to,
+// registers assigned exactly once hold the same value wherever they're used
+fn def_counts(function: &Function) -> HashMap<usize, usize> {
+    let mut counts = HashMap::new();
+    for block in function.blocks.iter() {
+        for dest in block.instrs.iter().filter_map(|instr| instr.dest()) {
+            *counts.entry(dest.id).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+// value can be freely moved around if it can't change between uses
+fn is_stable(value: &Value, defs: &HashMap<usize, usize>) -> bool {
+    match value {
+        Value::Reg(reg) => defs.get(&reg.id) == Some(&1),
+        _ => true,
+    }
+}
+
+// returns the stack-slots whose address is used for something else than loads and stores,
+// these could be modified through a pointer
+fn escaping_slots(function: &Function) -> HashSet<usize> {
+    let mut slots = HashSet::new();
+    for block in function.blocks.iter() {
+        for instr in block.instrs.iter() {
+            let operands = match instr {
+                Instr::Load { .. } => vec![],
+                Instr::Store { value, .. } => vec![value],
+                _ => instr.operands(),
+            };
+            for operand in operands {
+                if let Value::Local(slot) = operand {
+                    slots.insert(*slot);
+                }
+            }
+        }
+        for operand in block.terminator.operands() {
+            if let Value::Local(slot) = operand {
+                slots.insert(*slot);
+            }
+        }
+    }
+    slots
+}
+
+// replaces every use of a virtual register with the value it maps to
+fn replace_uses(function: &mut Function, replacements: &HashMap<usize, Value>) -> bool {
+    let mut changed = false;
+    let mut replace = |operand: &mut Value| {
+        while let Value::Reg(reg) = operand {
+            match replacements.get(&reg.id) {
+                Some(value) => {
+                    *operand = value.clone();
+                    changed = true;
+                }
+                None => break,
+            }
+        }
+    };
+
+    for block in function.blocks.iter_mut() {
+        for instr in block.instrs.iter_mut() {
+            instr.operands_mut().into_iter().for_each(&mut replace);
+        }
+        block
+            .terminator
+            .operands_mut()
+            .into_iter()
+            .for_each(&mut replace);
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::lower;
+
+    fn optimize(source: &str, level: OptLevel) -> String {
+        let mut module = lower(source);
+        PassManager::new(level, false, false).run(&mut module);
+        module.to_string()
+    }
+
+    #[test]
+    fn removes_code_after_return_and_propagates_stores() {
+        let actual = optimize(
+            "int main() {\nint x = 3;\nint y = x + 1;\nreturn y;\ny = 5;\n}",
+            OptLevel::O1,
+        );
+        let expected = "
+function main() -> int {
+  local.0: int ; x
+  local.1: int ; y
+bb0:
+  store 3, local.0
+  %1: int = add 3, 1
+  store %1, local.1
+  ret %1
+}
+";
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn hoists_loop_invariants() {
+        let actual = optimize(
+            "int main() {\nint n = 10;\nint i = 0;\nwhile (i < n) {\ni = i + n * 2;\n}\nreturn i;\n}",
+            OptLevel::O2,
+        );
+        let expected = "
+function main() -> int {
+  local.0: int ; n
+  local.1: int ; i
+bb0:
+  store 10, local.0
+  store 0, local.1
+  %5: int = mul 10, 2
+  jmp bb1
+bb1:
+  %0: int = load local.1
+  %2: int = lt %0, 10
+  br %2, bb2, bb3
+bb2:
+  %3: int = load local.1
+  %6: int = add %3, %5
+  store %6, local.1
+  jmp bb1
+bb3:
+  %7: int = load local.1
+  ret %7
+}
+";
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn keeps_volatile_loads() {
+        let actual = optimize(
+            "int main() {\nvolatile int x = 1;\nx;\nreturn x + x;\n}",
+            OptLevel::O2,
+        );
+        let expected = "
+function main() -> int {
+  local.0: volatile int ; x
+bb0:
+  store 1, local.0
+  %0: int = load volatile local.0
+  %1: int = load volatile local.0
+  %2: int = load volatile local.0
+  %3: int = add %1, %2
+  ret %3
+}
+";
+        assert_eq!(actual, expected);
+    }
+}
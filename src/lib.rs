@@ -0,0 +1,13 @@
+pub mod cli;
+pub mod codegen;
+pub mod common;
+pub mod dump;
+mod fold;
+pub mod formatter;
+pub mod ir;
+pub mod lsp;
+pub mod parser;
+pub mod scanner;
+#[cfg(test)]
+mod test_utils;
+pub mod typechecker;
@@ -0,0 +1,392 @@
+use crate::common::{environment::*, error::*, expr::*, stmt::*, token::*, types::*};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::typechecker::{Std, TypeChecker};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub error: Error,
+}
+
+// the declaration an identifier resolves to
+#[derive(Clone, PartialEq, Debug)]
+pub struct Definition {
+    pub token: Token,
+    // declaration as written in C, shown on hover
+    pub detail: String,
+    pub is_function: bool,
+}
+
+// everything the server knows about a single version of a document
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    // every identifier in the source together with the declaration it resolves to
+    references: Vec<(Token, Definition)>,
+    // functions and globals in source-order
+    pub symbols: Vec<Definition>,
+}
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        let mut analysis = Analysis {
+            diagnostics: Vec::new(),
+            references: Vec::new(),
+            symbols: Vec::new(),
+        };
+
+        let (tokens, errors) = Scanner::new(source).scan();
+        if !errors.is_empty() {
+            analysis.add_diagnostics(errors, Severity::Error);
+            return analysis;
+        }
+        let mut statements = match Parser::new(tokens).parse_statements() {
+            Ok(statements) => statements,
+            Err(errors) => {
+                analysis.add_diagnostics(errors, Severity::Error);
+                return analysis;
+            }
+        };
+
+        // resolving identifiers doesn't depend on a fully typechecked AST
+        let (errors, warnings) = TypeChecker::new(Std::C99).diagnose(&mut statements);
+        analysis.add_diagnostics(errors, Severity::Error);
+        analysis.add_diagnostics(warnings, Severity::Warning);
+
+        let mut resolver = Resolver {
+            env: Environment::new(None),
+            analysis: &mut analysis,
+        };
+        resolver.resolve(&statements);
+
+        analysis
+    }
+    fn add_diagnostics(&mut self, errors: Vec<Error>, severity: Severity) {
+        self.diagnostics.extend(
+            errors
+                .into_iter()
+                .map(|error| Diagnostic { severity, error }),
+        );
+    }
+
+    // positions are zero-based like in the protocol, the cursor may also be right after an identifier
+    pub fn reference_at(&self, line: usize, character: usize) -> Option<&(Token, Definition)> {
+        self.references.iter().find(|(token, _)| {
+            let (start, end) = span(token);
+            start.0 == line && start.1 <= character && character <= end.1
+        })
+    }
+}
+
+// zero-based start and end position of an identifier
+pub fn span(token: &Token) -> ((usize, usize), (usize, usize)) {
+    let line = (token.line_index - 1).max(0) as usize;
+    let start = (token.column - 1).max(0) as usize;
+    let len = match &token.token {
+        TokenType::Ident(name) => name.chars().count(),
+        _ => 1,
+    };
+    ((line, start), (line, start + len))
+}
+
+// walks the parsed program with the same scoping-rules as the typechecker
+struct Resolver<'a> {
+    env: Environment<Definition>,
+    analysis: &'a mut Analysis,
+}
+impl Resolver<'_> {
+    fn resolve(&mut self, statements: &[Stmt]) {
+        // functions can be called before their definition if they have a prototype, so always
+        // resolve to the definition
+        for statement in statements {
+            if let Stmt::Function(return_type, name, params, variadic, ..) = statement {
+                self.env.declare_var(
+                    name.unwrap_string(),
+                    function(return_type, name, &named(params), *variadic),
+                );
+            }
+        }
+        for statement in statements {
+            self.stmt(statement, true);
+        }
+    }
+    fn declare(&mut self, definition: Definition, is_global: bool) {
+        if is_global {
+            self.analysis.symbols.push(definition.clone());
+        }
+        self.analysis
+            .references
+            .push((definition.token.clone(), definition.clone()));
+        self.env
+            .declare_var(definition.token.unwrap_string(), definition);
+    }
+    fn enter_scope(&mut self) {
+        let enclosing = std::mem::replace(&mut self.env, Environment::new(None));
+        self.env = Environment::new(Some(Box::new(enclosing)));
+    }
+    fn exit_scope(&mut self) {
+        self.env = *self.env.enclosing.take().unwrap();
+    }
+    fn stmt(&mut self, statement: &Stmt, is_global: bool) {
+        match statement {
+            Stmt::Expr(expr) => self.expr(expr),
+            Stmt::DeclareVar(type_decl, name, ..) => {
+                self.declare(variable(type_decl, name), is_global)
+            }
+            Stmt::InitVar(type_decl, name, expr, ..) => {
+                self.expr(expr);
+                self.declare(variable(type_decl, name), is_global);
+            }
+            Stmt::InitList(type_decl, name, exprs, ..) => {
+                exprs.iter().for_each(|expr| self.expr(expr));
+                self.declare(variable(type_decl, name), is_global);
+            }
+            Stmt::Block(statements) => {
+                self.enter_scope();
+                statements.iter().for_each(|s| self.stmt(s, false));
+                self.exit_scope();
+            }
+            Stmt::If(_, cond, then_branch, else_branch) => {
+                self.expr(cond);
+                self.stmt(then_branch, false);
+                if let Some(else_branch) = &**else_branch {
+                    self.stmt(else_branch, false);
+                }
+            }
+            Stmt::While(_, cond, body) => {
+                self.expr(cond);
+                self.stmt(body, false);
+            }
+            Stmt::Function(return_type, name, params, variadic, _, body) => {
+                self.declare(function(return_type, name, &named(params), *variadic), true);
+
+                // parameters share the scope with the function-body
+                self.enter_scope();
+                for (type_decl, param) in params {
+                    self.declare(variable(type_decl, param), false);
+                }
+                body.iter().for_each(|s| self.stmt(s, false));
+                self.exit_scope();
+            }
+            Stmt::FunctionDeclaration(return_type, name, params, variadic, _) => {
+                let definition = function(return_type, name, params, *variadic);
+                self.analysis.symbols.push(definition.clone());
+
+                match self.env.get_var(name) {
+                    Ok(existing) => self.analysis.references.push((name.clone(), existing)),
+                    Err(_) => self.declare(definition, false),
+                }
+            }
+            Stmt::Return(_, expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr)
+                }
+            }
+        }
+    }
+    fn expr(&mut self, ast: &Expr) {
+        match &ast.kind {
+            ExprKind::Binary { left, right, .. }
+            | ExprKind::Logical { left, right, .. }
+            | ExprKind::Assign {
+                l_expr: left,
+                r_expr: right,
+                ..
+            }
+            | ExprKind::CompoundAssign {
+                l_expr: left,
+                r_expr: right,
+                ..
+            } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Unary { right: expr, .. }
+            | ExprKind::Grouping { expr }
+            | ExprKind::CastUp { expr }
+            | ExprKind::CastDown { expr }
+            | ExprKind::ScaleUp { expr, .. }
+            | ExprKind::ScaleDown { expr, .. }
+            | ExprKind::PostUnary { left: expr, .. } => self.expr(expr),
+            ExprKind::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ExprKind::Builtin { args, .. } => args.iter().for_each(|arg| self.expr(arg)),
+            ExprKind::Ident(token) => {
+                if let Ok(definition) = self.env.get_var(token) {
+                    self.analysis.references.push((token.clone(), definition));
+                }
+            }
+            ExprKind::String(_) | ExprKind::Number(_) | ExprKind::CharLit(_) => (),
+        }
+    }
+}
+
+// spells out the type around the declarator like it would be declared in C,
+// an empty declarator gives the abstract type
+fn declaration(type_decl: &NEWTypes, declarator: String) -> String {
+    // the declarator of a pointer to an array or function has to be grouped
+    let pointer = |to: &NEWTypes, declarator: String| match to {
+        NEWTypes::Array { .. } | NEWTypes::Function { .. } => format!("({})", declarator),
+        _ => declarator,
+    };
+    match type_decl {
+        NEWTypes::Primitive(_) => format!("{} {}", type_decl, declarator)
+            .trim_end()
+            .to_string(),
+        NEWTypes::Pointer(to) => declaration(to, pointer(to, format!("*{}", declarator))),
+        NEWTypes::Array { amount, of } => declaration(of, format!("{}[{}]", declarator, amount)),
+        NEWTypes::Function {
+            return_type,
+            params,
+            variadic,
+        } => {
+            let params = params
+                .iter()
+                .map(|type_decl| (type_decl.clone(), None))
+                .collect::<Vec<_>>();
+            declaration(
+                return_type,
+                format!("{}({})", declarator, param_list(&params, *variadic)),
+            )
+        }
+        // qualifiers of pointers follow the '*'
+        NEWTypes::Qualified(qualifiers, inner) => match &**inner {
+            NEWTypes::Pointer(to) => declaration(
+                to,
+                pointer(
+                    to,
+                    format!("*{} {}", qualifiers, declarator)
+                        .trim_end()
+                        .to_string(),
+                ),
+            ),
+            _ => format!("{} {}", qualifiers, declaration(inner, declarator)),
+        },
+    }
+}
+fn param_list(params: &[(NEWTypes, Option<Token>)], variadic: bool) -> String {
+    let mut params = params
+        .iter()
+        .map(|(type_decl, name)| {
+            declaration(
+                type_decl,
+                name.as_ref()
+                    .map_or(String::new(), |name| name.unwrap_string()),
+            )
+        })
+        .collect::<Vec<String>>();
+    if variadic {
+        params.push("...".to_string());
+    }
+    params.join(", ")
+}
+
+fn variable(type_decl: &NEWTypes, name: &Token) -> Definition {
+    Definition {
+        token: name.clone(),
+        detail: declaration(type_decl, name.unwrap_string()),
+        is_function: false,
+    }
+}
+// parameters of definitions always have a name
+fn named(params: &[(NEWTypes, Token)]) -> Vec<(NEWTypes, Option<Token>)> {
+    params
+        .iter()
+        .map(|(type_decl, name)| (type_decl.clone(), Some(name.clone())))
+        .collect()
+}
+fn function(
+    return_type: &NEWTypes,
+    name: &Token,
+    params: &[(NEWTypes, Option<Token>)],
+    variadic: bool,
+) -> Definition {
+    let declarator = format!("{}({})", name.unwrap_string(), param_list(params, variadic));
+    Definition {
+        token: name.clone(),
+        detail: declaration(return_type, declarator),
+        is_function: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_identifiers_through_scopes() {
+        let analysis = Analysis::new(
+            "int f(int a);\nlong g = 2;\nint main() {\nint a = 1;\n{\nchar *a;\n}\nreturn f(a) + g;\n}\nint f(int a) { return a; }",
+        );
+        assert!(analysis.diagnostics.is_empty());
+
+        // call before the definition resolves to the definition, not the prototype
+        let (_, f) = analysis.reference_at(7, 7).unwrap();
+        assert_eq!(f.detail, "int f(int a)");
+        assert_eq!(span(&f.token).0, (9, 4));
+
+        // inner 'char *a' is out of scope at the return
+        let (_, a) = analysis.reference_at(7, 10).unwrap();
+        assert_eq!(a.detail, "int a");
+        assert_eq!(span(&a.token).0, (3, 4));
+
+        let (_, g) = analysis.reference_at(7, 15).unwrap();
+        assert_eq!(g.detail, "long g");
+        assert!(analysis.reference_at(7, 2).is_none());
+
+        let symbols: Vec<&str> = analysis.symbols.iter().map(|s| s.detail.as_str()).collect();
+        assert_eq!(
+            symbols,
+            vec!["int f(int a)", "long g", "int main()", "int f(int a)"]
+        );
+    }
+    #[test]
+    fn details_are_declarations() {
+        let analysis = Analysis::new(
+            "int (*choose(int op))(int, int);\nint main(void) {\nchar c;\nint (*fp)(int, char *);\nconst char *s[2];\nchar *const t = &c;\nint (*p)[3];\nreturn fp(1, t) + *s[0] + (*p)[0];\n}",
+        );
+        assert!(analysis.diagnostics.is_empty());
+
+        let symbols: Vec<&str> = analysis.symbols.iter().map(|s| s.detail.as_str()).collect();
+        assert_eq!(
+            symbols,
+            vec!["int (*choose(int op))(int, int)", "int main()"]
+        );
+        let details: Vec<&str> = [7, 13, 19, 28]
+            .iter()
+            .map(|column| analysis.reference_at(7, *column).unwrap().1.detail.as_str())
+            .collect();
+        assert_eq!(
+            details,
+            vec![
+                "int (*fp)(int, char *)",
+                "char *const t",
+                "const char *s[2]",
+                "int (*p)[3]"
+            ]
+        );
+    }
+    #[test]
+    fn reports_errors_and_warnings() {
+        let analysis = Analysis::new("int main() {\nint x = 1;\nx /= 0;\nreturn y;\n}");
+        let diagnostics: Vec<(Severity, i32, &str)> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.error.line_index, d.error.msg.as_str()))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (Severity::Error, 4, "undeclared variable"),
+                (Severity::Warning, 3, "Division by zero")
+            ]
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// owned json-value, unlike dump::Json this also has to be parsed from incoming messages
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+impl Value {
+    pub fn object(fields: Vec<(&str, Value)>) -> Value {
+        Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+    pub fn string(s: impl Display) -> Value {
+        Value::String(s.to_string())
+    }
+    // returns Null for missing fields so that lookups can be chained
+    pub fn get(&self, key: &str) -> &Value {
+        match self {
+            Value::Object(fields) => fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value)
+                .unwrap_or(&Value::Null),
+            _ => &Value::Null,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) if n.fract() == 0.0 => write!(f, "{}", *n as i64),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", Value::string(key), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Value, String> {
+    let mut chars = input.chars().peekable();
+    let value = parse_value(&mut chars)?;
+    skip_whitespace(&mut chars);
+
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("trailing character '{}'", c)),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(format!("expected '{}', found '{}'", expected, c)),
+        None => Err(format!("expected '{}', found end of input", expected)),
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut fields = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(Value::Object(fields));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = match parse_value(chars)? {
+                    Value::String(key) => key,
+                    _ => return Err("object-keys have to be strings".to_string()),
+                };
+                expect(chars, ':')?;
+                fields.push((key, parse_value(chars)?));
+
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some('}') => return Ok(Value::Object(fields)),
+                    _ => return Err("expected ',' or '}' in object".to_string()),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut values = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(Value::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars)?);
+
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some(']') => return Ok(Value::Array(values)),
+                    _ => return Err("expected ',' or ']' in array".to_string()),
+                }
+            }
+        }
+        Some('"') => {
+            chars.next();
+            parse_string(chars).map(Value::String)
+        }
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) =
+                chars.next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+            {
+                number.push(c);
+            }
+            number
+                .parse::<f64>()
+                .map(Value::Number)
+                .map_err(|_| format!("invalid number '{}'", number))
+        }
+        Some(c) if c.is_ascii_alphabetic() => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "null" => Ok(Value::Null),
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => Err(format!("unexpected literal '{}'", word)),
+            }
+        }
+        Some(c) => Err(format!("unexpected character '{}'", c)),
+        None => Err("unexpected end of input".to_string()),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some('"') => s.push('"'),
+                Some('\\') => s.push('\\'),
+                Some('/') => s.push('/'),
+                Some('b') => s.push('\u{8}'),
+                Some('f') => s.push('\u{c}'),
+                Some('n') => s.push('\n'),
+                Some('r') => s.push('\r'),
+                Some('t') => s.push('\t'),
+                Some('u') => {
+                    let mut code = hex_escape(chars)?;
+                    // characters outside the BMP are encoded as a surrogate pair
+                    if (0xd800..0xdc00).contains(&code) {
+                        if chars.next() != Some('\\') || chars.next() != Some('u') {
+                            return Err("unpaired surrogate in string".to_string());
+                        }
+                        let low = hex_escape(chars)?;
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00));
+                    }
+                    s.push(char::from_u32(code).ok_or("invalid unicode escape in string")?);
+                }
+                _ => return Err("invalid escape in string".to_string()),
+            },
+            Some(c) => s.push(c),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+fn hex_escape(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let digits: String = chars.take(4).collect();
+    u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid unicode escape '{}'", digits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prints_messages() {
+        let input = r#" {"id": 1, "params": {"text": "a\n\"b\" \u00e4\ud83d\ude00",
+            "list": [true, false, null, -2.5, []], "empty": {}}} "#;
+        let value = parse(input).unwrap();
+
+        assert_eq!(value.get("id").as_usize(), Some(1));
+        assert_eq!(
+            value.get("params").get("text").as_str(),
+            Some("a\n\"b\" \u{e4}\u{1f600}")
+        );
+        assert_eq!(value.get("missing").get("field"), &Value::Null);
+        assert_eq!(
+            value.to_string(),
+            "{\"id\":1,\"params\":{\"text\":\"a\\n\\\"b\\\" \u{e4}\u{1f600}\",\
+             \"list\":[true,false,null,-2.5,[]],\"empty\":{}}}"
+        );
+        assert!(parse("{\"a\": }").is_err());
+        assert!(parse("[1, 2").is_err());
+    }
+}
@@ -0,0 +1,427 @@
+pub mod analysis;
+pub mod json;
+
+use crate::common::{error::*, token::*};
+use analysis::*;
+use json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+// json-rpc error-codes
+const PARSE_ERROR: f64 = -32700.0;
+const INVALID_REQUEST: f64 = -32600.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+// LSP SymbolKind
+const SYMBOL_FUNCTION: f64 = 12.0;
+const SYMBOL_VARIABLE: f64 = 13.0;
+
+// language-server speaking json-rpc over a pair of streams, normally stdin and stdout
+pub struct Server<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    // analysis of every open document by uri
+    documents: HashMap<String, Analysis>,
+    shutdown: bool,
+}
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Server {
+            reader,
+            writer,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+    // handles messages until the client sends 'exit' or closes the stream, returns the exit-code
+    pub fn run(&mut self) -> i32 {
+        while let Some(content) = self.read_message() {
+            let message = match json::parse(&content) {
+                Ok(message) => message,
+                Err(e) => {
+                    self.respond_error(Value::Null, PARSE_ERROR, &e);
+                    continue;
+                }
+            };
+            let method = match message.get("method").as_str() {
+                Some(method) => method.to_string(),
+                None => {
+                    // responses to server-requests are never expected
+                    if message.get("id") == &Value::Null {
+                        self.respond_error(Value::Null, INVALID_REQUEST, "missing method");
+                    }
+                    continue;
+                }
+            };
+            if method == "exit" {
+                return if self.shutdown { 0 } else { 1 };
+            }
+            let id = message.get("id").clone();
+            let params = message.get("params");
+
+            match (method.as_str(), id) {
+                ("initialize", id) => self.respond(id, capabilities()),
+                ("shutdown", id) => {
+                    self.shutdown = true;
+                    self.respond(id, Value::Null)
+                }
+                ("textDocument/didOpen", Value::Null) => {
+                    let document = params.get("textDocument");
+                    if let (Some(uri), Some(text)) =
+                        (document.get("uri").as_str(), document.get("text").as_str())
+                    {
+                        self.update(uri, text);
+                    }
+                }
+                ("textDocument/didChange", Value::Null) => {
+                    // only full-document sync is supported so the last change holds the whole text
+                    let uri = params.get("textDocument").get("uri").as_str();
+                    let changes = params.get("contentChanges").as_array();
+                    if let (Some(uri), Some(change)) = (uri, changes.and_then(|c| c.last())) {
+                        if let Some(text) = change.get("text").as_str() {
+                            self.update(uri, text);
+                        }
+                    }
+                }
+                ("textDocument/didClose", Value::Null) => {
+                    if let Some(uri) = params.get("textDocument").get("uri").as_str() {
+                        self.documents.remove(uri);
+                        self.publish_diagnostics(uri, Vec::new());
+                    }
+                }
+                ("textDocument/hover", id) => {
+                    let result = self.reference_at(params).map(|(token, definition)| {
+                        Value::object(vec![
+                            (
+                                "contents",
+                                Value::object(vec![
+                                    ("kind", Value::string("plaintext")),
+                                    ("value", Value::string(&definition.detail)),
+                                ]),
+                            ),
+                            ("range", range(token)),
+                        ])
+                    });
+                    self.respond(id, result.unwrap_or(Value::Null))
+                }
+                ("textDocument/definition", id) => {
+                    let uri = params.get("textDocument").get("uri").clone();
+                    let result = self.reference_at(params).map(|(_, definition)| {
+                        Value::object(vec![("uri", uri), ("range", range(&definition.token))])
+                    });
+                    self.respond(id, result.unwrap_or(Value::Null))
+                }
+                ("textDocument/documentSymbol", id) => {
+                    let uri = params.get("textDocument").get("uri").as_str();
+                    let symbols = match uri.and_then(|uri| self.documents.get(uri)) {
+                        Some(analysis) => analysis.symbols.iter().map(symbol).collect(),
+                        None => Vec::new(),
+                    };
+                    self.respond(id, Value::Array(symbols))
+                }
+                // notifications which aren't supported are ignored
+                (_, Value::Null) => (),
+                (method, id) => self.respond_error(
+                    id,
+                    METHOD_NOT_FOUND,
+                    &format!("unsupported method '{}'", method),
+                ),
+            }
+        }
+        1
+    }
+
+    fn update(&mut self, uri: &str, text: &str) {
+        let analysis = Analysis::new(text);
+        let diagnostics = analysis.diagnostics.iter().map(diagnostic).collect();
+        self.documents.insert(uri.to_string(), analysis);
+        self.publish_diagnostics(uri, diagnostics);
+    }
+    fn reference_at(&self, params: &Value) -> Option<&(Token, Definition)> {
+        let analysis = self
+            .documents
+            .get(params.get("textDocument").get("uri").as_str()?)?;
+        let position = params.get("position");
+
+        analysis.reference_at(
+            position.get("line").as_usize()?,
+            position.get("character").as_usize()?,
+        )
+    }
+
+    // reads the content of the next message, skipping over its headers
+    fn read_message(&mut self) -> Option<String> {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if self.reader.read_line(&mut header).ok()? == 0 {
+                return None;
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+        let mut content = vec![0; length?];
+        self.reader.read_exact(&mut content).ok()?;
+
+        String::from_utf8(content).ok()
+    }
+    fn send(&mut self, message: Value) {
+        let content = message.to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .and_then(|_| self.writer.flush())
+        .unwrap_or_else(|_| Error::sys_exit("couldn't write to client", 1));
+    }
+    fn respond(&mut self, id: Value, result: Value) {
+        self.send(Value::object(vec![
+            ("jsonrpc", Value::string("2.0")),
+            ("id", id),
+            ("result", result),
+        ]))
+    }
+    fn respond_error(&mut self, id: Value, code: f64, message: &str) {
+        self.send(Value::object(vec![
+            ("jsonrpc", Value::string("2.0")),
+            ("id", id),
+            (
+                "error",
+                Value::object(vec![
+                    ("code", Value::Number(code)),
+                    ("message", Value::string(message)),
+                ]),
+            ),
+        ]))
+    }
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) {
+        self.send(Value::object(vec![
+            ("jsonrpc", Value::string("2.0")),
+            ("method", Value::string("textDocument/publishDiagnostics")),
+            (
+                "params",
+                Value::object(vec![
+                    ("uri", Value::string(uri)),
+                    ("diagnostics", Value::Array(diagnostics)),
+                ]),
+            ),
+        ]))
+    }
+}
+
+fn capabilities() -> Value {
+    Value::object(vec![
+        (
+            "capabilities",
+            Value::object(vec![
+                // full-document sync
+                ("textDocumentSync", Value::Number(1.0)),
+                ("hoverProvider", Value::Bool(true)),
+                ("definitionProvider", Value::Bool(true)),
+                ("documentSymbolProvider", Value::Bool(true)),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Value::object(vec![("name", Value::string("rucc-lsp"))]),
+        ),
+    ])
+}
+
+fn position((line, character): (usize, usize)) -> Value {
+    Value::object(vec![
+        ("line", Value::Number(line as f64)),
+        ("character", Value::Number(character as f64)),
+    ])
+}
+fn range(token: &Token) -> Value {
+    let (start, end) = span(token);
+    Value::object(vec![("start", position(start)), ("end", position(end))])
+}
+
+fn diagnostic(diagnostic: &Diagnostic) -> Value {
+    let error = &diagnostic.error;
+    // errors without a location, like a missing main(), are shown at the start of the file
+    let (line, column) = match error.line_index {
+        -1 => (0, 0),
+        line => (
+            (line - 1).max(0) as usize,
+            (error.column - 1).max(0) as usize,
+        ),
+    };
+    Value::object(vec![
+        (
+            "range",
+            Value::object(vec![
+                ("start", position((line, column))),
+                ("end", position((line, column + 1))),
+            ]),
+        ),
+        ("severity", Value::Number(diagnostic.severity as i32 as f64)),
+        ("source", Value::string("rucc")),
+        ("message", Value::string(&error.msg)),
+    ])
+}
+
+fn symbol(definition: &Definition) -> Value {
+    let kind = match definition.is_function {
+        true => SYMBOL_FUNCTION,
+        false => SYMBOL_VARIABLE,
+    };
+    Value::object(vec![
+        ("name", Value::string(definition.token.unwrap_string())),
+        ("detail", Value::string(&definition.detail)),
+        ("kind", Value::Number(kind)),
+        ("range", range(&definition.token)),
+        ("selectionRange", range(&definition.token)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(content: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+    }
+    fn messages(output: &[u8]) -> Vec<Value> {
+        let mut server = Server::new(output, Vec::new());
+        std::iter::from_fn(|| server.read_message())
+            .map(|content| json::parse(&content).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn scripted_session() {
+        let uri = "file:///main.c";
+        let text = "int g;\\nint main() {\\nreturn g + x;\\n}";
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#.to_string(),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{}","languageId":"c","version":1,"text":"{}"}}}}}}"#,
+                uri, text
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{}","version":2}},"contentChanges":[{{"text":"{}"}}]}}}}"#,
+                uri,
+                text.replace("x", "1")
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{{"textDocument":{{"uri":"{}"}},"position":{{"line":2,"character":7}}}}}}"#,
+                uri
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{{"textDocument":{{"uri":"{}"}},"position":{{"line":2,"character":7}}}}}}"#,
+                uri
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":4,"method":"textDocument/documentSymbol","params":{{"textDocument":{{"uri":"{}"}}}}}}"#,
+                uri
+            ),
+            r#"{"jsonrpc":"2.0","id":5,"method":"textDocument/formatting","params":{}}"#
+                .to_string(),
+            r#"{"jsonrpc":"2.0","id":6,"method":"shutdown"}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+        ]
+        .iter()
+        .map(|content| frame(content))
+        .collect::<String>();
+
+        let mut output = Vec::new();
+        let exit_code = Server::new(input.as_bytes(), &mut output).run();
+        assert_eq!(exit_code, 0);
+
+        let messages = messages(&output);
+        assert_eq!(messages.len(), 8);
+        assert_eq!(
+            messages[0]
+                .get("result")
+                .get("capabilities")
+                .get("hoverProvider"),
+            &Value::Bool(true)
+        );
+
+        // the undeclared 'x' gets reported and is cleared after the change
+        let diagnostics = messages[1].get("params").get("diagnostics");
+        assert_eq!(
+            diagnostics.to_string(),
+            r#"[{"range":{"start":{"line":2,"character":11},"end":{"line":2,"character":12}},"severity":1,"source":"rucc","message":"undeclared variable"}]"#
+        );
+        assert_eq!(
+            messages[2].get("params").get("diagnostics"),
+            &Value::Array(vec![])
+        );
+
+        let hover = messages[3].get("result");
+        assert_eq!(hover.get("contents").get("value").as_str(), Some("int g"));
+        assert_eq!(
+            messages[4].get("result").to_string(),
+            r#"{"uri":"file:///main.c","range":{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}}"#
+        );
+
+        let symbols: Vec<(&str, Option<usize>)> = messages[5]
+            .get("result")
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| (s.get("detail").as_str().unwrap(), s.get("kind").as_usize()))
+            .collect();
+        assert_eq!(symbols, vec![("int g", Some(13)), ("int main()", Some(12))]);
+
+        assert_eq!(
+            messages[6].get("error").get("code"),
+            &Value::Number(METHOD_NOT_FOUND)
+        );
+        assert_eq!(messages[7].get("id").as_usize(), Some(6));
+    }
+    #[test]
+    fn survives_truncated_documents() {
+        let uri = "file:///main.c";
+        let open = |text: &str| {
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{}","languageId":"c","version":1,"text":"{}"}}}}}}"#,
+                uri, text
+            )
+        };
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string(),
+            open("int main("),
+            open("int x;\\nextern"),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/documentSymbol","params":{{"textDocument":{{"uri":"{}"}}}}}}"#,
+                uri
+            ),
+            r#"{"jsonrpc":"2.0","id":3,"method":"shutdown"}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+        ]
+        .iter()
+        .map(|content| frame(content))
+        .collect::<String>();
+
+        let mut output = Vec::new();
+        let exit_code = Server::new(input.as_bytes(), &mut output).run();
+        assert_eq!(exit_code, 0);
+
+        // errors at the end of the file point at the last token
+        let messages = messages(&output);
+        assert_eq!(messages.len(), 5);
+        assert_eq!(
+            messages[1].get("params").get("diagnostics").to_string(),
+            r#"[{"range":{"start":{"line":0,"character":8},"end":{"line":0,"character":9}},"severity":1,"source":"rucc","message":"Expected type found end of file"}]"#
+        );
+        assert_eq!(
+            messages[2].get("params").get("diagnostics").to_string(),
+            r#"[{"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":1}},"severity":1,"source":"rucc","message":"Expected declaration, found end of file"}]"#
+        );
+        assert_eq!(messages[3].get("result"), &Value::Array(vec![]));
+        assert_eq!(messages[4].get("id").as_usize(), Some(3));
+    }
+}
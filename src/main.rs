@@ -1,30 +1,37 @@
 use std::fs;
 
-mod codegen;
-mod common;
-mod parser;
-mod scanner;
-mod typechecker;
-
-use codegen::codegen::*;
-use common::error::*;
-use parser::*;
-use scanner::*;
-use typechecker::*;
+use rucc::cli::*;
+use rucc::codegen::{assembler::*, codegen::*, debug::DebugInfo, elf::*, printer::*};
+use rucc::common::error::*;
+use rucc::dump;
+use rucc::formatter;
+use rucc::ir::dot::*;
+use rucc::ir::lower::*;
+use rucc::ir::opt::*;
+use rucc::parser::*;
+use rucc::scanner::*;
+use rucc::typechecker::*;
 
 fn main() {
     // read input file
-    let args: Vec<String> = std::env::args().collect();
-    let file = match args.len() {
-        2 => &args[1],
-        _ => Error::sys_exit("usage: rucc <file>", 22),
-    };
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("fmt") {
+        return format_file(&FmtCli::parse(&args[1..]));
+    }
+    let cli = Cli::parse(&args);
+    let file = &cli.file;
 
     let source = fs::read_to_string(file)
         .unwrap_or_else(|_| Error::sys_exit(&format!("couldn't find file: '{}'", file), 2));
 
     // Scan input
-    let tokens = match Scanner::new(&source).scan_token() {
+    let mut scanner = Scanner::new(&source);
+    if let Some(format) = cli.dump_tokens {
+        let (tokens, errors) = scanner.scan();
+        print!("{}", dump::tokens(&tokens, &errors, format));
+        return;
+    }
+    let tokens = match scanner.scan_token() {
         Ok(v) => v,
         Err(e) => {
             for err in e {
@@ -41,9 +48,9 @@ fn main() {
     };
 
     // Check for errors
-    let mut typechecker = TypeChecker::new();
-    let (func_stack, const_labels) = match typechecker.check(&mut statements) {
-        Ok(func_stack) => func_stack,
+    let mut typechecker = TypeChecker::new(cli.std);
+    let const_labels = match typechecker.check(&mut statements) {
+        Ok(const_labels) => const_labels,
         Err(e) => {
             for err in e {
                 err.print_error();
@@ -52,6 +59,58 @@ fn main() {
         }
     };
 
+    if let Some(format) = cli.dump_ast {
+        print!("{}", dump::render(&dump::ast(&statements), format));
+        return;
+    }
+
+    // lower typechecked AST into IR
+    let mut module = Lowering::new(const_labels, cli.debug, cli.asm_comments).lower(&statements);
+
+    // optimize IR
+    PassManager::new(cli.opt_level, cli.print_passes, cli.time_passes).run(&mut module);
+    if cli.emit == Emit::Ir {
+        print!("{}", module);
+        return;
+    }
+    if cli.emit == Emit::CfgDot {
+        print!("{}", cfg_dot(&module));
+        return;
+    }
+
     // generate x8664 assembly
-    Compiler::new(func_stack, const_labels).compile(&statements);
+    let instrs = Compiler::new(
+        cli.opt_level,
+        cli.debug.then(|| DebugInfo::new(file)),
+        cli.asm_comments,
+    )
+    .compile(&module);
+
+    // either write out the textual assembly or assemble it into an object file directly
+    let result = if cli.emit == Emit::Object {
+        fs::write("generated.o", write_elf(&assemble(&instrs)))
+    } else {
+        fs::write("generated.s", print(&instrs, cli.syntax))
+    };
+    result.unwrap_or_else(|_| Error::sys_exit("couldn't write output file", 1));
+}
+
+fn format_file(cli: &FmtCli) {
+    let file = &cli.file;
+    let source = fs::read_to_string(file)
+        .unwrap_or_else(|_| Error::sys_exit(&format!("couldn't find file: '{}'", file), 2));
+
+    let formatted = formatter::format(&source).unwrap_or_else(|errors| {
+        for err in errors {
+            err.print_error();
+        }
+        std::process::exit(1)
+    });
+    if formatted == source {
+        return;
+    }
+    if cli.check {
+        Error::sys_exit(&format!("'{}' isn't formatted", file), 1)
+    }
+    fs::write(file, formatted).unwrap_or_else(|_| Error::sys_exit("couldn't write output file", 1));
 }
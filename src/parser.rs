@@ -1,36 +1,51 @@
 use crate::common::{error::*, expr::*, stmt::*, token::*, types::*};
+use crate::fold::const_eval;
 use std::cmp::Ordering;
 use std::iter::Peekable;
 use std::vec::IntoIter;
 
 pub struct Parser {
     tokens: Peekable<IntoIter<Token>>,
+    // errors at the end of the file point at the last token
+    last: Option<Token>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         Parser {
+            last: tokens.last().cloned(),
             tokens: tokens.into_iter().peekable(),
         }
     }
     pub fn parse(&mut self) -> Option<Vec<Stmt>> {
+        match self.parse_statements() {
+            Ok(statements) => Some(statements),
+            Err(errors) => {
+                for e in errors {
+                    e.print_error();
+                }
+                None
+            }
+        }
+    }
+    // collects all errors instead of printing them
+    pub fn parse_statements(&mut self) -> Result<Vec<Stmt>, Vec<Error>> {
         let mut statements: Vec<Stmt> = Vec::new();
-        let mut had_error = false;
+        let mut errors = Vec::new();
 
-        while self.tokens.peek() != None {
+        while self.tokens.peek().is_some() {
             match self.declaration() {
-                Ok(v) => statements.push(v),
+                Ok(v) => statements.extend(v),
                 Err(e) => {
-                    e.print_error();
+                    errors.push(e);
                     self.synchronize();
-                    had_error = true;
                 }
             }
         }
-        if had_error {
-            None
+        if errors.is_empty() {
+            Ok(statements)
         } else {
-            Some(statements)
+            Err(errors)
         }
     }
     fn synchronize(&mut self) {
@@ -44,32 +59,44 @@ impl Parser {
                     | TokenType::While
                     | TokenType::For
                     | TokenType::Char
-                    | TokenType::Int => return,
+                    | TokenType::Int
+                    | TokenType::Static
+                    | TokenType::Extern
+                    | TokenType::Const
+                    | TokenType::Volatile => return,
                     _ => (),
                 }
             }
             prev = self.tokens.next();
         }
     }
-    fn declaration(&mut self) -> Result<Stmt, Error> {
-        if let Some(t) = self.matches_type() {
+    fn declaration(&mut self) -> Result<Vec<Stmt>, Error> {
+        let storage_class = self.storage_class();
+        if let Some(t) = self.matches_type()? {
             if let Some(left) = self.matches(vec![TokenKind::LeftBracket]) {
                 return Err(Error::new(
                     &left,
                     "Brackets not allowed here; Put them after the Identifier",
                 ));
             }
-            self.type_declaration(t)
+            self.type_declaration(t, storage_class)
         } else {
             match self.tokens.peek() {
                 Some(t) => Err(Error::new(
-                    &t,
+                    t,
                     &format!("Expected declaration, found {}", t.token),
                 )),
-                None => panic!("Expected declaration, found nothing"),
+                None => Err(self.eof_error("Expected declaration, found end of file")),
             }
         }
     }
+    fn storage_class(&mut self) -> Option<StorageClass> {
+        self.matches(vec![TokenKind::Static, TokenKind::Extern])
+            .map(|token| match token.token {
+                TokenType::Static => StorageClass::Static,
+                _ => StorageClass::Extern,
+            })
+    }
     fn statement(&mut self) -> Result<Stmt, Error> {
         if self.matches(vec![TokenKind::For]).is_some() {
             return self.for_statement();
@@ -83,7 +110,7 @@ impl Parser {
         if self.matches(vec![TokenKind::While]).is_some() {
             return self.while_statement();
         }
-        if let Some(_) = self.matches(vec![TokenKind::LeftBrace]) {
+        if self.matches(vec![TokenKind::LeftBrace]).is_some() {
             return Ok(Stmt::Block(self.block()?));
         }
         self.expression_statement()
@@ -100,33 +127,33 @@ impl Parser {
         let left_paren = self.consume(TokenKind::LeftParen, "Expect '(' after for-statement")?;
 
         let mut init = None;
-        if let Some(token) = self.matches_type() {
-            init = Some(self.type_declaration(token)?);
+        if let Some(token) = self.matches_type()? {
+            init = Some(self.type_declaration(token, None)?);
         } else if !self.check(TokenKind::Semicolon) {
-            init = Some(self.expression_statement()?)
+            init = Some(vec![self.expression_statement()?])
         } else {
             self.consume(TokenKind::Semicolon, "Expect ';' in for loop")?;
         }
 
         let mut cond = None;
-        if self.matches(vec![TokenKind::Semicolon]) == None {
+        if self.matches(vec![TokenKind::Semicolon]).is_none() {
             cond = Some(self.expression()?);
             self.consume(TokenKind::Semicolon, "Expect ';' after for-condition")?;
         }
 
         let mut inc = None;
-        if self.matches(vec![TokenKind::RightParen]) == None {
+        if self.matches(vec![TokenKind::RightParen]).is_none() {
             inc = Some(self.expression()?);
             self.consume(TokenKind::RightParen, "Expect ')' after for increment")?;
         }
 
         // for loop is syntax sugar for while loop
         let mut body = self.statement()?;
-        if inc != None {
-            body = Stmt::Block(vec![body, Stmt::Expr(inc.unwrap())]);
+        if let Some(inc) = inc {
+            body = Stmt::Block(vec![body, Stmt::Expr(inc)]);
         }
-        if cond != None {
-            body = Stmt::While(left_paren.clone(), cond.unwrap(), Box::new(body));
+        if let Some(cond) = cond {
+            body = Stmt::While(left_paren.clone(), cond, Box::new(body));
         } else {
             // if no condition then condition is true
             body = Stmt::While(
@@ -135,8 +162,9 @@ impl Parser {
                 Box::new(body),
             );
         }
-        if init != None {
-            body = Stmt::Block(vec![init.unwrap(), body]);
+        if let Some(mut init) = init {
+            init.push(body);
+            body = Stmt::Block(init);
         }
 
         Ok(body)
@@ -160,13 +188,13 @@ impl Parser {
             if TokenKind::from(&token.token) == TokenKind::RightBrace {
                 break;
             }
-            statements.push(match self.tokens.peek() {
-                Some(v) => match v.is_type() {
-                    true => self.declaration()?,
-                    false => self.statement()?,
+            match self.tokens.peek() {
+                Some(v) => match v.is_type() || v.is_storage_class() || v.is_qualifier() {
+                    true => statements.extend(self.declaration()?),
+                    false => statements.push(self.statement()?),
                 },
                 None => panic!("empty"),
-            });
+            }
         }
         self.consume(TokenKind::RightBrace, "Expect '}' after Block")?;
         Ok(statements)
@@ -186,9 +214,8 @@ impl Parser {
 
         let then_branch = self.statement()?;
         let mut else_branch = None;
-        match self.matches(vec![TokenKind::Else]) {
-            Some(_) => else_branch = Some(self.statement()?),
-            None => (),
+        if self.matches(vec![TokenKind::Else]).is_some() {
+            else_branch = Some(self.statement()?)
         }
         Ok(Stmt::If(
             keyword,
@@ -197,50 +224,191 @@ impl Parser {
             Box::new(else_branch),
         ))
     }
-    fn parse_arr(&mut self, type_decl: NEWTypes) -> Result<NEWTypes, Error> {
-        if self.matches(vec![TokenKind::LeftBracket]).is_some() {
-            let size = self.consume(
-                TokenKind::Number,
-                "Expect array-size following array-declaration",
-            )?;
+    fn array_dimensions(&mut self) -> Result<Vec<usize>, Error> {
+        let mut dimensions = Vec::new();
+        while let Some(left_bracket) = self.matches(vec![TokenKind::LeftBracket]) {
+            let size = match self.tokens.peek() {
+                Some(t) if t.token == TokenType::RightBracket => {
+                    return Err(Error::new(
+                        t,
+                        "Expect array-size following array-declaration",
+                    ))
+                }
+                _ => const_eval(&self.expression()?),
+            };
             self.consume(
                 TokenKind::RightBracket,
                 "Expect closing ']' after array initialization",
             )?;
 
-            if size.unwrap_num() > 0 {
-                Ok(array_of(self.parse_arr(type_decl)?, size.unwrap_num()))
-            } else {
-                Err(Error::new(&size, "Can't initialize array with size <= 0"))
+            match size {
+                Some(size) if size > 0 => dimensions.push(size as usize),
+                Some(_) => {
+                    return Err(Error::new(
+                        &left_bracket,
+                        "Can't initialize array with size <= 0",
+                    ))
+                }
+                None => {
+                    return Err(Error::new(
+                        &left_bracket,
+                        "Array-size has to be an integer constant-expression",
+                    ))
+                }
             }
-        } else {
-            Ok(type_decl)
         }
+        Ok(dimensions)
     }
-    fn type_declaration(&mut self, mut type_decl: NEWTypes) -> Result<Stmt, Error> {
-        let name = self.consume(
-            TokenKind::Ident,
-            "Expect identifier following type-specifier",
-        )?;
+    // modifiers in the order they apply to the type-specifier: pointers bind looser than
+    // array-dimensions and parameter-lists, unless the pointers are inside of parentheses
+    // int *a[2]     => [Pointer, Array(2)]
+    // int (*a)[2]   => [Array(2), Pointer]
+    // int (*a)(int) => [Function([int]), Pointer]
+    // int (*a(int))(char) => [Function([char]), Pointer, Function([int])]
+    // abstract declarators, as in parameters of prototypes, don't need a name
+    fn declarator_modifiers(
+        &mut self,
+        named: bool,
+    ) -> Result<(Vec<Modifier>, Option<Token>), Error> {
+        let mut modifiers = Vec::new();
+        while self.matches(vec![TokenKind::Star]).is_some() {
+            modifiers.push(Modifier::Pointer(self.qualifiers().0));
+        }
 
-        if self.matches(vec![TokenKind::LeftParen]).is_some() {
-            self.function(type_decl, name)
+        let (inner, name) = if self.matches(vec![TokenKind::LeftParen]).is_some() {
+            let inner = self.declarator_modifiers(named)?;
+            self.consume(TokenKind::RightParen, "Expect ')' after declarator")?;
+            inner
+        } else if named {
+            let name = self.consume(
+                TokenKind::Ident,
+                "Expect identifier following type-specifier",
+            )?;
+            (Vec::new(), Some(name))
         } else {
-            type_decl = self.parse_arr(type_decl)?;
+            (Vec::new(), self.matches(vec![TokenKind::Ident]))
+        };
 
-            if self.matches(vec![TokenKind::Equal]).is_some() {
-                self.var_initialization(name, type_decl)
+        // the last suffix is the innermost type
+        let mut suffixes = Vec::new();
+        loop {
+            if self.check(TokenKind::LeftBracket) {
+                suffixes.extend(self.array_dimensions()?.into_iter().map(Modifier::Array));
+            } else if self.matches(vec![TokenKind::LeftParen]).is_some() {
+                let (params, variadic) = self.params()?;
+                suffixes.push(Modifier::Function(params, variadic));
             } else {
-                // declaration
+                break;
+            }
+        }
+        modifiers.extend(suffixes.into_iter().rev());
+        modifiers.extend(inner);
+
+        Ok((modifiers, name))
+    }
+    // parameter-list of any function-declarator, the names are optional
+    fn params(&mut self) -> Result<(Params, bool), Error> {
+        let mut params = Vec::new();
+        let mut variadic = false;
+
+        if !self.check(TokenKind::RightParen) {
+            loop {
+                // variable arguments can only follow the named parameters
+                if let Some(ellipsis) = self.matches(vec![TokenKind::Ellipsis]) {
+                    if params.is_empty() {
+                        return Err(Error::new(&ellipsis, "Expect named parameter before '...'"));
+                    }
+                    variadic = true;
+                    break;
+                }
+                let specifier = self.type_specifier()?;
+                let (modifiers, name) = self.declarator_modifiers(false)?;
+
+                params.push((decay_param(apply_modifiers(specifier, modifiers)), name));
+                if self.matches(vec![TokenKind::Comma]).is_none() {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenKind::RightParen,
+            "Expect ')' after function parameters",
+        )?;
+
+        // (void) means the function takes no arguments
+        if matches!(&params[..], [(NEWTypes::Primitive(Types::Void), None)]) {
+            params.clear();
+        }
+        Ok((params, variadic))
+    }
+    fn type_specifier(&mut self) -> Result<NEWTypes, Error> {
+        match self.matches_type()? {
+            Some(type_decl) => Ok(type_decl),
+            None => match self.tokens.peek() {
+                Some(actual) => Err(Error::new(
+                    actual,
+                    &format!("Expected type found {}", actual.token),
+                )),
+                None => Err(self.eof_error("Expected type found end of file")),
+            },
+        }
+    }
+    fn type_declaration(
+        &mut self,
+        specifier: NEWTypes,
+        storage_class: Option<StorageClass>,
+    ) -> Result<Vec<Stmt>, Error> {
+        let mut declarations = Vec::new();
+        loop {
+            let (mut modifiers, name) = self.declarator_modifiers(true)?;
+            let name = name.expect("named declarators always have a name");
+
+            // a parameter-list directly following the name declares a function
+            match modifiers.pop() {
+                Some(Modifier::Function(params, variadic)) if declarations.is_empty() => {
+                    let return_type = apply_modifiers(specifier, modifiers);
+                    return Ok(vec![self.function(
+                        return_type,
+                        name,
+                        params,
+                        variadic,
+                        storage_class,
+                    )?]);
+                }
+                Some(Modifier::Function(..)) => {
+                    return Err(Error::new(
+                        &name,
+                        "Functions have to be declared in their own declaration",
+                    ))
+                }
+                modifier => modifiers.extend(modifier),
+            }
+            let type_decl = apply_modifiers(specifier.clone(), modifiers);
+            let has_init = self.matches(vec![TokenKind::Equal]).is_some();
+            declarations.push(match has_init {
+                true => self.var_initialization(name, type_decl, storage_class)?,
+                false => Stmt::DeclareVar(type_decl, name, false, storage_class),
+            });
+
+            // every declarator in the list becomes its own declaration
+            if self.matches(vec![TokenKind::Comma]).is_none() {
                 self.consume(
                     TokenKind::Semicolon,
-                    "Expect ';' after variable declaration",
+                    match has_init {
+                        true => "Expect ';' after variable definition",
+                        false => "Expect ';' after variable declaration",
+                    },
                 )?;
-                Ok(Stmt::DeclareVar(type_decl, name, false))
+                return Ok(declarations);
             }
         }
     }
-    fn var_initialization(&mut self, name: Token, type_decl: NEWTypes) -> Result<Stmt, Error> {
+    fn var_initialization(
+        &mut self,
+        name: Token,
+        type_decl: NEWTypes,
+        storage_class: Option<StorageClass>,
+    ) -> Result<Stmt, Error> {
         match self.matches(vec![TokenKind::LeftBrace]) {
             Some(_) => {
                 let elements = self.initializer_list(&type_decl, name.clone())?;
@@ -251,16 +419,21 @@ impl Parser {
                     true,
                     Expr::new(ExprKind::Ident(name.clone()), ValueKind::Lvalue),
                 );
-
-                self.consume(TokenKind::Semicolon, "Expect ';' after variable definition")?;
-                Ok(Stmt::InitList(type_decl, name, assign_sugar, false))
-            }
-            None => {
-                let r_value = self.expression()?;
-
-                self.consume(TokenKind::Semicolon, "Expect ';' after variable definition")?;
-                Ok(Stmt::InitVar(type_decl, name, r_value, false))
+                Ok(Stmt::InitList(
+                    type_decl,
+                    name,
+                    assign_sugar,
+                    false,
+                    storage_class,
+                ))
             }
+            None => Ok(Stmt::InitVar(
+                type_decl,
+                name,
+                self.expression()?,
+                false,
+                storage_class,
+            )),
         }
     }
     fn initializer_list(&mut self, type_decl: &NEWTypes, token: Token) -> Result<Vec<Expr>, Error> {
@@ -318,49 +491,61 @@ impl Parser {
             ))
         }
     }
-    fn function(&mut self, return_type: NEWTypes, name: Token) -> Result<Stmt, Error> {
-        if matches!(return_type, NEWTypes::Array { .. }) {
-            return Err(Error::new(&name, "function can't return array-type"));
-        }
-        let mut params = Vec::new();
-
-        if !self.check(TokenKind::RightParen) {
-            loop {
-                let mut param_type = match self.matches_type() {
-                    Some(type_decl) => type_decl,
-                    None => {
-                        let actual = self.tokens.peek().expect("Expected Type");
-                        return Err(Error::new(
-                            actual,
-                            &format!("Expected type found {}", actual.token),
-                        ));
-                    }
-                };
-                let name = self.consume(TokenKind::Ident, "Expect identifier after type")?;
-
-                param_type = self.parse_arr(param_type)?;
-                if let NEWTypes::Array { of, .. } = param_type {
-                    param_type = NEWTypes::Pointer(of);
-                }
-
-                params.push((param_type, name));
-                if self.matches(vec![TokenKind::Comma]) == None {
-                    break;
-                }
+    fn function(
+        &mut self,
+        return_type: NEWTypes,
+        name: Token,
+        params: Params,
+        variadic: bool,
+        storage_class: Option<StorageClass>,
+    ) -> Result<Stmt, Error> {
+        match return_type {
+            NEWTypes::Array { .. } => {
+                return Err(Error::new(&name, "function can't return array-type"))
             }
+            NEWTypes::Function { .. } => {
+                return Err(Error::new(&name, "function can't return function-type"))
+            }
+            _ => (),
         }
-        self.consume(
-            TokenKind::RightParen,
-            "Expect ')' after function parameters",
-        )?;
 
         if self.matches(vec![TokenKind::Semicolon]).is_some() {
-            Ok(Stmt::FunctionDeclaration(return_type, name, params))
+            Ok(Stmt::FunctionDeclaration(
+                return_type,
+                name,
+                params,
+                variadic,
+                storage_class,
+            ))
         } else {
+            // only prototypes can leave out the parameter-names
+            let params = params
+                .into_iter()
+                .enumerate()
+                .map(|(i, (type_decl, param))| match param {
+                    Some(param) => Ok((type_decl, param)),
+                    None => Err(Error::new(
+                        &name,
+                        &format!(
+                            "Parameter {} of function-definition '{}' has no name",
+                            i + 1,
+                            name.unwrap_string()
+                        ),
+                    )),
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
             self.consume(TokenKind::LeftBrace, "Expect '{' before function body.")?;
             let body = self.block()?;
 
-            Ok(Stmt::Function(return_type, name, params, body))
+            Ok(Stmt::Function(
+                return_type,
+                name,
+                params,
+                variadic,
+                storage_class,
+                body,
+            ))
         }
     }
 
@@ -656,7 +841,7 @@ impl Parser {
         if !self.check(TokenKind::RightParen) {
             loop {
                 args.push(self.expression()?);
-                if self.matches(vec![TokenKind::Comma]) == None {
+                if self.matches(vec![TokenKind::Comma]).is_none() {
                     break;
                 }
             }
@@ -674,10 +859,12 @@ impl Parser {
     fn primary(&mut self) -> Result<Expr, Error> {
         //TODO: avoid repition
         if let Some(n) = self.matches(vec![TokenKind::Number]) {
-            return Ok(Expr::new(
-                ExprKind::Number(n.unwrap_num()),
-                ValueKind::Rvalue,
-            ));
+            let (n, type_decl) = n.unwrap_num();
+            // numbers default to int in the typechecker, so only long-literals need their type
+            return Ok(Expr {
+                type_decl: (type_decl == Types::Long).then_some(NEWTypes::Primitive(type_decl)),
+                ..Expr::new(ExprKind::Number(n), ValueKind::Rvalue)
+            });
         }
         if let Some(c) = self.matches(vec![TokenKind::CharLit]) {
             return Ok(Expr::new(
@@ -691,6 +878,11 @@ impl Parser {
         if let Some(s) = self.matches(vec![TokenKind::String]) {
             return Ok(Expr::new(ExprKind::String(s), ValueKind::Rvalue));
         }
+        if let Some(token) =
+            self.matches(vec![TokenKind::VaStart, TokenKind::VaArg, TokenKind::VaEnd])
+        {
+            return self.va_builtin(token);
+        }
 
         if self.matches(vec![TokenKind::LeftParen]).is_some() {
             let expr = self.expression()?;
@@ -707,14 +899,45 @@ impl Parser {
                 t,
                 &format!("Expected expression found: {}", t.token),
             )),
-            None => Err(Error {
-                line_index: -1,
-                line_string: "".to_string(),
-                column: -1,
-                msg: "Expected expression found end of file".to_string(),
-            }),
+            None => Err(self.eof_error("Expected expression found end of file")),
         }
     }
+    // va_start(list, last), va_arg(list, type) and va_end(list)
+    fn va_builtin(&mut self, token: Token) -> Result<Expr, Error> {
+        self.consume(
+            TokenKind::LeftParen,
+            &format!("Expect '(' after {}", token.token),
+        )?;
+        let mut args = vec![self.expression()?];
+        let mut type_arg = None;
+
+        match token.token {
+            TokenType::VaStart => {
+                self.consume(TokenKind::Comma, "Expect ',' after va_list argument")?;
+                args.push(self.expression()?);
+            }
+            TokenType::VaArg => {
+                self.consume(TokenKind::Comma, "Expect ',' after va_list argument")?;
+                let specifier = self.type_specifier()?;
+                let (modifiers, _) = self.declarator_modifiers(false)?;
+                type_arg = Some(apply_modifiers(specifier, modifiers));
+            }
+            _ => (),
+        }
+        self.consume(
+            TokenKind::RightParen,
+            &format!("Expect ')' after {} arguments", token.token),
+        )?;
+
+        Ok(Expr::new(
+            ExprKind::Builtin {
+                token,
+                args,
+                type_arg,
+            },
+            ValueKind::Rvalue,
+        ))
+    }
     fn consume(&mut self, token: TokenKind, msg: &str) -> Result<Token, Error> {
         match self.tokens.next() {
             Some(v) => {
@@ -724,12 +947,18 @@ impl Parser {
                     Ok(v)
                 }
             }
-            None => Err(Error {
+            None => Err(self.eof_error(msg)),
+        }
+    }
+    fn eof_error(&self, msg: &str) -> Error {
+        match &self.last {
+            Some(last) => Error::new(last, msg),
+            None => Error {
                 line_index: -1,
                 line_string: "".to_string(),
                 column: -1,
                 msg: msg.to_string(),
-            }),
+            },
         }
     }
     fn check(&mut self, expected: TokenKind) -> bool {
@@ -751,36 +980,105 @@ impl Parser {
         }
         self.tokens.next()
     }
-    fn matches_type(&mut self) -> Option<NEWTypes> {
+    // type-specifier with the qualifiers written before or after it
+    fn matches_type(&mut self) -> Result<Option<NEWTypes>, Error> {
+        let (mut qualifiers, mut restrict) = self.qualifiers();
         match self.tokens.peek() {
+            Some(v) if v.is_type() => (),
+            _ if qualifiers.is_empty() => return Ok(None),
             Some(v) => {
-                if !v.is_type() {
-                    return None;
-                }
+                return Err(Error::new(
+                    v,
+                    &format!("Expected type after qualifiers, found {}", v.token),
+                ))
             }
-            None => return None,
+            None => return Ok(None),
         }
-        let mut type_decl = self
+        // pointers are part of the declarator
+        let type_decl = self
             .tokens
             .next()
             .expect("can only be types because of previous check")
             .into_type();
 
-        while self.matches(vec![TokenKind::Star]).is_some() {
-            type_decl.pointer_to();
+        let (trailing, trailing_restrict) = self.qualifiers();
+        qualifiers = qualifiers.union(trailing);
+        restrict = restrict.or(trailing_restrict);
+
+        if let Some(restrict) = restrict {
+            return Err(Error::new(
+                &restrict,
+                "Only pointer-types can be 'restrict'-qualified",
+            ));
+        }
+        Ok(Some(type_decl.qualify(qualifiers)))
+    }
+    // also returns the 'restrict'-token since it's only valid after a pointer
+    fn qualifiers(&mut self) -> (Qualifiers, Option<Token>) {
+        let mut qualifiers = Qualifiers::default();
+        let mut restrict = None;
+        while let Some(token) = self.matches(vec![
+            TokenKind::Const,
+            TokenKind::Volatile,
+            TokenKind::Restrict,
+        ]) {
+            match token.token {
+                TokenType::Const => qualifiers.is_const = true,
+                TokenType::Volatile => qualifiers.is_volatile = true,
+                _ => {
+                    qualifiers.is_restrict = true;
+                    restrict = Some(token);
+                }
+            }
         }
-        Some(type_decl)
+        (qualifiers, restrict)
     }
 }
 
-fn array_of(type_decl: NEWTypes, size: i32) -> NEWTypes {
+// parameter-types and their optional names
+type Params = Vec<(NEWTypes, Option<Token>)>;
+
+enum Modifier {
+    // qualifiers of the pointer itself
+    Pointer(Qualifiers),
+    Array(usize),
+    // parameters and if the function is variadic
+    Function(Params, bool),
+}
+
+fn apply_modifiers(specifier: NEWTypes, modifiers: Vec<Modifier>) -> NEWTypes {
+    modifiers
+        .into_iter()
+        .fold(specifier, |type_decl, modifier| match modifier {
+            Modifier::Pointer(qualifiers) => {
+                NEWTypes::Pointer(Box::new(type_decl)).qualify(qualifiers)
+            }
+            Modifier::Array(size) => array_of(type_decl, size),
+            Modifier::Function(params, variadic) => NEWTypes::Function {
+                return_type: Box::new(type_decl),
+                params: params.into_iter().map(|(type_decl, _)| type_decl).collect(),
+                variadic,
+            },
+        })
+}
+
+// parameters declared as arrays or functions are passed as pointers
+fn decay_param(type_decl: NEWTypes) -> NEWTypes {
+    match type_decl {
+        NEWTypes::Array { of, .. } => NEWTypes::Pointer(of),
+        NEWTypes::Function { .. } => NEWTypes::Pointer(Box::new(type_decl)),
+        _ => type_decl,
+    }
+}
+
+fn array_of(type_decl: NEWTypes, size: usize) -> NEWTypes {
     NEWTypes::Array {
-        amount: size as usize,
+        amount: size,
         of: Box::new(type_decl),
     }
 }
 
-fn array_element_count(arr: NEWTypes) -> usize {
+pub fn array_element_count(arr: NEWTypes) -> usize {
     if let NEWTypes::Array { amount, of } = arr {
         amount * array_element_count(*of)
     } else {
@@ -790,7 +1088,7 @@ fn array_element_count(arr: NEWTypes) -> usize {
 
 fn list_sugar_assign(
     token: Token,
-    list: &Vec<Expr>,
+    list: &[Expr],
     type_decl: NEWTypes,
     is_outer: bool,
     left: Expr,
@@ -820,13 +1118,13 @@ fn list_sugar_assign(
         {
             list_sugar_assign(
                 token.clone(),
-                &list[i..list.len()].to_vec(),
+                &list[i..list.len()],
                 *of.clone(),
                 false,
                 index_sugar(
                     token.clone(),
                     left.clone(),
-                    Expr::new(ExprKind::Number(arr_i as i32), ValueKind::Rvalue),
+                    Expr::new(ExprKind::Number(arr_i as i64), ValueKind::Rvalue),
                 ),
             )
             .into_iter()
@@ -893,19 +1191,17 @@ mod tests {
     }
     macro_rules! tok_vec {
         ($($token_type:expr),+) => {{
-            let mut v:Vec<Token> = Vec::new();
-            $(v.push(token_default!($token_type));)+
-            v
+            vec![$(token_default!($token_type)),+]
         }}
     }
     #[test]
     fn creates_ast_for_expression() {
         let tokens = tok_vec![
-            TokenType::Number(32),
+            TokenType::Number(32, Types::Int),
             TokenType::Plus,
-            TokenType::Number(1),
+            TokenType::Number(1, Types::Int),
             TokenType::Star,
-            TokenType::Number(2)
+            TokenType::Number(2, Types::Int)
         ];
         let mut p = Parser::new(tokens);
 
@@ -930,31 +1226,31 @@ mod tests {
     #[test]
     fn matches_works_on_enums_with_values() {
         let tokens = vec![
-            token_default!(TokenType::Number(2)),
+            token_default!(TokenType::Number(2, Types::Int)),
             token_default!(TokenType::Plus),
         ];
         let mut p = Parser::new(tokens);
 
         let result = p.matches(vec![TokenKind::Number, TokenKind::String]);
-        let expected = Some(token_default!(TokenType::Number(2)));
+        let expected = Some(token_default!(TokenType::Number(2, Types::Int)));
         assert_eq!(result, expected);
     }
     #[test]
     fn nested_groupings() {
         let tokens = tok_vec![
             TokenType::LeftParen,
-            TokenType::Number(3),
+            TokenType::Number(3, Types::Int),
             TokenType::Slash,
             TokenType::LeftParen,
-            TokenType::Number(6),
+            TokenType::Number(6, Types::Int),
             TokenType::Minus,
-            TokenType::Number(7),
+            TokenType::Number(7, Types::Int),
             TokenType::RightParen,
             TokenType::Star,
-            TokenType::Number(2),
+            TokenType::Number(2, Types::Int),
             TokenType::RightParen,
             TokenType::Plus,
-            TokenType::Number(1)
+            TokenType::Number(1, Types::Int)
         ];
         let mut p = Parser::new(tokens);
 
@@ -1010,4 +1306,167 @@ mod tests {
 
         assert_eq!(result.unwrap(), expected);
     }
+    #[test]
+    fn declarator_list_with_pointers_and_arrays() {
+        let tokens = crate::scanner::Scanner::new("int *a[2], (*b)[3], c, **d;")
+            .scan_token()
+            .unwrap();
+        let declarations: Vec<(String, NEWTypes)> = Parser::new(tokens)
+            .parse_statements()
+            .unwrap()
+            .into_iter()
+            .map(|s| match s {
+                Stmt::DeclareVar(type_decl, name, ..) => (name.unwrap_string(), type_decl),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let int = NEWTypes::Primitive(Types::Int);
+        let pointer = |to: NEWTypes| NEWTypes::Pointer(Box::new(to));
+        assert_eq!(
+            declarations,
+            vec![
+                ("a".to_string(), array_of(pointer(int.clone()), 2)),
+                ("b".to_string(), pointer(array_of(int.clone(), 3))),
+                ("c".to_string(), int.clone()),
+                ("d".to_string(), pointer(pointer(int))),
+            ]
+        );
+    }
+    #[test]
+    fn function_pointer_declarators() {
+        let tokens = crate::scanner::Scanner::new(
+            "int (*a)(int, char *), (*b[2])(void), *(*c)(int (*)(int));",
+        )
+        .scan_token()
+        .unwrap();
+        let declarations: Vec<NEWTypes> = Parser::new(tokens)
+            .parse_statements()
+            .unwrap()
+            .into_iter()
+            .map(|s| match s {
+                Stmt::DeclareVar(type_decl, ..) => type_decl,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let int = NEWTypes::Primitive(Types::Int);
+        let pointer = |to: NEWTypes| NEWTypes::Pointer(Box::new(to));
+        let function = |return_type: NEWTypes, params: Vec<NEWTypes>| NEWTypes::Function {
+            return_type: Box::new(return_type),
+            params,
+            variadic: false,
+        };
+        assert_eq!(
+            declarations,
+            vec![
+                pointer(function(
+                    int.clone(),
+                    vec![int.clone(), pointer(NEWTypes::Primitive(Types::Char))]
+                )),
+                array_of(pointer(function(int.clone(), vec![])), 2),
+                pointer(function(
+                    pointer(int.clone()),
+                    vec![pointer(function(int.clone(), vec![int]))]
+                )),
+            ]
+        );
+    }
+    #[test]
+    fn function_declarators() {
+        let tokens = crate::scanner::Scanner::new(
+            "int f(int, char *s);\nint main(void);\nint (*choose(int op))(int, int);",
+        )
+        .scan_token()
+        .unwrap();
+        let declarations: Vec<_> = Parser::new(tokens)
+            .parse_statements()
+            .unwrap()
+            .into_iter()
+            .map(|s| match s {
+                Stmt::FunctionDeclaration(return_type, _, params, ..) => (
+                    return_type,
+                    params
+                        .into_iter()
+                        .map(|(type_decl, name)| (type_decl, name.map(|n| n.unwrap_string())))
+                        .collect::<Vec<_>>(),
+                ),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let int = NEWTypes::Primitive(Types::Int);
+        let pointer = |to: NEWTypes| NEWTypes::Pointer(Box::new(to));
+        assert_eq!(
+            declarations,
+            vec![
+                (
+                    int.clone(),
+                    vec![
+                        (int.clone(), None),
+                        (
+                            pointer(NEWTypes::Primitive(Types::Char)),
+                            Some("s".to_string())
+                        )
+                    ]
+                ),
+                (int.clone(), vec![]),
+                (
+                    pointer(NEWTypes::Function {
+                        return_type: Box::new(int.clone()),
+                        params: vec![int.clone(), int.clone()],
+                        variadic: false,
+                    }),
+                    vec![(int, Some("op".to_string()))]
+                ),
+            ]
+        );
+    }
+    #[test]
+    fn qualified_declarators() {
+        let tokens = crate::scanner::Scanner::new(
+            "const char *a; char *const b; int const c[2]; volatile int *restrict d;",
+        )
+        .scan_token()
+        .unwrap();
+        let declarations: Vec<NEWTypes> = Parser::new(tokens)
+            .parse_statements()
+            .unwrap()
+            .into_iter()
+            .map(|s| match s {
+                Stmt::DeclareVar(type_decl, ..) => type_decl,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let qualified = |q: Qualifiers, to: NEWTypes| NEWTypes::Qualified(q, Box::new(to));
+        let pointer = |to: NEWTypes| NEWTypes::Pointer(Box::new(to));
+        let is_const = Qualifiers {
+            is_const: true,
+            ..Default::default()
+        };
+        let char_type = NEWTypes::Primitive(Types::Char);
+        let int = NEWTypes::Primitive(Types::Int);
+        assert_eq!(
+            declarations,
+            vec![
+                pointer(qualified(is_const, char_type.clone())),
+                qualified(is_const, pointer(char_type)),
+                array_of(qualified(is_const, int.clone()), 2),
+                qualified(
+                    Qualifiers {
+                        is_restrict: true,
+                        ..Default::default()
+                    },
+                    pointer(qualified(
+                        Qualifiers {
+                            is_volatile: true,
+                            ..Default::default()
+                        },
+                        int
+                    ))
+                ),
+            ]
+        );
+    }
 }
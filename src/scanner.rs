@@ -1,4 +1,4 @@
-use crate::common::{error::*, token::*};
+use crate::common::{error::*, token::*, types::Types};
 use std::collections::HashMap;
 use std::iter::Peekable;
 use std::str::Chars;
@@ -10,6 +10,9 @@ pub struct Scanner<'a> {
     pub column: i32,
     keywords: HashMap<&'a str, TokenType>,
     err: bool,
+    // comments which haven't been attached to a token yet, after scanning these are the ones at
+    // the end of the file
+    pub comments: Vec<Comment>,
 }
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Self {
@@ -22,6 +25,7 @@ impl<'a> Scanner<'a> {
             line: 1,
             column: 1,
             err: false,
+            comments: Vec::new(),
             keywords: HashMap::from([
                 ("void", TokenType::Void),
                 ("int", TokenType::Int),
@@ -32,6 +36,15 @@ impl<'a> Scanner<'a> {
                 ("for", TokenType::For),
                 ("while", TokenType::While),
                 ("return", TokenType::Return),
+                ("static", TokenType::Static),
+                ("extern", TokenType::Extern),
+                ("const", TokenType::Const),
+                ("volatile", TokenType::Volatile),
+                ("restrict", TokenType::Restrict),
+                ("va_list", TokenType::VaList),
+                ("va_start", TokenType::VaStart),
+                ("va_arg", TokenType::VaArg),
+                ("va_end", TokenType::VaEnd),
             ]),
         }
     }
@@ -43,32 +56,65 @@ impl<'a> Scanner<'a> {
         }
     }
     fn add_token(&mut self, tokens: &mut Vec<Token>, current_token: TokenType) {
+        let len = Self::get_token_len(&current_token);
+        self.add_literal(tokens, current_token, len);
+    }
+    // literals can contain escape-sequences so their length in the source has to be passed in
+    fn add_literal(&mut self, tokens: &mut Vec<Token>, current_token: TokenType, len: i32) {
         tokens.push(Token {
-            token: current_token.clone(),
+            token: current_token,
             line_index: self.line,
             column: self.column,
             line_string: self.raw_source[(self.line - 1) as usize].clone(),
+            comments: std::mem::take(&mut self.comments),
         });
-        self.column += Self::get_token_len(current_token);
+        self.column += len;
     }
-    fn get_token_len(token: TokenType) -> i32 {
+    fn get_token_len(token: &TokenType) -> i32 {
         match token {
             TokenType::BangEqual
             | TokenType::EqualEqual
             | TokenType::GreaterEqual
-            | TokenType::LessEqual => 2,
-            TokenType::String(s) => (s.len() + 2) as i32,
+            | TokenType::LessEqual
+            | TokenType::MinusEqual
+            | TokenType::MinusMinus
+            | TokenType::PlusEqual
+            | TokenType::PlusPlus
+            | TokenType::SlashEqual
+            | TokenType::StarEqual
+            | TokenType::ModEqual
+            | TokenType::AmpEqual
+            | TokenType::AmpAmp
+            | TokenType::PipeEqual
+            | TokenType::PipePipe
+            | TokenType::XorEqual
+            | TokenType::GreaterGreater
+            | TokenType::LessLess => 2,
+            TokenType::GreaterGreaterEqual | TokenType::LessLessEqual | TokenType::Ellipsis => 3,
             TokenType::Ident(s) => s.len() as i32,
             TokenType::Int | TokenType::For => 3,
-            TokenType::Char | TokenType::Else | TokenType::Long => 4,
-            TokenType::While => 5,
+            TokenType::Char | TokenType::Else | TokenType::Long | TokenType::Void => 4,
+            TokenType::While | TokenType::Const => 5,
             TokenType::If => 2,
-            TokenType::Return => 6,
-            TokenType::Number(n) => n.to_string().len() as i32,
+            TokenType::Return
+            | TokenType::Static
+            | TokenType::Extern
+            | TokenType::VaArg
+            | TokenType::VaEnd => 6,
+            TokenType::VaList => 7,
+            TokenType::VaStart | TokenType::Volatile | TokenType::Restrict => 8,
             _ => 1,
         }
     }
     pub fn scan_token(&mut self) -> Result<Vec<Token>, Vec<Error>> {
+        let (tokens, errors) = self.scan();
+        match self.err {
+            true => Err(errors),
+            false => Ok(tokens),
+        }
+    }
+    // scans the whole source, keeping the valid tokens even if errors occurred
+    pub fn scan(&mut self) -> (Vec<Token>, Vec<Error>) {
         let mut errors: Vec<Error> = Vec::new();
         let mut tokens: Vec<Token> = Vec::new();
 
@@ -81,7 +127,15 @@ impl<'a> Scanner<'a> {
                 '{' => self.add_token(&mut tokens, TokenType::LeftBrace),
                 '}' => self.add_token(&mut tokens, TokenType::RightBrace),
                 ',' => self.add_token(&mut tokens, TokenType::Comma),
-                '.' => self.add_token(&mut tokens, TokenType::Dot),
+                '.' => {
+                    let mut ahead = self.source.clone();
+                    if ahead.next() == Some('.') && ahead.next() == Some('.') {
+                        self.source.nth(1);
+                        self.add_token(&mut tokens, TokenType::Ellipsis);
+                    } else {
+                        self.add_token(&mut tokens, TokenType::Dot);
+                    }
+                }
                 ';' => self.add_token(&mut tokens, TokenType::Semicolon),
                 '~' => self.add_token(&mut tokens, TokenType::Tilde),
                 '-' => {
@@ -166,13 +220,24 @@ impl<'a> Scanner<'a> {
 
                 '/' => {
                     if self.matches('/') {
-                        // there has to be a better way to consume the iter without the first \n
-                        while self
-                            .source
-                            .by_ref()
-                            .next_if(|&c| c != '\n' && c != '\0')
-                            .is_some()
-                        {}
+                        let mut text = String::from("//");
+                        while let Some(c) = self.source.next_if(|&c| c != '\n' && c != '\0') {
+                            text.push(c);
+                        }
+                        self.comments.push(Comment {
+                            text: text.trim_end().to_string(),
+                            line_index: self.line,
+                            trailing: tokens.last().is_some_and(|t| t.line_index == self.line),
+                        });
+                    } else if self.matches('*') {
+                        let trailing = tokens.last().is_some_and(|t| t.line_index == self.line);
+                        match self.block_comment(trailing) {
+                            Ok(comment) => self.comments.push(comment),
+                            Err(e) => {
+                                self.err = true;
+                                errors.push(e)
+                            }
+                        }
                     } else {
                         let token = self.match_next('=', TokenType::SlashEqual, TokenType::Slash);
                         self.add_token(&mut tokens, token);
@@ -185,14 +250,18 @@ impl<'a> Scanner<'a> {
                 }
 
                 '"' => match self.string() {
-                    Ok(string) => self.add_token(&mut tokens, TokenType::String(string.clone())),
+                    Ok((string, len)) => {
+                        self.add_literal(&mut tokens, TokenType::String(string), len)
+                    }
                     Err(e) => {
                         self.err = true;
                         errors.push(e)
                     }
                 },
                 '\'' => match self.char_lit() {
-                    Ok(char) => self.add_token(&mut tokens, TokenType::CharLit(char as i8)),
+                    Ok((char, len)) => {
+                        self.add_literal(&mut tokens, TokenType::CharLit(char as i8), len)
+                    }
                     Err(e) => {
                         self.err = true;
                         errors.push(e)
@@ -201,16 +270,29 @@ impl<'a> Scanner<'a> {
 
                 _ => {
                     if c.is_ascii_digit() {
-                        // Number
+                        // Number, includes prefix and suffix so that they can be validated
                         let mut num = String::new();
                         // have to prepend already consumned char
                         num.push(c);
 
-                        while let Some(digit) = self.source.by_ref().next_if(|c| c.is_ascii_digit())
+                        while let Some(c) = self
+                            .source
+                            .by_ref()
+                            .next_if(|c| c.is_ascii_alphanumeric() || *c == '_')
                         {
-                            num.push(digit);
+                            num.push(c);
+                        }
+                        let len = num.len() as i32;
+                        match self.number(&num) {
+                            Ok((n, type_decl)) => {
+                                self.add_literal(&mut tokens, TokenType::Number(n, type_decl), len)
+                            }
+                            Err(e) => {
+                                self.err = true;
+                                errors.push(e);
+                                self.column += len;
+                            }
                         }
-                        self.add_token(&mut tokens, TokenType::Number(num.parse::<i32>().unwrap()));
                     } else if c.is_alphabetic() || c == '_' {
                         // Identifier
                         let mut value = String::new();
@@ -241,10 +323,7 @@ impl<'a> Scanner<'a> {
                 }
             }
         }
-        match self.err {
-            true => Err(errors),
-            false => Ok(tokens),
-        }
+        (tokens, errors)
     }
 
     fn matches(&mut self, expected: char) -> bool {
@@ -259,53 +338,237 @@ impl<'a> Scanner<'a> {
         self.source.next();
         true
     }
-    fn char_lit(&mut self) -> Result<char, Error> {
-        let mut last_char = '\0';
-        let result = self
-            .source
-            .by_ref()
-            .take_while(|c| {
-                last_char = *c;
-                *c != '\''
+    // consumes everything up to and including the closing '*/', which can span multiple lines
+    fn block_comment(&mut self, trailing: bool) -> Result<Comment, Error> {
+        let start = Error::new_scan_error(self, "unterminated block comment");
+        let mut text = String::from("/*");
+        self.column += 2;
+
+        while let Some(c) = self.source.next() {
+            text.push(c);
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                '*' if self.matches('/') => {
+                    text.push('/');
+                    self.column += 2;
+                    return Ok(Comment {
+                        text,
+                        line_index: start.line_index,
+                        trailing,
+                    });
+                }
+                _ => self.column += 1,
+            }
+        }
+        Err(start)
+    }
+    // evaluates an integer-literal and chooses its type, as there are no unsigned types
+    // a 'u'-suffix is accepted but doesn't change the type
+    fn number(&self, literal: &str) -> Result<(i64, Types), Error> {
+        let lower = literal.to_ascii_lowercase();
+        let (radix, name, digits) = if let Some(digits) = lower.strip_prefix("0x") {
+            (16, "hexadecimal", digits)
+        } else if let Some(digits) = lower.strip_prefix("0b") {
+            (2, "binary", digits)
+        } else if lower.len() > 1 && lower.starts_with('0') {
+            (8, "octal", &lower[1..])
+        } else {
+            (10, "decimal", lower.as_str())
+        };
+
+        let digit_count = digits
+            .chars()
+            .take_while(|c| match radix {
+                16 => c.is_ascii_hexdigit(),
+                _ => c.is_ascii_digit(),
             })
-            .collect::<String>();
-        if last_char != '\'' {
-            return Err(Error::new_scan_error(self, "unterminated char literal"));
-        } else if result.len() != 1 {
+            .count();
+        let (digits, suffix) = digits.split_at(digit_count);
+        let suffix = &literal[literal.len() - suffix.len()..];
+
+        if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
             return Err(Error::new_scan_error(
                 self,
-                "char literal must contain single character",
+                &format!("invalid digit '{}' in {} constant", c, name),
             ));
-        } else if !result.is_ascii() {
+        }
+        if digits.is_empty() && radix != 8 {
             return Err(Error::new_scan_error(
                 self,
-                "char literal must be valid ascii value",
+                &format!("{} constant has no digits", name),
             ));
         }
+        // suffixes are case-insensitive but 'll' has to be either all lower- or uppercase
+        let mixed_case = suffix.contains("lL") || suffix.contains("Ll");
+        let (is_long, is_unsigned) = match suffix.to_ascii_lowercase().as_str() {
+            "" => Some((false, false)),
+            "u" => Some((false, true)),
+            "l" | "ll" if !mixed_case => Some((true, false)),
+            "ul" | "lu" | "ull" | "llu" if !mixed_case => Some((true, true)),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            Error::new_scan_error(
+                self,
+                &format!("invalid suffix '{}' on integer constant", suffix),
+            )
+        })?;
+        let unsupported = |type_decl: &str| {
+            Error::new_scan_error(
+                self,
+                &format!(
+                    "{} constant has type '{}' which isn't supported",
+                    name, type_decl
+                ),
+            )
+        };
+        if is_unsigned {
+            return Err(unsupported(if is_long {
+                "unsigned long"
+            } else {
+                "unsigned int"
+            }));
+        }
+
+        // the octal '0' has no digits after the prefix
+        let value = u64::from_str_radix(if digits.is_empty() { "0" } else { digits }, radix)
+            .map_err(|_| {
+                Error::new_scan_error(
+                    self,
+                    "integer literal is too large to be represented in any integer type",
+                )
+            })?;
+        // decimal literals have the first type out of int and long that can represent their
+        // value, the others can also become unsigned int or unsigned long
+        let type_decl = if !is_long && value <= i32::MAX as u64 {
+            Types::Int
+        } else if radix != 10 && !is_long && value <= u32::MAX as u64 {
+            return Err(unsupported("unsigned int"));
+        } else if value <= i64::MAX as u64 {
+            Types::Long
+        } else if radix != 10 {
+            return Err(unsupported("unsigned long"));
+        } else {
+            return Err(Error::new_scan_error(
+                self,
+                "integer literal is too large to be represented in a signed integer type",
+            ));
+        };
+        let value = value as i64;
 
-        Ok(result.chars().next().unwrap())
+        Ok((value, type_decl))
     }
 
-    fn string(&mut self) -> Result<String, Error> {
-        let mut last_char = '\0';
-        let result = self
-            .source
-            .by_ref()
-            .take_while(|c| {
-                last_char = *c;
-                *c != '"'
-            })
-            .collect::<String>();
-        if last_char != '"' {
-            return Err(Error {
-                line_index: self.line,
-                line_string: self.raw_source[(self.line - 1) as usize].clone(),
-                column: self.column,
-                msg: "Unterminated string".to_string(),
-            });
+    // returns the byte-value of the literal and its length in the source
+    fn char_lit(&mut self) -> Result<(u8, i32), Error> {
+        let (bytes, len) = self.literal('\'', "unterminated char literal")?;
+        match bytes.as_slice() {
+            [byte] => Ok((*byte, len)),
+            _ => Err(Error::new_scan_error(
+                self,
+                "char literal must contain single character",
+            )),
         }
+    }
 
-        Ok(result)
+    // every char in the returned string represents a single byte, so that strings
+    // can still be used as keys for their labels
+    fn string(&mut self) -> Result<(String, i32), Error> {
+        let (bytes, len) = self.literal('"', "Unterminated string")?;
+        Ok((bytes.into_iter().map(char::from).collect(), len))
+    }
+
+    // decodes the literal up to the closing delimiter, the length includes both quotes
+    fn literal(&mut self, delimiter: char, unterminated: &str) -> Result<(Vec<u8>, i32), Error> {
+        let mut bytes = Vec::new();
+        let mut len = 1;
+        // an invalid escape still consumes the rest of the literal
+        let mut invalid_escape = None;
+        loop {
+            // a newline is left for the main loop so that line-numbers stay correct
+            match self.source.next_if(|c| *c != '\n') {
+                Some(c) if c == delimiter => {
+                    return match invalid_escape {
+                        Some(e) => {
+                            self.column += len + 1;
+                            Err(e)
+                        }
+                        None => Ok((bytes, len + 1)),
+                    }
+                }
+                Some('\\') => {
+                    len += 1;
+                    match self.escape_sequence(&mut len) {
+                        Ok(byte) => bytes.push(byte),
+                        Err(e) => {
+                            invalid_escape.get_or_insert(e);
+                        }
+                    }
+                }
+                Some(c) => {
+                    len += 1;
+                    let mut buf = [0; 4];
+                    bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+                }
+                None => return Err(Error::new_scan_error(self, unterminated)),
+            }
+        }
+    }
+    fn escape_sequence(&mut self, len: &mut i32) -> Result<u8, Error> {
+        let c = match self.source.next_if(|c| *c != '\n') {
+            Some(c) => c,
+            None => return Err(Error::new_scan_error(self, "incomplete escape sequence")),
+        };
+        *len += 1;
+        let simple = match c {
+            'n' => Some(b'\n'),
+            't' => Some(b'\t'),
+            'r' => Some(b'\r'),
+            'a' => Some(7),
+            'b' => Some(8),
+            'f' => Some(12),
+            'v' => Some(11),
+            '\\' | '\'' | '"' | '?' => Some(c as u8),
+            _ => None,
+        };
+        if let Some(byte) = simple {
+            return Ok(byte);
+        }
+
+        // octal escapes take at most 3 digits, hex escapes take as many as there are
+        let (radix, mut value, max_digits) = match c {
+            '0'..='7' => (8, c.to_digit(8).unwrap(), 2),
+            'x' => (16, 0, usize::MAX),
+            _ => {
+                return Err(Error::new_scan_error(
+                    self,
+                    &format!("unknown escape sequence '\\{}'", c),
+                ))
+            }
+        };
+        let mut digits = 0;
+        while digits < max_digits {
+            match self.source.next_if(|d| d.is_digit(radix)) {
+                Some(d) => {
+                    value = value
+                        .saturating_mul(radix)
+                        .saturating_add(d.to_digit(radix).unwrap());
+                    digits += 1;
+                    *len += 1;
+                }
+                None => break,
+            }
+        }
+        if c == 'x' && digits == 0 {
+            return Err(Error::new_scan_error(
+                self,
+                "\\x used with no following hex digits",
+            ));
+        }
+        u8::try_from(value).map_err(|_| Error::new_scan_error(self, "escape sequence out of range"))
     }
 }
 
@@ -332,14 +595,14 @@ mod tests {
         assert_eq!(result, expected);
     }
     #[test]
-    fn ignores_comments() {
+    fn keeps_comments_as_trivia() {
         let source = "// this is a    comment\n\n!this";
         let mut scanner = Scanner::new(source);
         let result = match scanner.scan_token() {
             Ok(v) => v,
             Err(e) => panic!("test"),
         };
-        let expected = vec![
+        let mut expected = vec![
             Token::new(TokenType::Bang, 3, 1, "!this".to_string()),
             Token::new(
                 TokenType::Ident("this".to_string()),
@@ -348,6 +611,11 @@ mod tests {
                 "!this".to_string(),
             ),
         ];
+        expected[0].comments.push(Comment {
+            text: "// this is a    comment".to_string(),
+            line_index: 1,
+            trailing: false,
+        });
         assert_eq!(result, expected);
     }
     #[test]
@@ -359,11 +627,26 @@ mod tests {
             Err(e) => panic!("test"),
         };
         let expected = vec![
-            Token::new(TokenType::Number(3), 1, 1, "3 + 1 / 4".to_string()),
+            Token::new(
+                TokenType::Number(3, Types::Int),
+                1,
+                1,
+                "3 + 1 / 4".to_string(),
+            ),
             Token::new(TokenType::Plus, 1, 3, "3 + 1 / 4".to_string()),
-            Token::new(TokenType::Number(1), 1, 5, "3 + 1 / 4".to_string()),
+            Token::new(
+                TokenType::Number(1, Types::Int),
+                1,
+                5,
+                "3 + 1 / 4".to_string(),
+            ),
             Token::new(TokenType::Slash, 1, 7, "3 + 1 / 4".to_string()),
-            Token::new(TokenType::Number(4), 1, 9, "3 + 1 / 4".to_string()),
+            Token::new(
+                TokenType::Number(4, Types::Int),
+                1,
+                9,
+                "3 + 1 / 4".to_string(),
+            ),
         ];
         assert_eq!(result, expected);
     }
@@ -376,11 +659,26 @@ mod tests {
             Err(e) => panic!("test"),
         };
         let expected = vec![
-            Token::new(TokenType::Number(300), 1, 1, "300 - 11 * 41".to_string()),
+            Token::new(
+                TokenType::Number(300, Types::Int),
+                1,
+                1,
+                "300 - 11 * 41".to_string(),
+            ),
             Token::new(TokenType::Minus, 1, 5, "300 - 11 * 41".to_string()),
-            Token::new(TokenType::Number(11), 1, 7, "300 - 11 * 41".to_string()),
+            Token::new(
+                TokenType::Number(11, Types::Int),
+                1,
+                7,
+                "300 - 11 * 41".to_string(),
+            ),
             Token::new(TokenType::Star, 1, 10, "300 - 11 * 41".to_string()),
-            Token::new(TokenType::Number(41), 1, 12, "300 - 11 * 41".to_string()),
+            Token::new(
+                TokenType::Number(41, Types::Int),
+                1,
+                12,
+                "300 - 11 * 41".to_string(),
+            ),
         ];
         assert_eq!(result, expected);
     }
@@ -421,6 +719,64 @@ mod tests {
         assert_eq!(result, expected);
     }
     #[test]
+    fn tracks_position_after_block_comments() {
+        let source = "a /* one\n * two */ b /**/c\n/* three */";
+        let mut scanner = Scanner::new(source);
+        let result = scanner.scan_token().unwrap();
+
+        let positions: Vec<(i32, i32)> = result.iter().map(|t| (t.line_index, t.column)).collect();
+        assert_eq!(positions, vec![(1, 1), (2, 11), (2, 17)]);
+        assert_eq!(
+            result[1].comments,
+            vec![Comment {
+                text: "/* one\n * two */".to_string(),
+                line_index: 1,
+                trailing: true,
+            }]
+        );
+        assert_eq!(result[2].comments[0].text, "/**/");
+        assert_eq!(scanner.comments[0].text, "/* three */");
+    }
+    #[test]
+    fn scans_ellipsis_and_va_keywords() {
+        let source = "(int a, ...) va_list va_start";
+        let mut scanner = Scanner::new(source);
+        let result = scanner.scan_token().unwrap();
+
+        let tokens: Vec<(TokenType, i32)> =
+            result.into_iter().map(|t| (t.token, t.column)).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenType::LeftParen, 1),
+                (TokenType::Int, 2),
+                (TokenType::Ident("a".to_string()), 6),
+                (TokenType::Comma, 7),
+                (TokenType::Ellipsis, 9),
+                (TokenType::RightParen, 12),
+                (TokenType::VaList, 14),
+                (TokenType::VaStart, 22),
+            ]
+        );
+    }
+    #[test]
+    fn errors_on_unterminated_block_comment() {
+        let source = "int a;\n  /* never\nclosed";
+        let mut scanner = Scanner::new(source);
+
+        let result = match scanner.scan_token() {
+            Ok(v) => panic!(),
+            Err(e) => e,
+        };
+        let expected = vec![Error {
+            line_index: 2,
+            line_string: "  /* never".to_string(),
+            column: 3,
+            msg: "unterminated block comment".to_string(),
+        }];
+        assert_eq!(result, expected);
+    }
+    #[test]
     fn errors_on_unterminated_string() {
         let source = "int some = \"this is a string";
         let mut scanner = Scanner::new(source);
@@ -479,7 +835,7 @@ mod tests {
                 "while (val >= 12) {*p = val}".to_string(),
             ),
             Token::new(
-                TokenType::Number(12),
+                TokenType::Number(12, Types::Int),
                 2,
                 15,
                 "while (val >= 12) {*p = val}".to_string(),
@@ -592,7 +948,12 @@ mod tests {
                 "int ä = 123".to_string(),
             ),
             Token::new(TokenType::Equal, 2, 8, "int ä = 123".to_string()), // ä len is 2 but thats fine because its the same when indexing
-            Token::new(TokenType::Number(123), 2, 10, "int ä = 123".to_string()),
+            Token::new(
+                TokenType::Number(123, Types::Int),
+                2,
+                10,
+                "int ä = 123".to_string(),
+            ),
         ];
         assert_eq!(result, expected);
     }
@@ -670,4 +1031,80 @@ mod tests {
         }];
         assert_eq!(result, expected);
     }
+    #[test]
+    fn decodes_escape_sequences() {
+        let source = r#"'\n' "a\"\x41\101\0" '\xff';"#;
+        let result = Scanner::new(source).scan_token().unwrap();
+        let expected = vec![
+            Token::new(TokenType::CharLit(b'\n' as i8), 1, 1, source.to_string()),
+            Token::new(
+                TokenType::String("a\"AA\0".to_string()),
+                1,
+                6,
+                source.to_string(),
+            ),
+            Token::new(TokenType::CharLit(-1), 1, 22, source.to_string()),
+            Token::new(TokenType::Semicolon, 1, 28, source.to_string()),
+        ];
+        assert_eq!(result, expected);
+    }
+    #[test]
+    fn errors_on_invalid_escape_sequences() {
+        let source = r#""\q" '\x' "\400" 1"#;
+        let (tokens, errors) = Scanner::new(source).scan();
+        let errors: Vec<(i32, &str)> = errors.iter().map(|e| (e.column, e.msg.as_str())).collect();
+        assert_eq!(
+            errors,
+            vec![
+                (1, "unknown escape sequence '\\q'"),
+                (6, "\\x used with no following hex digits"),
+                (11, "escape sequence out of range")
+            ]
+        );
+        // the rest of an invalid literal is still consumed
+        assert_eq!(
+            tokens.iter().map(|t| t.token.clone()).collect::<Vec<_>>(),
+            vec![TokenType::Number(1, Types::Int)]
+        );
+    }
+    #[test]
+    fn scans_integer_literals() {
+        let source = "0x1F 017 0b101 0 7L 2147483648 0x100000000 10u 0x80000000 0xffffffffffffffff";
+        let (tokens, errors) = Scanner::new(source).scan();
+        let literals: Vec<(TokenType, i32)> =
+            tokens.into_iter().map(|t| (t.token, t.column)).collect();
+        assert_eq!(
+            literals,
+            vec![
+                (TokenType::Number(31, Types::Int), 1),
+                (TokenType::Number(15, Types::Int), 6),
+                (TokenType::Number(5, Types::Int), 10),
+                (TokenType::Number(0, Types::Int), 16),
+                (TokenType::Number(7, Types::Long), 18),
+                (TokenType::Number(2147483648, Types::Long), 21),
+                (TokenType::Number(4294967296, Types::Long), 32),
+            ]
+        );
+        // literals which would need an unsigned type are rejected until those exist
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.column, e.msg.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    44,
+                    "decimal constant has type 'unsigned int' which isn't supported"
+                ),
+                (
+                    48,
+                    "hexadecimal constant has type 'unsigned int' which isn't supported"
+                ),
+                (
+                    59,
+                    "hexadecimal constant has type 'unsigned long' which isn't supported"
+                )
+            ]
+        );
+    }
 }
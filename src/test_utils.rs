@@ -0,0 +1,34 @@
+// runs the front-end on source-code so unit-tests of later stages don't have to,
+// every stage is expected to succeed
+use crate::common::{error::*, stmt::*};
+use crate::ir::{instr::Module, lower::Lowering};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::typechecker::{Std, TypeChecker};
+
+pub fn parse(source: &str) -> Vec<Stmt> {
+    let tokens = Scanner::new(source).scan_token().unwrap();
+    Parser::new(tokens).parse().unwrap()
+}
+
+pub fn typecheck(source: &str) -> Vec<Stmt> {
+    let mut statements = parse(source);
+    TypeChecker::new(Std::C99).check(&mut statements).unwrap();
+    statements
+}
+
+pub fn lower(source: &str) -> Module {
+    let mut statements = parse(source);
+    let mut typechecker = TypeChecker::new(Std::C99);
+    let const_labels = typechecker.check(&mut statements).unwrap();
+
+    Lowering::new(const_labels, false, false).lower(&statements)
+}
+
+// the messages of all errors and warnings the typechecker finds
+pub fn diagnose(source: &str, std: Std) -> (Vec<String>, Vec<String>) {
+    let (errors, warnings) = TypeChecker::new(std).diagnose(&mut parse(source));
+
+    let msgs = |diagnostics: Vec<Error>| diagnostics.into_iter().map(|e| e.msg).collect();
+    (msgs(errors), msgs(warnings))
+}
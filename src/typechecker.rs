@@ -1,7 +1,15 @@
-use crate::codegen::codegen::align;
 use crate::common::{environment::*, error::*, expr::*, stmt::*, token::*, types::*};
+use crate::fold::*;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+// language standard selected with -std
+#[derive(PartialEq, Clone, Copy)]
+pub enum Std {
+    // allows calling undeclared functions
+    C89,
+    C99,
+}
 
 #[derive(PartialEq)]
 enum Scope {
@@ -9,16 +17,28 @@ enum Scope {
     Block,
     Function(String, NEWTypes), // function name and return type
 }
+// linkage of a global variable or function, decided by its first declaration
+#[derive(Clone, Copy, PartialEq)]
+enum Linkage {
+    Internal,
+    External,
+}
 pub struct TypeChecker {
     errors: Vec<Error>,
+    warnings: Vec<Error>,
     scope: Vec<Scope>,
     env: Environment<NEWTypes>,
     global_env: Environment<NEWTypes>,
     returns_all_paths: bool,
-    func_stack_size: HashMap<String, usize>, // typechecker passes info about how many stack allocation there are in a function
     found_main: bool,
     const_labels: HashMap<String, usize>,
     const_label_count: usize,
+    std: Std,
+    linkages: HashMap<String, Linkage>,
+    // globals which already have an initializer, tentative definitions without one can be repeated
+    initialized_vars: HashSet<String>,
+    // elements of an init-list are assigned to even if they're read-only
+    initializing: bool,
 }
 macro_rules! cast {
     ($ex:expr,$new_type:expr,$kind:ident) => {
@@ -31,35 +51,53 @@ macro_rules! cast {
         }
     };
 }
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new(Std::C99)
+    }
+}
 impl TypeChecker {
-    pub fn new() -> Self {
+    pub fn new(std: Std) -> Self {
         TypeChecker {
             errors: vec![],
+            warnings: vec![],
             env: Environment::new(None),
             global_env: Environment::new(None),
             scope: vec![Scope::Global],
             returns_all_paths: false,
             found_main: false,
-            func_stack_size: HashMap::new(),
             const_labels: HashMap::new(),
             const_label_count: 0,
+            std,
+            linkages: HashMap::new(),
+            initialized_vars: HashSet::new(),
+            initializing: false,
         }
     }
     pub fn check(
         &mut self,
         statements: &mut Vec<Stmt>,
-    ) -> Result<(&HashMap<String, usize>, &HashMap<String, usize>), Vec<Error>> {
+    ) -> Result<&HashMap<String, usize>, Vec<Error>> {
+        let (errors, warnings) = self.diagnose(statements);
+        for warning in warnings.iter() {
+            warning.print_warning();
+        }
+        if !errors.is_empty() {
+            Err(errors)
+        } else {
+            Ok(&self.const_labels)
+        }
+    }
+    // returns errors and warnings instead of printing them
+    pub fn diagnose(&mut self, statements: &mut Vec<Stmt>) -> (Vec<Error>, Vec<Error>) {
         if let Err(e) = self.check_statements(statements) {
             self.errors.push(e);
             // synchronize
         }
-        if !self.errors.is_empty() {
-            Err(self.errors.clone())
-        } else if !self.found_main {
-            Err(vec![Error::missing_entrypoint()])
-        } else {
-            Ok((&self.func_stack_size, &self.const_labels))
+        if self.errors.is_empty() && !self.found_main {
+            self.errors.push(Error::missing_entrypoint());
         }
+        (self.errors.clone(), self.warnings.clone())
     }
     fn check_statements(&mut self, statements: &mut Vec<Stmt>) -> Result<(), Error> {
         for s in statements {
@@ -69,20 +107,26 @@ impl TypeChecker {
     }
     fn visit(&mut self, statement: &mut Stmt) -> Result<(), Error> {
         match statement {
-            Stmt::DeclareVar(type_decl, var_name, is_global) => {
-                self.declare_var(type_decl, var_name, is_global)
+            Stmt::DeclareVar(type_decl, var_name, is_global, storage_class) => {
+                self.declare_var(type_decl, var_name, is_global, storage_class)
             }
-            Stmt::InitVar(type_decl, name, ref mut expr, is_global) => {
-                self.init_var(type_decl.clone(), name, expr, is_global)
+            Stmt::InitVar(type_decl, name, ref mut expr, is_global, storage_class) => {
+                self.init_var(type_decl.clone(), name, expr, is_global, storage_class)
             }
-            Stmt::InitList(type_decl, var_name, exprs, is_global) => {
-                self.init_list(type_decl, var_name, exprs, is_global)
+            Stmt::InitList(type_decl, var_name, exprs, is_global, storage_class) => {
+                self.init_list(type_decl, var_name, exprs, is_global, storage_class)
             }
-            Stmt::Function(return_type, name, params, body) => {
-                self.function_definition(return_type, name, params.clone(), body)
-            }
-            Stmt::FunctionDeclaration(return_type, name, params) => {
-                self.function_declaration(return_type, name, params)
+            Stmt::Function(return_type, name, params, variadic, storage_class, body) => self
+                .function_definition(
+                    return_type,
+                    name,
+                    params.clone(),
+                    *variadic,
+                    storage_class,
+                    body,
+                ),
+            Stmt::FunctionDeclaration(return_type, name, params, variadic, storage_class) => {
+                self.function_declaration(return_type, name, params, *variadic, storage_class)
             }
             Stmt::Return(keyword, ref mut value) => self.return_statement(keyword, value),
             Stmt::Expr(ref mut expr) => match self.expr_type(expr) {
@@ -125,29 +169,116 @@ impl TypeChecker {
         type_decl: &NEWTypes,
         var_name: &Token,
         is_global: &mut bool,
+        storage_class: &mut Option<StorageClass>,
     ) -> Result<(), Error> {
         let name = var_name.unwrap_string();
 
-        if self.env.current.vars.contains_key(&name) {
-            return Err(Error::new(
-                var_name,
-                &format!("Redefinition of variable '{}'", var_name.unwrap_string()),
-            ));
-        }
+        self.check_redeclaration(var_name, type_decl, storage_class, false)?;
         if type_decl.is_void() {
             return Err(Error::new(
                 var_name,
                 &format!("Can't assign to 'void' {}", var_name.unwrap_string()),
             ));
         }
+        Self::not_function(type_decl, var_name)?;
         if *self.scope.last().unwrap() == Scope::Global {
             *is_global = true;
-        } else {
-            self.increment_stack_size(type_decl)?;
         }
         self.env.declare_var(name, type_decl.clone());
         Ok(())
     }
+    // only globals can be declared multiple times, as long as at most one declaration has an initializer
+    fn check_redeclaration(
+        &mut self,
+        var_name: &Token,
+        type_decl: &NEWTypes,
+        storage_class: &mut Option<StorageClass>,
+        has_initializer: bool,
+    ) -> Result<(), Error> {
+        let name = var_name.unwrap_string();
+        let redefinition = Error::new(var_name, &format!("Redefinition of variable '{}'", name));
+
+        if *self.scope.last().unwrap() != Scope::Global {
+            if self.env.current.vars.contains_key(&name) {
+                return Err(redefinition);
+            }
+            // block-scope extern-declarations refer to a global and so take part in its linkage
+            if *storage_class == Some(StorageClass::Extern) {
+                self.check_linkage(var_name, storage_class, false)?;
+            }
+            return Ok(());
+        }
+        self.check_linkage(var_name, storage_class, false)?;
+
+        match self.env.current.vars.get(&name) {
+            Some(existing) if existing != type_decl => Err(Error::new(
+                var_name,
+                &format!(
+                    "Conflicting types for '{}': expected '{}', found '{}'",
+                    name, existing, type_decl
+                ),
+            )),
+            _ if has_initializer && !self.initialized_vars.insert(name) => Err(redefinition),
+            _ => Ok(()),
+        }
+    }
+    // declarations of the same global have to agree on its linkage, extern-declarations and
+    // function-declarations without storage-class take on the linkage of a previous declaration
+    fn check_linkage(
+        &mut self,
+        name_token: &Token,
+        storage_class: &mut Option<StorageClass>,
+        is_function: bool,
+    ) -> Result<(), Error> {
+        let name = name_token.unwrap_string();
+        let previous = self.linkages.get(&name).copied();
+        let linkage = match (*storage_class, previous) {
+            (Some(StorageClass::Static), _) => Linkage::Internal,
+            (Some(StorageClass::Extern), Some(previous)) => previous,
+            (None, Some(previous)) if is_function => previous,
+            _ => Linkage::External,
+        };
+
+        match (previous, linkage) {
+            (Some(Linkage::External), Linkage::Internal) => Err(Error::new(
+                name_token,
+                &format!(
+                    "Static declaration of '{}' follows non-static declaration",
+                    name
+                ),
+            )),
+            (Some(Linkage::Internal), Linkage::External) => Err(Error::new(
+                name_token,
+                &format!(
+                    "Non-static declaration of '{}' follows static declaration",
+                    name
+                ),
+            )),
+            _ => {
+                // later stages only look at the storage-class of the declaration itself
+                if linkage == Linkage::Internal && storage_class.is_none() {
+                    *storage_class = Some(StorageClass::Static);
+                }
+                self.linkages.insert(name, linkage);
+                Ok(())
+            }
+        }
+    }
+    // only pointers to functions can be stored in variables
+    fn not_function(type_decl: &NEWTypes, var_name: &Token) -> Result<(), Error> {
+        if type_decl.is_func() {
+            Err(Error::new(
+                var_name,
+                &format!(
+                    "Can't declare variable '{}' with function-type '{}'",
+                    var_name.unwrap_string(),
+                    type_decl
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
     fn check_type_compatibility(
         &self,
         token: &Token,
@@ -167,35 +298,31 @@ impl TypeChecker {
         &mut self,
         type_decl: &mut NEWTypes,
         var_name: &Token,
-        exprs: &mut Vec<Expr>,
+        exprs: &mut [Expr],
         is_global: &mut bool,
+        storage_class: &mut Option<StorageClass>,
     ) -> Result<(), Error> {
         let name = var_name.unwrap_string();
-        if self.env.current.vars.contains_key(&name) {
-            return Err(Error::new(
-                var_name,
-                &format!("Redefinition of variable '{}'", name),
-            ));
-        }
+        self.local_extern_init(var_name, *storage_class)?;
+        self.check_redeclaration(var_name, type_decl, storage_class, true)?;
 
         self.env.init_var(name, type_decl.clone());
-        if *self.scope.last().unwrap() == Scope::Global {
+
+        // check all assigns first so that they're folded
+        self.initializing = true;
+        let result = exprs
+            .iter_mut()
+            .try_for_each(|e| self.expr_type(e).map(|_| ()));
+        self.initializing = false;
+        result?;
+
+        if let Some(msg) = self.static_storage(*storage_class) {
             for e in exprs.iter() {
                 if !is_constant(e) {
-                    return Err(Error::new(
-                        var_name,
-                        "Global variables can only be initialized to compile-time constants",
-                    ));
+                    return Err(Error::new(var_name, msg));
                 }
             }
-            *is_global = true;
-        } else {
-            self.increment_stack_size(type_decl)?;
-        }
-
-        // then check all assigns
-        for e in exprs {
-            self.expr_type(e)?;
+            *is_global = *self.scope.last().unwrap() == Scope::Global;
         }
 
         Ok(())
@@ -206,65 +333,79 @@ impl TypeChecker {
         var_name: &Token,
         expr: &mut Expr,
         is_global: &mut bool,
+        storage_class: &mut Option<StorageClass>,
     ) -> Result<(), Error> {
         let name = var_name.unwrap_string();
         let mut value_type = self.expr_type(expr)?;
 
-        if self.env.current.vars.contains_key(&name) {
-            return Err(Error::new(
-                var_name,
-                &format!("Redefinition of variable '{}'", name),
-            ));
+        self.local_extern_init(var_name, *storage_class)?;
+        self.check_redeclaration(var_name, &type_decl, storage_class, true)?;
+        Self::not_function(&type_decl, var_name)?;
+        if value_type.is_func() {
+            crate::arr_decay!(value_type, expr, var_name);
         }
+
         // char[] s = "literal" is valid
         match (type_decl.clone(), &expr.kind) {
             (NEWTypes::Array { of, .. }, ExprKind::String(..))
-                if matches!(*of, NEWTypes::Primitive(Types::Char)) =>
-            {
-                ()
-            }
+                if matches!(of.unqualified(), NEWTypes::Primitive(Types::Char)) => {}
             _ => self.check_type_compatibility(var_name, &type_decl, &value_type)?,
         }
 
         self.maybe_cast(&type_decl, &value_type, expr);
 
-        if *self.scope.last().unwrap() == Scope::Global {
+        if let Some(msg) = self.static_storage(*storage_class) {
             if !is_constant(expr) {
-                return Err(Error::new(
-                    var_name,
-                    "Global variables can only be initialized to compile-time constants",
-                ));
+                return Err(Error::new(var_name, msg));
             }
-            *is_global = true;
+            *is_global = *self.scope.last().unwrap() == Scope::Global;
         } else {
-            crate::arr_decay!(value_type, expr, var_name);
-            self.increment_stack_size(&type_decl)?;
+            // only the expression is decayed, the variable keeps its declared type
+            crate::arr_decay!(&value_type => expr, var_name);
         }
         self.env.init_var(name, type_decl);
 
         Ok(())
     }
+    // variables with static storage are initialized before the program runs, returns the
+    // error-message for when their initializer isn't constant
+    fn static_storage(&self, storage_class: Option<StorageClass>) -> Option<&'static str> {
+        if *self.scope.last().unwrap() == Scope::Global {
+            Some("Global variables can only be initialized to compile-time constants")
+        } else if storage_class == Some(StorageClass::Static) {
+            Some("Static variables can only be initialized to compile-time constants")
+        } else {
+            None
+        }
+    }
+    fn local_extern_init(
+        &self,
+        var_name: &Token,
+        storage_class: Option<StorageClass>,
+    ) -> Result<(), Error> {
+        if *self.scope.last().unwrap() != Scope::Global
+            && storage_class == Some(StorageClass::Extern)
+        {
+            Err(Error::new(
+                var_name,
+                &format!(
+                    "Can't initialize local 'extern' variable '{}'",
+                    var_name.unwrap_string()
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
     fn maybe_cast(&self, type_decl: &NEWTypes, other_type: &NEWTypes, expr: &mut Expr) {
+        // the converted value isn't qualified, only the object it's stored in
+        let type_decl = type_decl.unqualified();
         match other_type.size().cmp(&type_decl.size()) {
             Ordering::Less => cast!(expr, type_decl.clone(), CastUp),
             Ordering::Greater => cast!(expr, type_decl.clone(), CastDown),
             Ordering::Equal => (),
         }
     }
-    fn increment_stack_size(&mut self, type_decl: &NEWTypes) -> Result<(), Error> {
-        match find_function(&self.scope) {
-            Some(Scope::Function(name, _)) => {
-                *self.func_stack_size.get_mut(name).unwrap() += type_decl.size();
-                *self.func_stack_size.get_mut(name).unwrap() =
-                    align(self.func_stack_size[name], type_decl);
-                Ok(())
-            }
-            _ => {
-                // if we're not inside a function we can only be in global scope
-                Ok(())
-            }
-        }
-    }
     fn if_statement(
         &mut self,
         keyword: &Token,
@@ -297,19 +438,34 @@ impl TypeChecker {
         &mut self,
         return_type: &NEWTypes,
         name_token: &Token,
-        params: &Vec<(NEWTypes, Token)>,
+        params: &[(NEWTypes, Option<Token>)],
+        variadic: bool,
+        storage_class: &mut Option<StorageClass>,
     ) -> Result<(), Error> {
         let name = &name_token.unwrap_string();
+        if *self.scope.last().unwrap() != Scope::Global
+            && *storage_class == Some(StorageClass::Static)
+        {
+            return Err(Error::new(
+                name_token,
+                &format!("Invalid storage-class 'static' for function '{}'", name),
+            ));
+        }
+        self.check_linkage(name_token, storage_class, true)?;
         if let Some(f) = self.global_env.get_func(name, FunctionKind::Declaration) {
-            self.cmp_decl(name_token, f, return_type, params)?;
+            self.cmp_decl(name_token, f, return_type, params, variadic)?;
         }
         if let Some(f) = self.global_env.get_func(name, FunctionKind::DefDeclaration) {
-            self.cmp_decl(name_token, f, return_type, params)?;
+            self.cmp_decl(name_token, f, return_type, params, variadic)?;
         }
         self.global_env.declare_func(
             return_type.clone(),
             name,
-            params.clone(),
+            params
+                .iter()
+                .map(|(type_decl, _)| type_decl.clone())
+                .collect(),
+            variadic,
             FunctionKind::Declaration,
         );
         Ok(())
@@ -319,6 +475,8 @@ impl TypeChecker {
         return_type: &NEWTypes,
         name_token: &Token,
         params: Vec<(NEWTypes, Token)>,
+        variadic: bool,
+        storage_class: &mut Option<StorageClass>,
         body: &mut Vec<Stmt>,
     ) -> Result<(), Error> {
         if *self.scope.last().unwrap() != Scope::Global {
@@ -327,6 +485,7 @@ impl TypeChecker {
                 "Can only define functions in global scope",
             ));
         }
+        self.check_linkage(name_token, storage_class, true)?;
         let name = name_token.unwrap_string();
         if name == "main" {
             self.found_main = true;
@@ -342,12 +501,20 @@ impl TypeChecker {
             ));
         } else if let Some(f) = self.global_env.get_func(&name, FunctionKind::Declaration) {
             // compare function_definition with declaration and see if they match
-            self.cmp_decl(name_token, f, return_type, &params)?;
+            let params: Vec<_> = params
+                .iter()
+                .map(|(type_decl, name)| (type_decl.clone(), Some(name.clone())))
+                .collect();
+            self.cmp_decl(name_token, f, return_type, &params, variadic)?;
         } else {
             self.global_env.declare_func(
                 return_type.clone(),
                 &name,
-                params.clone(),
+                params
+                    .iter()
+                    .map(|(type_decl, _)| type_decl.clone())
+                    .collect(),
+                variadic,
                 FunctionKind::DefDeclaration,
             );
         }
@@ -357,10 +524,7 @@ impl TypeChecker {
             .push(Scope::Function(name.clone(), return_type.clone()));
         let mut env = Environment::new(Some(Box::new(self.env.clone()))); // create new scope for function body
 
-        // initialize stack size for current function-scope
-        self.func_stack_size.insert(name.clone(), 0);
         for (type_decl, name) in params.iter().by_ref() {
-            self.increment_stack_size(type_decl)?; // add params to stack-size
             env.init_var(name.unwrap_string(), type_decl.clone()) // initialize params in local scope
         }
 
@@ -370,9 +534,6 @@ impl TypeChecker {
         self.main_returns_int(name_token, return_type)?;
         self.implicit_return_main(name_token, body);
 
-        // align function stack by 16Bytes
-        *self.func_stack_size.get_mut(&name).unwrap() = align_by(self.func_stack_size[&name], 16);
-
         if !return_type.is_void() && !self.returns_all_paths {
             Err(Error::new(
                 name_token,
@@ -402,7 +563,11 @@ impl TypeChecker {
 
             body.push(Stmt::Return(
                 name_token.clone(),
-                Some(Expr::new(ExprKind::Number(0), ValueKind::Rvalue)),
+                Some(Expr {
+                    kind: ExprKind::Number(0),
+                    type_decl: Some(NEWTypes::Primitive(Types::Int)),
+                    value_kind: ValueKind::Rvalue,
+                }),
             ));
         }
     }
@@ -411,7 +576,8 @@ impl TypeChecker {
         name_token: &Token,
         declaration: &Function,
         return_type: &NEWTypes,
-        params: &Vec<(NEWTypes, Token)>,
+        params: &[(NEWTypes, Option<Token>)],
+        variadic: bool,
     ) -> Result<(), Error> {
         if declaration.return_type != *return_type {
             Err(Error::new(
@@ -421,16 +587,22 @@ impl TypeChecker {
                     declaration.return_type, return_type
                 ),
             ))
+        } else if declaration.variadic != variadic {
+            Err(Error::new(
+                name_token,
+                "Mismatched variable arguments in function-declarations",
+            ))
         } else if declaration.arity() != params.len() {
             Err(Error::new(name_token,
                 &format!("Mismatched number of parameters in function-declarations: expected {}, found {}",
                     declaration.arity(),params.len())))
         } else {
             for (i, (types, token)) in params.iter().enumerate() {
-                if *types != declaration.params[i].0 {
-                    return Err(Error::new(token,
+                if *types != declaration.params[i] {
+                    // unnamed parameters are reported at the function-name
+                    return Err(Error::new(token.as_ref().unwrap_or(name_token),
                         &format!("Mismatched parameter-types in function-declarations: expected '{}', found '{}'",
-                            declaration.params[i].0,types)));
+                            declaration.params[i],types)));
                 }
             }
             Ok(())
@@ -483,13 +655,17 @@ impl TypeChecker {
             }
             ExprKind::Unary { token, right } => self.evaluate_unary(token, right)?,
             ExprKind::Grouping { expr } => self.evaluate_grouping(expr)?,
-            ExprKind::Number(_) => NEWTypes::Primitive(Types::Int),
+            // long-literals are typed by the parser, folded constants keep their type
+            ExprKind::Number(_) => ast
+                .type_decl
+                .clone()
+                .unwrap_or(NEWTypes::Primitive(Types::Int)),
             ExprKind::CharLit(_) => NEWTypes::Primitive(Types::Char),
             ExprKind::String(token) => self.string(token.unwrap_string())?,
             ExprKind::Logical { left, token, right } => {
                 self.evaluate_logical(left, token, right)?
             }
-            ExprKind::Ident(token) => self.env.get_var(token)?,
+            ExprKind::Ident(token) => self.ident(token)?,
             ExprKind::Assign {
                 l_expr,
                 token,
@@ -515,12 +691,21 @@ impl TypeChecker {
                 token,
                 by_amount,
             } => self.evaluate_postunary(token, left, by_amount)?,
+            ExprKind::Builtin {
+                token,
+                args,
+                type_arg,
+            } => self.evaluate_builtin(token, args, type_arg)?,
             ExprKind::CastUp { .. } => unimplemented!("explicit casts"),
             ExprKind::CastDown { .. } => unimplemented!("explicit casts"),
             ExprKind::ScaleUp { .. } => unreachable!("is only used in codegen"),
             ExprKind::ScaleDown { .. } => unreachable!("is only used in codegen"),
         });
-        Ok(ast.type_decl.clone().unwrap())
+        let type_decl = ast.type_decl.clone().unwrap();
+
+        fold(ast, &mut self.warnings);
+
+        Ok(type_decl)
     }
     fn evaluate_postunary(
         &mut self,
@@ -535,6 +720,7 @@ impl TypeChecker {
         } else if expr.value_kind == ValueKind::Rvalue {
             return Err(Error::new(token, "Can't increment Rvalues"));
         }
+        self.check_read_only(token, expr)?;
 
         // scale depending on type-size
         if let NEWTypes::Pointer(inner) = &operand {
@@ -543,8 +729,55 @@ impl TypeChecker {
 
         Ok(operand)
     }
+    fn evaluate_builtin(
+        &mut self,
+        token: &Token,
+        args: &mut [Expr],
+        type_arg: &Option<NEWTypes>,
+    ) -> Result<NEWTypes, Error> {
+        // va_list decays into a pointer to its first member
+        let mut list_type = self.expr_type(&mut args[0])?;
+        crate::arr_decay!(list_type, args[0], token);
+        if list_type != NEWTypes::Pointer(Box::new(NEWTypes::Primitive(Types::Long))) {
+            return Err(Error::new(
+                token,
+                &format!(
+                    "Expected 'va_list' argument to {}, found '{}'",
+                    token.token, list_type
+                ),
+            ));
+        }
+
+        match token.token {
+            TokenType::VaStart => {
+                let variadic = match find_function(&self.scope) {
+                    Some(Scope::Function(name, _)) => self
+                        .function_type(name)
+                        .is_some_and(|function| function.variadic),
+                    _ => false,
+                };
+                if !variadic {
+                    return Err(Error::new(
+                        token,
+                        "Can't use 'va_start' in function with fixed arguments",
+                    ));
+                }
+                self.expr_type(&mut args[1])?;
+
+                Ok(NEWTypes::Primitive(Types::Void))
+            }
+            TokenType::VaArg => match type_arg.clone().unwrap() {
+                t if t.is_void() || matches!(t, NEWTypes::Array { .. }) => Err(Error::new(
+                    token,
+                    &format!("Can't read variable argument of type '{}'", t),
+                )),
+                t => Ok(t.unqualified().clone()),
+            },
+            _ => Ok(NEWTypes::Primitive(Types::Void)),
+        }
+    }
     fn string(&mut self, data: String) -> Result<NEWTypes, Error> {
-        let len = data.len() + 1; // extra byte for \0-Terminator
+        let len = data.chars().count() + 1; // extra byte for \0-Terminator
         self.const_labels
             .insert(data, create_label(&mut self.const_label_count));
 
@@ -570,11 +803,18 @@ impl TypeChecker {
             line_string: token.line_string.clone(),
             line_index: token.line_index,
             column: token.column,
+            comments: Vec::new(),
         };
 
         // can ignore scale-down because ptr -= ptr is a type-error
         let r_type = self.evaluate_binary(&mut tmp, bin_token, r_expr)?.0;
 
+        if matches!(bin_token.token, TokenType::Slash | TokenType::Mod)
+            && constant_value(r_expr) == Some(0)
+        {
+            self.warnings.push(Error::new(token, "Division by zero"));
+        }
+
         // have to clone r_expr so that change from evaluate_binary isn't overwritten
         let type_decl = self.assign_var(l_expr, l_type, token, &mut r_expr.clone(), r_type)?;
 
@@ -598,6 +838,9 @@ impl TypeChecker {
         if l_expr.value_kind != ValueKind::Lvalue {
             return Err(Error::new(token, "Expect Lvalue left of assignment"));
         }
+        if !self.initializing {
+            self.check_read_only(token, l_expr)?;
+        }
 
         crate::arr_decay!(r_type, r_expr, token);
 
@@ -606,18 +849,117 @@ impl TypeChecker {
 
         Ok(l_type)
     }
+    // qualifiers of the object an lvalue designates, expression-types don't carry them
+    fn lvalue_qualifiers(&self, expr: &Expr) -> Qualifiers {
+        match &expr.kind {
+            ExprKind::Ident(token) => self
+                .env
+                .get_var(token)
+                .map_or(Qualifiers::default(), |t| t.qualifiers()),
+            // arrays are still annotated with their type before decaying
+            ExprKind::Unary { token, right } if token.token == TokenType::Star => {
+                match right.type_decl.as_ref().map(|t| t.unqualified()) {
+                    Some(NEWTypes::Pointer(to) | NEWTypes::Array { of: to, .. }) => to.qualifiers(),
+                    _ => Qualifiers::default(),
+                }
+            }
+            ExprKind::Grouping { expr } => self.lvalue_qualifiers(expr),
+            _ => Qualifiers::default(),
+        }
+    }
+    fn check_read_only(&self, token: &Token, l_expr: &Expr) -> Result<(), Error> {
+        if !self.lvalue_qualifiers(l_expr).is_const {
+            return Ok(());
+        }
+        Err(Error::new(
+            token,
+            &match &l_expr.kind {
+                ExprKind::Ident(name) => {
+                    format!("Can't modify read-only variable '{}'", name.unwrap_string())
+                }
+                _ => "Can't modify read-only location".to_string(),
+            },
+        ))
+    }
 
+    // identifiers which aren't variables can still designate a function
+    fn ident(&self, token: &Token) -> Result<NEWTypes, Error> {
+        // the value of a variable isn't qualified, only the variable itself
+        let var_type = self.env.get_var(token).map(|t| t.unqualified().clone());
+        var_type.or_else(|e| {
+            self.function_type(&token.unwrap_string())
+                .map(|function| function.type_decl())
+                .ok_or(e)
+        })
+    }
+    fn function_type(&self, name: &str) -> Option<&Function> {
+        self.global_env
+            .get_func(name, FunctionKind::DefDeclaration)
+            .or_else(|| self.global_env.get_func(name, FunctionKind::Declaration))
+    }
     fn evaluate_call(
         &mut self,
         left_paren: &Token,
         callee: &mut Expr,
-        args: &mut Vec<Expr>,
+        args: &mut [Expr],
     ) -> Result<NEWTypes, Error> {
         let func_name = match &callee.kind {
-            ExprKind::Ident(func_name) => func_name,
-            _ => return Err(Error::new(left_paren, "function-name has to be identifier")),
+            ExprKind::Ident(func_name) => Some(func_name.unwrap_string()),
+            _ => None,
         };
+        let mut implicit = false;
+        if let (ExprKind::Ident(token), Some(name)) = (&callee.kind, &func_name) {
+            if self.env.get_var(token).is_err() && self.function_type(name).is_none() {
+                if self.std != Std::C89 {
+                    return Err(Error::new(
+                        left_paren,
+                        &format!("no function {} exists", name),
+                    ));
+                }
+                self.warnings.push(Error::new(
+                    token,
+                    &format!("implicit declaration of function '{}'", name),
+                ));
+                implicit = true;
+            }
+        }
 
+        // can call functions directly or through function-pointers
+        let callee_type = match implicit {
+            // undeclared functions are assumed to return int and take any arguments
+            true => {
+                let implicit_type = NEWTypes::Function {
+                    return_type: Box::new(NEWTypes::Primitive(Types::Int)),
+                    params: vec![],
+                    variadic: true,
+                };
+                callee.type_decl = Some(implicit_type.clone());
+                implicit_type
+            }
+            false => self.expr_type(callee)?,
+        };
+        let function_type = match callee_type {
+            NEWTypes::Pointer(to) if to.is_func() => *to,
+            _ => callee_type,
+        };
+        let (return_type, params, variadic) = match function_type {
+            NEWTypes::Function {
+                return_type,
+                params,
+                variadic,
+            } => (return_type.unqualified().clone(), params, variadic),
+            _ => {
+                return Err(Error::new(
+                    left_paren,
+                    &format!(
+                        "called object of type '{}' is not a function or function-pointer",
+                        function_type
+                    ),
+                ))
+            }
+        };
+
+        // arguments get the default promotions, which also apply to the variable arguments
         let mut arg_types: Vec<NEWTypes> = Vec::new();
         for expr in args.iter_mut() {
             let mut t = self.expr_type(expr)?;
@@ -627,46 +969,45 @@ impl TypeChecker {
             arg_types.push(t);
         }
 
-        match (
-            self.global_env
-                .current
-                .func_def_decl
-                .get(&func_name.unwrap_string()),
-            self.global_env
-                .current
-                .func_decl
-                .get(&func_name.unwrap_string()),
-        ) {
-            (None, None) => Err(Error::new(
+        if params.len() == args.len() || (variadic && args.len() > params.len()) {
+            self.args_and_params_match(left_paren, &params, arg_types)?;
+            Ok(return_type)
+        } else {
+            let expected = match variadic {
+                true => format!("at least {}", params.len()),
+                false => params.len().to_string(),
+            };
+            Err(Error::new(
                 left_paren,
-                &format!("no function {} exists", func_name.unwrap_string()),
-            )),
-            (Some(function), None) | (None, Some(function)) | (Some(function), Some(_)) => {
-                if function.arity() == args.len() {
-                    self.args_and_params_match(left_paren, &function.params, arg_types)?;
-                    Ok(function.return_type.clone())
-                } else {
-                    Err(Error::new(
-                        left_paren,
-                        &format!(
-                            "at '{}': expected {} argument(s) found {}",
-                            func_name.unwrap_string(),
-                            function.arity(),
-                            args.len()
-                        ),
-                    ))
-                }
-            }
+                &match func_name {
+                    Some(name) => format!(
+                        "at '{}': expected {} argument(s) found {}",
+                        name,
+                        expected,
+                        args.len()
+                    ),
+                    None => format!("expected {} argument(s) found {}", expected, args.len()),
+                },
+            ))
         }
     }
     fn args_and_params_match(
         &self,
         left_paren: &Token,
-        params: &[(NEWTypes, Token)],
+        params: &[NEWTypes],
         args: Vec<NEWTypes>,
     ) -> Result<(), Error> {
         for (i, type_decl) in args.iter().enumerate() {
-            self.check_type_compatibility(left_paren, &params[i].0, type_decl)?;
+            match params.get(i) {
+                Some(param) => self.check_type_compatibility(left_paren, param, type_decl)?,
+                None if type_decl.is_void() => {
+                    return Err(Error::new(
+                        left_paren,
+                        "Can't pass 'void' as variable argument",
+                    ))
+                }
+                None => (),
+            }
         }
         Ok(())
     }
@@ -682,18 +1023,27 @@ impl TypeChecker {
     fn is_valid_bin(token: &Token, left_type: &NEWTypes, right_type: &NEWTypes) -> bool {
         match (&left_type, &right_type) {
             (NEWTypes::Primitive(Types::Void), _) | (_, NEWTypes::Primitive(Types::Void)) => false,
-            (NEWTypes::Pointer(_), NEWTypes::Pointer(_)) => {
-                if left_type.type_compatible(right_type) {
-                    token.token == TokenType::Minus
-                        || token.token == TokenType::EqualEqual
-                        || token.token == TokenType::BangEqual
+            (NEWTypes::Pointer(to), NEWTypes::Pointer(_)) => {
+                // pointers to differently qualified types can still be compared
+                if left_type.type_compatible(right_type) || right_type.type_compatible(left_type) {
+                    match token.token {
+                        // functions have no size so there is no arithmetic on their pointers
+                        TokenType::Minus => !to.is_func(),
+                        TokenType::EqualEqual
+                        | TokenType::BangEqual
+                        | TokenType::Less
+                        | TokenType::LessEqual
+                        | TokenType::Greater
+                        | TokenType::GreaterEqual => true,
+                        _ => false,
+                    }
                 } else {
                     false
                 }
             }
-            (_, NEWTypes::Pointer(_)) => token.token == TokenType::Plus,
-            (NEWTypes::Pointer(_), _) => {
-                token.token == TokenType::Plus || token.token == TokenType::Minus
+            (_, NEWTypes::Pointer(to)) => token.token == TokenType::Plus && !to.is_func(),
+            (NEWTypes::Pointer(to), _) => {
+                (token.token == TokenType::Plus || token.token == TokenType::Minus) && !to.is_func()
             }
             _ => true,
         }
@@ -787,9 +1137,15 @@ impl TypeChecker {
                 Ok((right_type, None))
             }
             Ordering::Equal => match (&left_type, &right_type) {
-                (NEWTypes::Pointer(inner), NEWTypes::Pointer(_)) => {
+                // only the difference of two pointers is scaled, comparisons just compare addresses
+                (NEWTypes::Pointer(inner), NEWTypes::Pointer(_))
+                    if token.token == TokenType::Minus =>
+                {
                     Ok((NEWTypes::Primitive(Types::Long), Some(inner.size())))
                 }
+                (NEWTypes::Pointer(_), NEWTypes::Pointer(_)) => {
+                    Ok((NEWTypes::Primitive(Types::Int), None))
+                }
                 _ => Ok((left_type, None)),
             },
         }
@@ -831,7 +1187,8 @@ impl TypeChecker {
                             ),
                         ));
                     }
-                    NEWTypes::Primitive(Types::Int)
+                    // long operands aren't truncated
+                    right_type
                 }
                 _ => unreachable!(), // ++a or --a are evaluated as compound assignment
             })
@@ -844,8 +1201,9 @@ impl TypeChecker {
         expr: &mut Expr,
     ) -> Result<NEWTypes, Error> {
         if expr.value_kind == ValueKind::Lvalue {
+            let qualifiers = self.lvalue_qualifiers(expr);
             Self::lval_to_rval(expr);
-            Ok(NEWTypes::Pointer(Box::new(type_decl)))
+            Ok(NEWTypes::Pointer(Box::new(type_decl.qualify(qualifiers))))
         } else {
             Err(Error::new(token, "can't call '&' on r-value"))
         }
@@ -858,7 +1216,7 @@ impl TypeChecker {
     ) -> Result<NEWTypes, Error> {
         if let Some(inner) = type_decl.deref_at() {
             Self::rval_to_lval(expr);
-            Ok(inner)
+            Ok(inner.unqualified().clone())
         } else {
             Err(Error::new(
                 token,
@@ -921,14 +1279,21 @@ fn find_function(scopes: &[Scope]) -> Option<&Scope> {
 // returns true if expression is known at compile-time
 fn is_constant(expr: &Expr) -> bool {
     match expr.kind {
-        ExprKind::String(_)
-        | ExprKind::Number(_)
-        | ExprKind::CharLit(_)
-        | ExprKind::CastUp { .. }
-        | ExprKind::CastDown { .. } => true,
+        ExprKind::String(_) => true,
+        ExprKind::CastUp { expr: ref inner } | ExprKind::CastDown { expr: ref inner }
+            if matches!(inner.kind, ExprKind::String(_)) =>
+        {
+            true
+        }
         ExprKind::Assign { ref r_expr, .. } => is_constant(r_expr),
-        // ExprKind::Unary { ref right, .. } => is_constant(&right),
-        _ => false,
+        // address of a function
+        ExprKind::Unary {
+            ref token,
+            ref right,
+        } if token.token == TokenType::Amp && matches!(right.kind, ExprKind::Ident(_)) => {
+            right.type_decl.as_ref().is_some_and(|t| t.is_func())
+        }
+        _ => constant_value(expr).is_some(),
     }
 }
 
@@ -945,6 +1310,86 @@ fn log_2(x: i32) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{diagnose, parse};
+
+    #[test]
+    fn implicit_function_declarations_only_in_c89() {
+        let source = "int main() {\nreturn puts(\"hi\", 1);\n}";
+
+        assert_eq!(
+            diagnose(source, Std::C99),
+            (vec!["no function puts exists".to_string()], vec![])
+        );
+        assert_eq!(
+            diagnose(source, Std::C89),
+            (
+                vec![],
+                vec!["implicit declaration of function 'puts'".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn function_definition_inherits_static_linkage() {
+        let source = "static int f();\nint f() { return 1; }\nint main() {}";
+        let mut statements = parse(source);
+        let (errors, _) = TypeChecker::new(Std::C99).diagnose(&mut statements);
+
+        assert!(errors.is_empty());
+        assert!(matches!(
+            statements[1],
+            Stmt::Function(.., Some(StorageClass::Static), _)
+        ));
+        assert_eq!(
+            diagnose("int f();\nstatic int f();\nint main() {}", Std::C99).0,
+            vec!["Static declaration of 'f' follows non-static declaration".to_string()]
+        );
+    }
+
+    #[test]
+    fn read_only_objects() {
+        let check = |body: &str| diagnose(&format!("int main() {{\n{}\n}}", body), Std::C99).0;
+
+        assert_eq!(
+            check("const int a = 1;\na = 2;"),
+            vec!["Can't modify read-only variable 'a'".to_string()]
+        );
+        assert_eq!(
+            check("int a;\nconst int *p = &a;\n(*p)++;"),
+            vec!["Can't modify read-only location".to_string()]
+        );
+        assert_eq!(
+            check("const char *s = \"hi\";\nchar *t = s;"),
+            vec!["Can't assign to type 'char*' with type 'const char*'".to_string()]
+        );
+        assert!(
+            check("char *s = \"hi\";\nconst char *t = s;\nchar *const u = s;\nt = u;").is_empty()
+        );
+    }
+
+    #[test]
+    fn no_arithmetic_on_function_pointers() {
+        let check = |expr: &str| {
+            diagnose(
+                &format!(
+                    "int f();\nint main() {{\nint (*fp)() = f;\nlong x = {};\n}}",
+                    expr
+                ),
+                Std::C99,
+            )
+            .0
+        };
+
+        assert_eq!(
+            check("fp - f"),
+            vec!["invalid binary expression: 'int()*' '-' 'int()*'".to_string()]
+        );
+        assert_eq!(
+            check("fp + 1"),
+            vec!["invalid binary expression: 'int()*' '+' 'int'".to_string()]
+        );
+        assert!(check("(fp == f) + (fp != f) + (fp < f)").is_empty());
+    }
 
     #[test]
     fn alignes_stack1() {